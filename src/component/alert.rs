@@ -7,6 +7,8 @@ use gtk::prelude::*;
 use relm4::prelude::*;
 use relm4::MessageBroker;
 
+use super::app::windows;
+
 pub static ERROR_BROKER: MessageBroker<AlertMsg> = MessageBroker::new();
 
 #[derive(Debug)]
@@ -32,6 +34,8 @@ impl SimpleComponent for AlertModel {
         dialog = gtk::MessageDialog {
             set_message_type: gtk::MessageType::Error,
             #[watch]
+            set_transient_for: windows::active_window().as_ref(),
+            #[watch]
             set_visible: model.is_active,
             connect_response[sender] => move |_, _| {
                 sender.input(AlertMsg::Response);
@@ -1,62 +1,283 @@
 //! Application entrypoint.
 
+use std::cell::RefCell;
 use std::convert::identity;
-use std::path::{self, PathBuf};
+use std::rc::Rc;
 
-use gtk::{gio, glib, prelude::*};
-use relm4::actions::{RelmAction, RelmActionGroup};
+use gtk::{gdk, gio, glib, prelude::*};
+use relm4::actions::{ActionName, RelmAction, RelmActionGroup};
 use relm4::factory::FactoryVecDeque;
 use relm4::prelude::*;
 use tracing::*;
 
-use crate::config::State;
-use crate::ops::Progress;
+use crate::config::{Config, DirectorySort, State, TabState, ViewMode, WindowState};
+use crate::ops::journal::OperationId;
+use crate::ops::{self, Progress, ProgressUnit};
+use crate::shortcuts;
+use crate::util;
 
-use super::alert::{AlertModel, AlertMsg, ERROR_BROKER};
-use super::directory_list::{Directory, Selection};
-use super::file_preview::{FilePreviewModel, FilePreviewMsg};
+use super::alert::{AlertMsg, ERROR_BROKER};
+use super::browser::{Browser, BrowserInit, BrowserMsg};
+use super::directory_list::Selection;
 use super::mount::{Mount, MountMsg};
+use super::path_bar::{PathBarModel, PathBarMsg};
 use super::places_sidebar::PlacesSidebarModel;
+use super::preferences::{Preferences, PreferencesMsg};
 use super::transfer_progress::{NewTransfer, TransferProgress, TransferProgressMsg};
 
+mod file_manager1;
+pub(super) mod windows;
+
+/// Button number identifying the back button on a mouse.
+const BUTTON_BACK: u32 = 8;
+
+/// Button number identifying the forward button on a mouse.
+const BUTTON_FORWARD: u32 = 9;
+
+/// The location that a window or tab is opened at.
+#[derive(Debug)]
+pub struct AppInit {
+    /// The file to select and preview, or the directory to list.
+    file: gio::File,
+
+    /// The directory listed by the leftmost column. Every directory between the root and the file
+    /// is listed. If `None`, or if the file is not beneath it, the root is the directory or the
+    /// parent of the file.
+    root: Option<gio::File>,
+
+    /// Other files in the same directory as the file, which are selected along with it.
+    siblings: Vec<gio::File>,
+
+    /// A window from the previous session, which is restored instead of opening the file.
+    session: Option<WindowState>,
+}
+
+impl AppInit {
+    pub fn new(file: gio::File) -> Self {
+        AppInit {
+            file,
+            root: None,
+            siblings: vec![],
+            session: None,
+        }
+    }
+
+    /// List every directory from the given root to the file.
+    pub fn with_root(self, root: gio::File) -> Self {
+        AppInit {
+            root: Some(root),
+            ..self
+        }
+    }
+
+    /// Select another file in the same directory as the file, along with the file.
+    pub fn selecting(mut self, sibling: gio::File) -> Self {
+        self.siblings.push(sibling);
+        self
+    }
+
+    fn restoring(window: WindowState) -> Self {
+        AppInit {
+            session: Some(window),
+            ..AppInit::new(gio::File::for_path(glib::home_dir()))
+        }
+    }
+
+    /// Returns the initial state of a browser at the location.
+    fn resolve(self) -> BrowserInit {
+        let AppInit {
+            file,
+            root,
+            siblings,
+            ..
+        } = self;
+
+        let mut init = match root {
+            Some(root) if file.equal(&root) => BrowserInit::new(root),
+            Some(root) if file.has_prefix(&root) => BrowserInit::new(root).selecting(file),
+            root => {
+                if let Some(root) = root {
+                    warn!("{} is not beneath the root {}", file.uri(), root.uri());
+                }
+
+                match file.query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE) {
+                    gio::FileType::Directory => BrowserInit::new(file),
+                    _ => match file.parent() {
+                        Some(parent) => BrowserInit::new(parent).selecting(file),
+                        None => BrowserInit::new(file),
+                    },
+                }
+            }
+        };
+
+        // The siblings can only be selected if the file is.
+        if !init.selection.is_empty() {
+            init.selection.extend(siblings);
+        }
+
+        init
+    }
+}
+
 #[derive(Debug)]
 pub struct AppModel {
-    /// The directory listed by the leftmost column.
-    root: gio::File,
+    /// The browser in each tab, in no particular order.
+    browsers: Vec<Controller<Browser>>,
 
-    /// The directory listings. This factory acts as a stack, where new directories are pushed and
-    /// popped relative to the root as the user clicks on new directory entries.
-    directories: FactoryVecDeque<Directory>,
+    tab_view: adw::TabView,
 
     /// Displays the progress of ongoing file operations.
     progress: FactoryVecDeque<TransferProgress>,
 
-    error_alert: Controller<AlertModel>,
-    file_preview: Controller<FilePreviewModel>,
     mount: Controller<Mount>,
+    preferences: Controller<Preferences>,
+    path_bar: Controller<PathBarModel>,
     _places_sidebar: Controller<PlacesSidebarModel>,
 
-    /// Whether the directory panes scroll window should update its scroll position to the upper
-    /// bound on the next view update.
-    update_directory_scroll_position: bool,
+    back_action: gio::SimpleAction,
+    forward_action: gio::SimpleAction,
+    show_hidden_action: gio::SimpleAction,
+    view_mode_action: gio::SimpleAction,
 
-    state: State,
+    state: Rc<RefCell<State>>,
 }
 
 impl AppModel {
-    /// Returns the deepest directory that is listed (the rightmost listing).
-    pub fn last_dir(&self) -> gio::File {
-        self.directories
-            .back()
-            .expect("there must be at least one directory listed")
-            .dir()
+    /// Open a new window at the given location.
+    pub fn open(init: AppInit) {
+        let mut controller = AppModel::builder().launch(init).detach();
+        let window = controller.widget().clone();
+        controller.detach_runtime();
+
+        relm4::main_application().add_window(&window);
+        window.present();
+    }
+
+    /// Open the windows that were open when fm last exited. Returns `false` if there were none.
+    pub fn restore_session() -> bool {
+        let session = windows::state()
+            .borrow()
+            .windows
+            .iter()
+            .filter(|window| !window.tabs.is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Open the most recently focused window last, so that it's on top.
+        for window in session.iter().rev() {
+            AppModel::open(AppInit::restoring(window.clone()));
+        }
+
+        !session.is_empty()
+    }
+
+    /// Open a new tab at the given location in the most recently focused window, or a new window
+    /// if there are none.
+    pub fn open_in_tab(init: AppInit) {
+        match windows::active_sender() {
+            Some(sender) => sender.emit(AppMsg::ShowInNewTab(init)),
+            None => AppModel::open(init),
+        }
+    }
+
+    /// Open a new tab containing a browser, and return its page.
+    fn open_tab(&mut self, init: BrowserInit, sender: &ComponentSender<Self>) -> adw::TabPage {
+        let browser = Browser::builder()
+            .launch(init)
+            .forward(sender.input_sender(), identity);
+
+        let page = self.tab_view.append(browser.widget());
+        self.browsers.push(browser);
+        self.update_tab_titles();
+
+        page
+    }
+
+    /// Returns the browser displayed by a tab page.
+    fn browser_for_page(&self, page: &adw::TabPage) -> Option<&Controller<Browser>> {
+        let child = page.child();
+
+        self.browsers
+            .iter()
+            .find(|browser| browser.widget().upcast_ref::<gtk::Widget>() == &child)
+    }
+
+    /// Returns the browser in the selected tab.
+    fn selected_browser(&self) -> Option<&Controller<Browser>> {
+        self.tab_view
+            .selected_page()
+            .and_then(|page| self.browser_for_page(&page))
+    }
+
+    /// Returns the state of the window, so that it can be restored later.
+    fn window_state(&self, widgets: &AppWidgets) -> WindowState {
+        let (width, height) = widgets.main_window.default_size();
+
+        let tabs = (0..self.tab_view.n_pages())
+            .filter_map(|n| self.browser_for_page(&self.tab_view.nth_page(n)))
+            .map(|browser| browser.model().tab_state())
+            .collect();
+
+        WindowState {
+            width,
+            height,
+            is_maximized: widgets.main_window.is_maximized(),
+            sidebar_revealed: widgets.flap.reveals_flap(),
+            tabs,
+            selected_tab: self
+                .tab_view
+                .selected_page()
+                .map_or(0, |page| self.tab_view.page_position(&page) as usize),
+        }
+    }
+
+    /// Title each tab after the directory displayed by its browser.
+    fn update_tab_titles(&self) {
+        for browser in &self.browsers {
+            let current_dir = browser.model().current_dir();
+            let page = self.tab_view.page(browser.widget());
+
+            page.set_title(&util::file_label(&current_dir));
+            page.set_tooltip(&current_dir.parse_name());
+        }
+    }
+
+    /// Returns the position of a transfer in the list of transfers.
+    fn transfer_index(&self, id: u64) -> Option<usize> {
+        self.progress.iter().position(|child| child.id == id)
     }
 }
 
 #[derive(Debug)]
 pub enum Transfer {
-    New { id: u64, description: String },
+    New {
+        id: u64,
+        description: String,
+
+        /// Whether the transfer can be paused.
+        pausable: bool,
+
+        /// What the progress of the transfer is measured in.
+        unit: ProgressUnit,
+    },
     Progress(Progress),
+
+    /// The transfer has finished, and will not make further progress.
+    Finished {
+        id: u64,
+        status: TransferStatus,
+    },
+
+    /// Remove a finished transfer from the list of transfers.
+    Dismiss(u64),
+}
+
+/// The final outcome of a transfer.
+#[derive(Debug, Clone)]
+pub enum TransferStatus {
+    Completed,
+    Failed(String),
+    Cancelled,
 }
 
 #[derive(Debug)]
@@ -84,6 +305,106 @@ pub enum AppMsg {
     /// Display a toast.
     Toast(String),
 
+    /// Display a toast offering to undo an operation.
+    UndoToast {
+        message: String,
+        operation: OperationId,
+    },
+
+    /// Undo the most recent file operation.
+    Undo,
+
+    /// Undo a specific file operation.
+    UndoOperation(OperationId),
+
+    /// Redo the most recently undone file operation.
+    Redo,
+
+    /// Permanently delete files. The user has already confirmed the deletion.
+    Delete(Vec<gio::File>),
+
+    /// The sort order of a directory listing was changed.
+    DirectorySortChanged { dir: gio::File, sort: DirectorySort },
+
+    /// Whether hidden and backup files are listed in every directory. The choice is saved to the
+    /// configuration file.
+    SetShowHidden(bool),
+
+    /// How the directories of the selected tab are displayed. The choice is remembered by the tab,
+    /// and restored along with it.
+    SetViewMode(ViewMode),
+
+    /// The size of the icons in the icon view, which is saved to the configuration file once the
+    /// user stops zooming.
+    SetIconSize(i32),
+
+    /// The configuration was changed, either by the user or by editing the configuration file.
+    ConfigChanged,
+
+    /// Search for files beneath the root, displaying the results in place of the directory
+    /// listings.
+    StartSearch,
+
+    /// Close the search results and display the directory listings again.
+    StopSearch,
+
+    /// Close the search, and list the directories leading from the root to the given file so that
+    /// it's selected.
+    RevealFile(gio::File),
+
+    /// A directory was opened from the listing of its parent. If a single directory is listed at a
+    /// time, its listing is shown in place of the parent's.
+    OpenDirectory(gio::File),
+
+    /// The location displayed by a tab changed, or a different tab was selected.
+    TabChanged,
+
+    /// Open a new tab at the same location as the selected tab.
+    NewTab,
+
+    /// Open a new window at the same location as the selected tab.
+    NewWindow,
+
+    /// Open a new tab listing the given directory, without selecting it.
+    OpenInNewTab(gio::File),
+
+    /// Open a new tab at the given location, select it, and present the window.
+    ShowInNewTab(AppInit),
+
+    /// Close the selected tab, or the window if it's the only tab.
+    CloseTab,
+
+    /// A tab was closed. The child of its page is the browser's widget.
+    TabClosed(gtk::Widget),
+
+    /// Files were dropped on a tab, and should be transferred into its deepest listed directory.
+    DropOnTab {
+        page: adw::TabPage,
+        files: Vec<gio::File>,
+        action: gdk::DragAction,
+    },
+
+    /// Switch the path bar to a text entry, so that a location can be typed.
+    EditLocation,
+
+    /// Return to the previously visited location.
+    Back,
+
+    /// Return to the location that was visited before going back.
+    Forward,
+
+    /// The window was closed. Record its final state and destroy the window.
+    Close,
+
+    /// Display the preferences window.
+    Preferences,
+
+    /// Display the keyboard shortcuts of the application.
+    ShowShortcuts,
+
+    /// Show or hide the places sidebar.
+    ToggleSidebar,
+
     /// Display the about window.
     About,
 
@@ -94,7 +415,7 @@ pub enum AppMsg {
 #[relm4::component(pub)]
 impl Component for AppModel {
     type Widgets = AppWidgets;
-    type Init = PathBuf;
+    type Init = AppInit;
     type Input = AppMsg;
     type Output = ();
     type CommandOutput = ();
@@ -102,7 +423,7 @@ impl Component for AppModel {
     view! {
         #[name = "main_window"]
         adw::Window {
-            set_default_size: (state.width, state.height),
+            set_default_size: (geometry.width, geometry.height),
             set_title: Some("fm"),
 
             #[name = "toast_overlay"]
@@ -111,6 +432,37 @@ impl Component for AppModel {
                     set_orientation: gtk::Orientation::Vertical,
 
                     adw::HeaderBar {
+                        set_title_widget: Some(path_bar.widget()),
+
+                        pack_start = &gtk::Box {
+                            add_css_class: "linked",
+
+                            gtk::Button {
+                                set_icon_name: "go-previous-symbolic",
+                                set_tooltip_text: Some("Back"),
+                                set_action_name: Some("win.back"),
+                            },
+
+                            gtk::Button {
+                                set_icon_name: "go-next-symbolic",
+                                set_tooltip_text: Some("Forward"),
+                                set_action_name: Some("win.forward"),
+                            },
+                        },
+
+                        #[name = "search_button"]
+                        pack_start = &gtk::ToggleButton {
+                            set_icon_name: "system-search-symbolic",
+                            set_tooltip_text: Some("Search"),
+                            connect_toggled[sender] => move |this| {
+                                sender.input(if this.is_active() {
+                                    AppMsg::StartSearch
+                                } else {
+                                    AppMsg::StopSearch
+                                });
+                            } @search_toggled_handler,
+                        },
+
                         pack_end = &gtk::MenuButton {
                             set_icon_name: "open-menu-symbolic",
                             set_menu_model: Some(&primary_menu),
@@ -135,7 +487,10 @@ impl Component for AppModel {
                         },
                     },
 
+                    #[name = "flap"]
                     adw::Flap {
+                        set_reveal_flap: geometry.sidebar_revealed,
+
                         #[wrap(Some)]
                         set_flap = &gtk::Box {
                             set_orientation: gtk::Orientation::Vertical,
@@ -149,14 +504,22 @@ impl Component for AppModel {
                         set_content = &gtk::Box {
                             set_orientation: gtk::Orientation::Vertical,
 
-                            #[name = "directory_panes_scroller"]
-                            gtk::ScrolledWindow {
-                                set_hexpand: true,
+                            #[name = "tab_bar"]
+                            adw::TabBar {
+                                set_view: Some(&tab_view),
+                            },
+
+                            #[name = "tab_view"]
+                            adw::TabView {
                                 set_vexpand: true,
 
-                                #[name = "directory_panes"]
-                                panel::Paned {
-                                    append: file_preview.widget(),
+                                connect_selected_page_notify[sender] => move |_| {
+                                    sender.input(AppMsg::TabChanged);
+                                },
+
+                                connect_close_page[sender] => move |_, page| {
+                                    sender.input(AppMsg::TabClosed(page.child()));
+                                    glib::Propagation::Proceed
                                 },
                             },
                         },
@@ -164,67 +527,104 @@ impl Component for AppModel {
                 },
             },
 
-            connect_close_request => move |this| {
-                let (width, height) = this.default_size();
-                let is_maximized = this.is_maximized();
-
-                let new_state = State {
-                    width,
-                    height,
-                    is_maximized,
-                };
-
-                if let Err(e) = new_state.write() {
-                    warn!("unable to write application state: {}", e);
-                }
-
-                glib::signal::Propagation::Proceed
+            // The window is destroyed once its final state has been recorded.
+            connect_close_request[sender] => move |_| {
+                sender.input(AppMsg::Close);
+                glib::signal::Propagation::Stop
             }
         }
     }
 
     menu! {
         primary_menu: {
+            section! {
+                "New Window" => NewWindowAction,
+            },
+            section! {
+                "Show Hidden Files" => ShowHiddenAction,
+            },
+            section! {
+                "Columns" => ViewModeAction(ViewMode::Columns.id().to_owned()),
+                "Details" => ViewModeAction(ViewMode::Details.id().to_owned()),
+                "Icons" => ViewModeAction(ViewMode::Icons.id().to_owned()),
+            },
             section! {
                 "Connect to server..." => MountAction,
             },
             section! {
+                "Preferences" => PreferencesAction,
+                "Keyboard Shortcuts" => ShowShortcutsAction,
                 "About" => AboutAction,
             },
         }
     }
 
-    fn init(dir: PathBuf, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
-        let dir = if !dir.is_dir() {
-            dir.parent().unwrap_or(&dir)
-        } else {
-            &dir
+    fn init(
+        mut init: AppInit,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let state = windows::state();
+
+        let (geometry, tabs) = match init.session.take() {
+            Some(window) => {
+                let tabs = window.tabs.iter().map(restore_tab).collect::<Vec<_>>();
+                (window, tabs)
+            }
+            None => {
+                let geometry = state.borrow().window(windows::count());
+                (geometry, vec![init.resolve()])
+            }
         };
 
-        let dir = gio::File::for_path(dir);
-
-        let state = State::read()
-            .map_err(|e| {
-                warn!("unable to read application state: {}", e);
-                e
-            })
-            .unwrap_or_default();
-
-        info!("starting with application state: {:?}", state);
-
-        let file_preview = FilePreviewModel::builder().launch(()).detach();
+        let dir = tabs[0].root.clone();
 
         let places_sidebar = PlacesSidebarModel::builder()
             .launch(dir.clone())
             .forward(sender.input_sender(), identity);
 
+        let path_bar = PathBarModel::builder()
+            .launch(dir.clone())
+            .forward(sender.input_sender(), identity);
+
         let widgets = view_output!();
 
+        let sender_ = sender.clone();
+        let back_action: RelmAction<BackAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::Back);
+        });
+        back_action.set_enabled(false);
+
+        let sender_ = sender.clone();
+        let forward_action: RelmAction<ForwardAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::Forward);
+        });
+        forward_action.set_enabled(false);
+
+        let sender_ = sender.clone();
+        let show_hidden_action: RelmAction<ShowHiddenAction> = RelmAction::new_stateful(
+            &windows::config().show_hidden,
+            move |_, state: &mut bool| {
+                *state = !*state;
+                sender_.input(AppMsg::SetShowHidden(*state));
+            },
+        );
+
+        let sender_ = sender.clone();
+        let view_mode_action: RelmAction<ViewModeAction> =
+            RelmAction::new_stateful_with_target_value(
+                &windows::config().view_mode.id().to_owned(),
+                move |_, state: &mut String, id: String| {
+                    if let Some(mode) = ViewMode::from_id(&id) {
+                        *state = id;
+                        sender_.input(AppMsg::SetViewMode(mode));
+                    }
+                },
+            );
+
         let mut model = AppModel {
-            root: dir.clone(),
-            directories: FactoryVecDeque::builder()
-                .launch(widgets.directory_panes.clone())
-                .forward(sender.input_sender(), identity),
+            browsers: vec![],
+            tab_view: widgets.tab_view.clone(),
             progress: FactoryVecDeque::builder()
                 .launch(widgets.transfer_progress.clone())
                 .forward(sender.input_sender(), identity),
@@ -232,43 +632,164 @@ impl Component for AppModel {
                 .transient_for(&widgets.main_window)
                 .launch(())
                 .forward(sender.input_sender(), identity),
-            error_alert: AlertModel::builder()
-                .transient_for(widgets.main_window.clone())
-                .launch_with_broker((), &ERROR_BROKER)
+            preferences: Preferences::builder()
+                .transient_for(&widgets.main_window)
+                .launch(())
                 .detach(),
-            file_preview,
+            path_bar,
             _places_sidebar: places_sidebar,
-            update_directory_scroll_position: false,
+            back_action: back_action.gio_action().clone(),
+            forward_action: forward_action.gio_action().clone(),
+            show_hidden_action: show_hidden_action.gio_action().clone(),
+            view_mode_action: view_mode_action.gio_action().clone(),
             state,
         };
 
-        model.directories.guard().push_back(dir);
+        for tab in tabs {
+            model.open_tab(tab, &sender);
+        }
+
+        if geometry.selected_tab < model.tab_view.n_pages() as usize {
+            let page = model.tab_view.nth_page(geometry.selected_tab as i32);
+            model.tab_view.set_selected_page(&page);
+        }
+
+        if geometry.is_maximized {
+            widgets.main_window.maximize();
+        }
+
+        windows::register(&widgets.main_window, sender.input_sender().clone());
+
+        widgets.tab_bar.setup_extra_drop_target(
+            gdk::DragAction::COPY | gdk::DragAction::MOVE | gdk::DragAction::LINK,
+            &[gdk::FileList::static_type(), gio::File::static_type()],
+        );
+        let sender_ = sender.clone();
+        widgets
+            .tab_bar
+            .connect_extra_drag_drop(move |this, page, value| {
+                // The tab bar's drop target isn't exposed, so the modifiers are read from the
+                // keyboard instead.
+                let modifiers = this
+                    .display()
+                    .default_seat()
+                    .and_then(|seat| seat.keyboard())
+                    .map_or(gdk::ModifierType::empty(), |keyboard| {
+                        keyboard.modifier_state()
+                    });
+
+                sender_.input(AppMsg::DropOnTab {
+                    page: page.clone(),
+                    files: ops::dropped_files(value),
+                    action: ops::drop_action_for_modifiers(modifiers),
+                });
+                true
+            });
 
         let mut group = RelmActionGroup::<WindowActionGroup>::new();
 
+        let sender_ = sender.clone();
+        let preferences_action: RelmAction<PreferencesAction> =
+            RelmAction::new_stateless(move |_| {
+                sender_.input(AppMsg::Preferences);
+            });
+        group.add_action(preferences_action);
+
         let sender_ = sender.clone();
         let about_action: RelmAction<AboutAction> = RelmAction::new_stateless(move |_| {
             sender_.input(AppMsg::About);
         });
         group.add_action(about_action);
 
+        let sender_ = sender.clone();
         let mount_action: RelmAction<MountAction> = RelmAction::new_stateless(move |_| {
-            sender.input(AppMsg::Mount);
+            sender_.input(AppMsg::Mount);
         });
         group.add_action(mount_action);
 
+        let sender_ = sender.clone();
+        let undo_action: RelmAction<UndoAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::Undo);
+        });
+        group.add_action(undo_action);
+
+        let sender_ = sender.clone();
+        let undo_operation_action: RelmAction<UndoOperationAction> =
+            RelmAction::new_with_target_value(move |_, operation| {
+                sender_.input(AppMsg::UndoOperation(operation));
+            });
+        group.add_action(undo_operation_action);
+
+        let sender_ = sender.clone();
+        let redo_action: RelmAction<RedoAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::Redo);
+        });
+        group.add_action(redo_action);
+
+        let sender_ = sender.clone();
+        let search_action: RelmAction<SearchAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::StartSearch);
+        });
+        group.add_action(search_action);
+
+        let sender_ = sender.clone();
+        let location_action: RelmAction<LocationAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::EditLocation);
+        });
+        group.add_action(location_action);
+
+        let sender_ = sender.clone();
+        let new_tab_action: RelmAction<NewTabAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::NewTab);
+        });
+        group.add_action(new_tab_action);
+
+        let sender_ = sender.clone();
+        let close_tab_action: RelmAction<CloseTabAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::CloseTab);
+        });
+        group.add_action(close_tab_action);
+
+        let sender_ = sender.clone();
+        let new_window_action: RelmAction<NewWindowAction> = RelmAction::new_stateless(move |_| {
+            sender_.input(AppMsg::NewWindow);
+        });
+        group.add_action(new_window_action);
+
+        group.add_action(back_action);
+        group.add_action(forward_action);
+        group.add_action(show_hidden_action);
+        group.add_action(view_mode_action);
+
+        let sender_ = sender.clone();
+        let toggle_sidebar_action: RelmAction<ToggleSidebarAction> =
+            RelmAction::new_stateless(move |_| {
+                sender_.input(AppMsg::ToggleSidebar);
+            });
+        group.add_action(toggle_sidebar_action);
+
+        let sender_ = sender.clone();
+        let show_shortcuts_action: RelmAction<ShowShortcutsAction> =
+            RelmAction::new_stateless(move |_| {
+                sender_.input(AppMsg::ShowShortcuts);
+            });
+        group.add_action(show_shortcuts_action);
+
         widgets
             .main_window
             .insert_action_group("win", Some(&group.into_action_group()));
 
-        // TODO: There's sometimes a delay in updating the adjustment upper bound when a new pane
-        // is added, causing this code to not trigger at the right time. Needs more investigation.
-        widgets
-            .directory_panes_scroller
-            .hadjustment()
-            .connect_notify(Some("upper"), |this, _| {
-                set_adjustment_to_upper_bound(this);
-            });
+        let navigation_controller = gtk::GestureClick::builder().button(0).build();
+        navigation_controller.connect_pressed(move |gesture, _, _, _| {
+            match gesture.current_button() {
+                BUTTON_BACK => sender.input(AppMsg::Back),
+                BUTTON_FORWARD => sender.input(AppMsg::Forward),
+                _ => return,
+            }
+
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+        });
+        widgets.main_window.add_controller(navigation_controller);
 
         ComponentParts { model, widgets }
     }
@@ -277,122 +798,198 @@ impl Component for AppModel {
         &mut self,
         widgets: &mut Self::Widgets,
         msg: Self::Input,
-        _sender: ComponentSender<Self>,
+        sender: ComponentSender<Self>,
         _: &Self::Root,
     ) {
-        self.update_directory_scroll_position = false;
+        // Navigation is handled by the browser in the selected tab.
+        let msg = match BrowserMsg::try_from(msg) {
+            Ok(msg) => {
+                if let Some(browser) = self.selected_browser() {
+                    browser.emit(msg);
+                }
+                return;
+            }
+            Err(msg) => msg,
+        };
 
         match msg {
             AppMsg::Error(err) => {
-                self.error_alert.emit(AlertMsg::Show {
+                ERROR_BROKER.send(AlertMsg::Show {
                     text: err.to_string(),
                 });
             }
-            AppMsg::NewSelection(Selection::Files(selection)) => {
-                let mut last_dir = self.last_dir();
-
-                let file = if selection.files.len() == 1 {
-                    selection.files.first().unwrap()
-                } else {
-                    &selection.parent
-                };
-
-                let file_path = match glib::Uri::split(&file.uri(), glib::UriFlags::NONE) {
-                    Ok((_, _, _, _, path, _, _)) => PathBuf::from(&path),
-                    Err(e) => {
-                        warn!("unable to parse URI: {}", e);
-                        return;
+            AppMsg::Transfer(transfer) => {
+                match transfer {
+                    Transfer::New {
+                        id,
+                        description,
+                        pausable,
+                        unit,
+                    } => {
+                        self.progress.guard().push_back(NewTransfer {
+                            id,
+                            description,
+                            pausable,
+                            unit,
+                        });
                     }
-                };
-
-                let last_dir_path = glib::Uri::split(&last_dir.uri(), glib::UriFlags::NONE)
-                    .map(|(_, _, _, _, path, _, _)| path)
-                    .expect("last visited directory must be a valid URI");
-
-                let diff = pathdiff::diff_paths(file_path, &last_dir_path)
-                    .expect("new selection must be relative to the listed directories");
-
-                info!(
-                    "new selection: {:?}, last dir: {}, diff: {}",
-                    selection,
-                    last_dir.uri(),
-                    diff.display()
-                );
-
-                let mut directories = self.directories.guard();
-
-                for component in diff.components() {
-                    match component {
-                        path::Component::ParentDir => {
-                            directories.pop_back();
-                            last_dir = last_dir.parent().unwrap();
+                    Transfer::Progress(progress) => {
+                        if let Some(idx) = self.transfer_index(progress.id) {
+                            self.progress
+                                .send(idx, TransferProgressMsg::Update(progress));
                         }
-                        path::Component::Normal(name) => {
-                            let component_file = last_dir.child(name);
-                            if component_file.query_file_type(
-                                gio::FileQueryInfoFlags::NONE,
-                                gio::Cancellable::NONE,
-                            ) == gio::FileType::Directory
-                            {
-                                directories.push_back(component_file.clone());
-                                last_dir = component_file;
-                            }
+                    }
+                    Transfer::Finished { id, status } => {
+                        if let Some(idx) = self.transfer_index(id) {
+                            self.progress
+                                .send(idx, TransferProgressMsg::Finished(status));
+                        }
+                    }
+                    Transfer::Dismiss(id) => {
+                        if let Some(idx) = self.transfer_index(id) {
+                            self.progress.guard().remove(idx);
                         }
-                        _ => unreachable!("unexpected path component: {:?}", component),
                     }
                 }
 
-                self.file_preview
-                    .emit(FilePreviewMsg::NewSelection(selection));
-
-                self.update_directory_scroll_position = true;
+                widgets
+                    .transfer_progress_button
+                    .set_visible(!self.progress.is_empty());
             }
-            AppMsg::NewSelection(Selection::None) => {
-                self.file_preview.emit(FilePreviewMsg::Hide);
-
-                self.update_directory_scroll_position = true;
+            AppMsg::Toast(message) => {
+                widgets.toast_overlay.add_toast(adw::Toast::new(&message));
             }
-            AppMsg::NewRoot(new_root) => {
-                info!("new root: {:?}", new_root);
-
-                let mut directories = self.directories.guard();
-
-                directories.clear();
-
-                self.root = new_root;
-                directories.push_back(self.root.clone());
+            AppMsg::UndoToast { message, operation } => {
+                let toast = adw::Toast::new(&message);
+                toast.set_button_label(Some("Undo"));
+                toast.set_action_name(Some("win.undo-operation"));
+                toast.set_action_target_value(Some(&operation.to_variant()));
+                widgets.toast_overlay.add_toast(toast);
+            }
+            AppMsg::Undo => {
+                relm4::spawn_local(ops::journal::undo(sender.input_sender().clone()));
+            }
+            AppMsg::UndoOperation(operation) => {
+                relm4::spawn_local(ops::journal::undo_operation(
+                    operation,
+                    sender.input_sender().clone(),
+                ));
+            }
+            AppMsg::Redo => {
+                relm4::spawn_local(ops::journal::redo(sender.input_sender().clone()));
+            }
+            AppMsg::Delete(files) => {
+                relm4::spawn_local(ops::delete(files, sender.input_sender().clone()));
+            }
+            AppMsg::DirectorySortChanged { dir, sort } => {
+                self.state.borrow_mut().set_directory_sort(
+                    dir.uri().into(),
+                    sort,
+                    windows::config().default_sort,
+                );
+            }
+            AppMsg::SetShowHidden(show_hidden) => {
+                let mut config = Config::clone(&windows::config());
+                config.show_hidden = show_hidden;
+                windows::set_config(config);
+            }
+            AppMsg::SetViewMode(view_mode) => {
+                if let Some(browser) = self.selected_browser() {
+                    browser.emit(BrowserMsg::SetViewMode(view_mode));
+                }
+            }
+            AppMsg::SetIconSize(icon_size) => {
+                let mut config = Config::clone(&windows::config());
+                config.icon_size = icon_size;
+                windows::set_config_deferred(config);
+            }
+            AppMsg::ConfigChanged => {
+                let config = windows::config();
+                self.show_hidden_action
+                    .set_state(&config.show_hidden.to_variant());
 
-                self.file_preview.emit(FilePreviewMsg::Hide);
+                for browser in &self.browsers {
+                    browser.emit(BrowserMsg::ConfigChanged);
+                }
 
-                self.update_directory_scroll_position = true;
+                self.preferences.emit(PreferencesMsg::ConfigChanged);
             }
-            AppMsg::Transfer(transfer) => {
-                match transfer {
-                    Transfer::New { id, description } => {
-                        self.progress
-                            .guard()
-                            .push_back(NewTransfer { id, description });
-                    }
-                    Transfer::Progress(progress) => {
-                        let idx = self
-                            .progress
-                            .iter()
-                            .position(|child| child.id == progress.id);
-
-                        if let Some(idx) = idx {
-                            self.progress
-                                .send(idx, TransferProgressMsg::Update(progress));
-                        }
+            AppMsg::TabChanged => self.update_tab_titles(),
+            AppMsg::NewTab => {
+                if let Some(last_dir) = self
+                    .selected_browser()
+                    .map(|browser| browser.model().last_dir())
+                {
+                    let page = self.open_tab(BrowserInit::new(last_dir), &sender);
+                    self.tab_view.set_selected_page(&page);
+                }
+            }
+            AppMsg::NewWindow => {
+                if let Some(last_dir) = self
+                    .selected_browser()
+                    .map(|browser| browser.model().last_dir())
+                {
+                    AppModel::open(AppInit::new(last_dir));
+                }
+            }
+            AppMsg::OpenInNewTab(dir) => {
+                self.open_tab(BrowserInit::new(dir), &sender);
+            }
+            AppMsg::ShowInNewTab(init) => {
+                let page = self.open_tab(init.resolve(), &sender);
+                self.tab_view.set_selected_page(&page);
+                widgets.main_window.present();
+            }
+            AppMsg::CloseTab => {
+                if self.tab_view.n_pages() > 1 {
+                    if let Some(page) = self.tab_view.selected_page() {
+                        self.tab_view.close_page(&page);
                     }
+                } else {
+                    widgets.main_window.close();
                 }
+            }
+            AppMsg::TabClosed(child) => {
+                self.browsers
+                    .retain(|browser| browser.widget().upcast_ref::<gtk::Widget>() != &child);
 
-                if !self.progress.is_empty() {
-                    widgets.transfer_progress_button.set_visible(true);
+                if self.browsers.is_empty() {
+                    widgets.main_window.close();
                 }
             }
-            AppMsg::Toast(message) => {
-                widgets.toast_overlay.add_toast(adw::Toast::new(&message));
+            AppMsg::DropOnTab {
+                page,
+                files,
+                action,
+            } => {
+                if let Some(browser) = self.browser_for_page(&page) {
+                    let destination = browser.model().last_dir();
+
+                    let transfer = ops::transfer_files(
+                        files,
+                        destination,
+                        action,
+                        sender.input_sender().clone(),
+                    );
+
+                    relm4::spawn_local(async move {
+                        transfer.await;
+                    });
+                }
+            }
+            AppMsg::EditLocation => self.path_bar.emit(PathBarMsg::Edit),
+            AppMsg::Close => {
+                windows::update(&widgets.main_window, self.window_state(widgets));
+                windows::unregister(&widgets.main_window);
+                widgets.main_window.destroy();
             }
+            AppMsg::Preferences => self.preferences.emit(PreferencesMsg::Show),
+            AppMsg::ShowShortcuts => {
+                let shortcuts_window = shortcuts::window(&windows::config());
+                shortcuts_window.set_transient_for(Some(&widgets.main_window));
+                shortcuts_window.present();
+            }
+            AppMsg::ToggleSidebar => widgets.flap.set_reveal_flap(!widgets.flap.reveals_flap()),
             AppMsg::About => {
                 gtk::AboutDialog::builder()
                     .authors(
@@ -413,34 +1010,99 @@ impl Component for AppModel {
             }
             AppMsg::Mount => self.mount.emit(MountMsg::Mount),
         }
-    }
 
-    fn post_view(&self, widgets: &mut Self::Widgets) {
-        if self.state.is_maximized {
-            widgets.main_window.maximize();
+        if let Some(browser) = self.selected_browser() {
+            let browser = browser.model();
+
+            widgets
+                .search_button
+                .block_signal(&widgets.search_toggled_handler);
+            widgets.search_button.set_active(browser.searching());
+            widgets
+                .search_button
+                .unblock_signal(&widgets.search_toggled_handler);
+
+            self.path_bar.emit(PathBarMsg::Update {
+                root: browser.root(),
+                last_dir: browser.current_dir(),
+            });
+
+            self.back_action.set_enabled(browser.can_go_back());
+            self.forward_action.set_enabled(browser.can_go_forward());
+            self.view_mode_action
+                .set_state(&browser.view_mode().id().to_variant());
         }
 
-        if self.update_directory_scroll_position {
-            // Although this function is already called whenever the hadjustment changes, we also
-            // sometimes want to scroll when the adjustment doesn't change.
-            //
-            // Consider the user selecting a new directory entry on a partially obscured panel. The
-            // adjustment won't change, because the total number of panels is the same. However,
-            // we still want to scroll over to it because it's new information that the user wants
-            // to see.
-            set_adjustment_to_upper_bound(&widgets.directory_panes_scroller.hadjustment());
+        windows::update(&widgets.main_window, self.window_state(widgets));
+    }
+}
+
+/// Returns the initial state of a tab from a previous session.
+///
+/// Locations that no longer exist are replaced by their nearest existing ancestor, so that the tab
+/// opens as close as possible to where it was left.
+fn restore_tab(tab: &TabState) -> BrowserInit {
+    let root = util::existing_ancestor(gio::File::for_uri(&tab.root))
+        .unwrap_or_else(|| gio::File::for_path(glib::home_dir()));
+
+    let mut init = BrowserInit::new(root.clone());
+
+    init.selection = tab
+        .selection
+        .iter()
+        .map(|uri| gio::File::for_uri(uri))
+        .filter(|file| file.has_prefix(&root) && file.query_exists(gio::Cancellable::NONE))
+        .collect();
+
+    if init.selection.is_empty() {
+        if let Some(last_dir) = util::existing_ancestor(gio::File::for_uri(&tab.last_dir))
+            .filter(|last_dir| last_dir.has_prefix(&root))
+        {
+            init.selection.push(last_dir);
         }
     }
+
+    init.column_widths = tab.column_widths.clone();
+    init.preview_width = tab.preview_width;
+
+    if let Some(view_mode) = tab.view_mode {
+        init.view_mode = view_mode;
+    }
+
+    init
 }
 
 relm4::new_action_group!(WindowActionGroup, "win");
+relm4::new_stateless_action!(PreferencesAction, WindowActionGroup, "preferences");
+relm4::new_stateless_action!(ShowShortcutsAction, WindowActionGroup, "show-shortcuts");
+relm4::new_stateless_action!(ToggleSidebarAction, WindowActionGroup, "toggle-sidebar");
 relm4::new_stateless_action!(AboutAction, WindowActionGroup, "about");
 relm4::new_stateless_action!(MountAction, WindowActionGroup, "mount");
+relm4::new_stateless_action!(UndoAction, WindowActionGroup, "undo");
+relm4::new_stateless_action!(RedoAction, WindowActionGroup, "redo");
 
-/// Updates the value of an adjustment to its upper bound.
-///
-/// This is used to keep new directories and file information visible inside the directory panes
-/// scroll window as user interacts with the application.
-fn set_adjustment_to_upper_bound(adjustment: &gtk::Adjustment) {
-    adjustment.set_value(adjustment.upper());
+/// Undoes the operation identified by the target, rather than the most recent one.
+struct UndoOperationAction;
+
+impl ActionName for UndoOperationAction {
+    type Group = WindowActionGroup;
+    type Target = OperationId;
+    type State = ();
+
+    const NAME: &'static str = "undo-operation";
 }
+relm4::new_stateless_action!(SearchAction, WindowActionGroup, "search");
+relm4::new_stateless_action!(LocationAction, WindowActionGroup, "location");
+relm4::new_stateless_action!(NewTabAction, WindowActionGroup, "new-tab");
+relm4::new_stateless_action!(CloseTabAction, WindowActionGroup, "close-tab");
+relm4::new_stateless_action!(NewWindowAction, WindowActionGroup, "new-window");
+relm4::new_stateless_action!(BackAction, WindowActionGroup, "back");
+relm4::new_stateless_action!(ForwardAction, WindowActionGroup, "forward");
+relm4::new_stateful_action!(ShowHiddenAction, WindowActionGroup, "show-hidden", (), bool);
+relm4::new_stateful_action!(
+    ViewModeAction,
+    WindowActionGroup,
+    "view-mode",
+    String,
+    String
+);
@@ -0,0 +1,218 @@
+//! Implementation of the `org.freedesktop.FileManager1` D-Bus interface, which other applications
+//! (such as web browsers revealing a download) use to show files in the user's file manager.
+//!
+//! See <https://www.freedesktop.org/wiki/Specifications/file-manager-interface/>.
+
+use std::cell::RefCell;
+
+use relm4::gtk::{gio, glib, prelude::*};
+use tracing::*;
+
+use super::{AppInit, AppModel};
+
+const NAME: &str = "org.freedesktop.FileManager1";
+const OBJECT_PATH: &str = "/org/freedesktop/FileManager1";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.freedesktop.FileManager1">
+    <method name="ShowFolders">
+      <arg type="as" name="URIs" direction="in"/>
+      <arg type="s" name="StartupId" direction="in"/>
+    </method>
+    <method name="ShowItems">
+      <arg type="as" name="URIs" direction="in"/>
+      <arg type="s" name="StartupId" direction="in"/>
+    </method>
+    <method name="ShowItemProperties">
+      <arg type="as" name="URIs" direction="in"/>
+      <arg type="s" name="StartupId" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+thread_local! {
+    /// The pending or granted request for the interface's name, if it was requested.
+    static OWNER: RefCell<Option<gio::OwnerId>> = const { RefCell::new(None) };
+
+    /// The connection on which the interface is served, and its registration.
+    static REGISTRATION: RefCell<Option<(gio::DBusConnection, gio::RegistrationId)>> =
+        const { RefCell::new(None) };
+}
+
+/// Request or release ownership of the interface's name on the session bus.
+///
+/// If another file manager already owns the name, the request is queued, and the interface is
+/// only served once the other file manager releases it.
+pub fn set_enabled(enabled: bool) {
+    OWNER.with(|owner| {
+        let mut owner = owner.borrow_mut();
+
+        if enabled {
+            owner.get_or_insert_with(|| {
+                gio::bus_own_name(
+                    gio::BusType::Session,
+                    NAME,
+                    gio::BusNameOwnerFlags::NONE,
+                    register_object,
+                    |_, name| info!("acquired bus name: {}", name),
+                    |_, name| info!("lost or unable to acquire bus name: {}", name),
+                )
+            });
+        } else if let Some(owner_id) = owner.take() {
+            info!("releasing bus name: {}", NAME);
+            gio::bus_unown_name(owner_id);
+            unregister_object();
+        }
+    });
+}
+
+fn register_object(connection: gio::DBusConnection, _: &str) {
+    let interface = gio::DBusNodeInfo::for_xml(INTERFACE_XML)
+        .expect("interface XML must be valid")
+        .lookup_interface(NAME)
+        .expect("interface XML must describe the interface");
+
+    let registration = connection
+        .register_object(OBJECT_PATH, &interface)
+        .method_call(|_, _, _, _, method, parameters, invocation| {
+            handle_method_call(method, parameters, invocation);
+        })
+        .build();
+
+    match registration {
+        Ok(registration) => {
+            REGISTRATION.with(|current| current.replace(Some((connection, registration))));
+        }
+        Err(e) => warn!("unable to register {}: {}", OBJECT_PATH, e),
+    }
+}
+
+fn unregister_object() {
+    if let Some((connection, registration)) = REGISTRATION.with(RefCell::take) {
+        if let Err(e) = connection.unregister_object(registration) {
+            warn!("unable to unregister {}: {}", OBJECT_PATH, e);
+        }
+    }
+}
+
+fn handle_method_call(
+    method: &str,
+    parameters: glib::Variant,
+    invocation: gio::DBusMethodInvocation,
+) {
+    info!("{} called with {}", method, parameters);
+
+    // The startup ID is not used, because the new windows are presented immediately.
+    let Some((uris, _startup_id)) = parameters.get::<(Vec<String>, String)>() else {
+        invocation.return_error(gio::DBusError::InvalidArgs, "expected parameters (ass)");
+        return;
+    };
+
+    let files = uris.iter().map(|uri| gio::File::for_uri(uri));
+
+    match method {
+        "ShowFolders" => {
+            for folder in files {
+                AppModel::open(AppInit::new(folder));
+            }
+        }
+
+        // There is no separate properties dialog. The preview of a selected file displays its
+        // properties instead.
+        "ShowItems" | "ShowItemProperties" => {
+            group_by_parent(files).into_iter().for_each(show_items)
+        }
+        _ => {
+            invocation.return_error(
+                gio::DBusError::UnknownMethod,
+                &format!("unknown method: {}", method),
+            );
+            return;
+        }
+    }
+
+    invocation.return_value(None);
+}
+
+/// Groups files by the directory that contains them, in the order that each directory first
+/// appears.
+fn group_by_parent(files: impl IntoIterator<Item = gio::File>) -> Vec<Vec<gio::File>> {
+    let mut groups: Vec<Vec<gio::File>> = vec![];
+
+    for file in files {
+        let parent = file.parent();
+        let group = groups
+            .iter_mut()
+            .find(|group| match (group[0].parent(), &parent) {
+                (Some(a), Some(b)) => a.equal(b),
+                _ => false,
+            });
+
+        match group {
+            Some(group) => group.push(file),
+            None => groups.push(vec![file]),
+        }
+    }
+
+    groups
+}
+
+/// Open a window listing the directories leading to items in the same directory, with the items
+/// selected.
+///
+/// Items beneath the home directory are listed starting from the home directory. Other items are
+/// listed starting from their parent.
+fn show_items(items: Vec<gio::File>) {
+    let mut items = items.into_iter();
+    let Some(first) = items.next() else {
+        return;
+    };
+
+    let home = gio::File::for_path(glib::home_dir());
+
+    let init = if first.has_prefix(&home) {
+        AppInit::new(first).with_root(home)
+    } else {
+        AppInit::new(first)
+    };
+
+    AppModel::open(items.fold(init, AppInit::selecting));
+}
+
+#[cfg(test)]
+mod tests {
+    use relm4::gtk::{gio, prelude::*};
+
+    use super::group_by_parent;
+
+    fn paths(groups: Vec<Vec<gio::File>>) -> Vec<Vec<String>> {
+        groups
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|file| file.path().unwrap().display().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn items_are_grouped_by_directory_in_order() {
+        let files = ["/a/1", "/b/1", "/a/2", "/a/sub/1", "/b/2"].map(gio::File::for_path);
+
+        assert_eq!(
+            paths(group_by_parent(files)),
+            [vec!["/a/1", "/a/2"], vec!["/b/1", "/b/2"], vec!["/a/sub/1"],]
+        );
+    }
+
+    #[test]
+    fn files_without_a_parent_are_alone() {
+        let files = ["/", "/"].map(gio::File::for_path);
+
+        assert_eq!(paths(group_by_parent(files)), [vec!["/"], vec!["/"]]);
+    }
+}
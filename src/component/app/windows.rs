@@ -0,0 +1,367 @@
+//! Bookkeeping shared by every window of the application.
+//!
+//! The application state, the user's configuration and the dialogs that are shown through message
+//! brokers exist once per application rather than once per window. The dialogs are launched when
+//! the first window is registered, and are shown on top of the most recently focused window. The
+//! first window also requests the `org.freedesktop.FileManager1` bus name if the user enabled it,
+//! binds the keyboard shortcuts, and starts watching the configuration file for changes. The
+//! state of the open windows is persisted when the application shuts down.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::Once;
+use std::time::Duration;
+
+use gtk::{gio, glib, prelude::*};
+use relm4::prelude::*;
+use tracing::*;
+
+use super::{file_manager1, AppMsg};
+use crate::component::alert::{AlertModel, AlertMsg, ERROR_BROKER};
+use crate::component::conflict_dialog::{ConflictDialog, CONFLICT_BROKER};
+use crate::component::delete_dialog::{DeleteDialog, DELETE_BROKER};
+use crate::config::{self, Config, State, WindowState};
+use crate::shortcuts;
+
+/// A window that is open, and the sender of the component that manages it.
+#[derive(Debug)]
+struct OpenWindow {
+    window: adw::Window,
+    sender: relm4::Sender<AppMsg>,
+
+    /// The most recently recorded state of the window.
+    state: WindowState,
+}
+
+thread_local! {
+    static STATE: Rc<RefCell<State>> = Rc::new(RefCell::new(read_state()));
+    static CONFIG: RefCell<Rc<Config>> = RefCell::new(Rc::new(read_config().unwrap_or_default()));
+    static CONFIG_MONITOR: RefCell<Option<gio::FileMonitor>> = const { RefCell::new(None) };
+
+    /// Whether the configuration file couldn't be read the last time it was read, in which case
+    /// the configuration in use doesn't reflect the file.
+    static CONFIG_UNREADABLE: Cell<bool> = const { Cell::new(false) };
+    static WINDOWS: RefCell<Vec<OpenWindow>> = const { RefCell::new(vec![]) };
+
+    /// The state of the last window that was closed, if no windows are open.
+    static LAST_CLOSED: RefCell<Option<WindowState>> = const { RefCell::new(None) };
+
+    /// Writes the configuration once it stops changing, if a deferred write is pending.
+    static DEFERRED_WRITE: RefCell<Option<glib::SourceId>> = const { RefCell::new(None) };
+}
+
+static STARTUP: Once = Once::new();
+
+/// How long a configuration that changes continuously must stay unchanged before it's written.
+const DEFERRED_WRITE_DELAY: Duration = Duration::from_millis(500);
+
+fn read_state() -> State {
+    let state = State::read()
+        .map_err(|e| {
+            warn!("unable to read application state: {}", e);
+            e
+        })
+        .unwrap_or_default();
+
+    info!("starting with application state: {:?}", state);
+
+    state
+}
+
+/// Read the configuration file. Errors are shown to the user, since the file is edited by hand.
+fn read_config() -> Option<Config> {
+    let config = Config::read();
+    CONFIG_UNREADABLE.set(config.is_err());
+
+    match config {
+        Ok(config) => {
+            info!("read configuration: {:?}", config);
+            Some(config)
+        }
+        Err(e) => {
+            warn!("unable to read configuration: {}", e);
+            ERROR_BROKER.send(AlertMsg::Show {
+                text: format!("Unable to read the configuration file: {}", e),
+            });
+            None
+        }
+    }
+}
+
+/// Returns the application state, which is read the first time it's requested.
+pub fn state() -> Rc<RefCell<State>> {
+    STATE.with(Rc::clone)
+}
+
+/// Returns the user's configuration, which is read the first time it's requested.
+pub fn config() -> Rc<Config> {
+    CONFIG.with(|config| Rc::clone(&config.borrow()))
+}
+
+/// Write a new configuration to disk, and apply it to every window. If the configuration file
+/// couldn't be read, it isn't overwritten, and the configuration only applies until it's read
+/// again.
+pub fn set_config(config: Config) {
+    cancel_deferred_write();
+    write_config(&config);
+    apply_config(config);
+}
+
+/// Apply a new configuration to every window right away, but only write it to disk once it hasn't
+/// changed for a moment. Used for settings that change continuously, such as the icon size while
+/// zooming.
+pub fn set_config_deferred(config: Config) {
+    cancel_deferred_write();
+    apply_config(config);
+
+    let source = glib::timeout_add_local_once(DEFERRED_WRITE_DELAY, || {
+        DEFERRED_WRITE.take();
+        write_config(&self::config());
+    });
+    DEFERRED_WRITE.set(Some(source));
+}
+
+/// Write the configuration now if a deferred write is pending.
+fn flush_deferred_write() {
+    if let Some(source) = DEFERRED_WRITE.take() {
+        source.remove();
+        write_config(&config());
+    }
+}
+
+fn cancel_deferred_write() {
+    if let Some(source) = DEFERRED_WRITE.take() {
+        source.remove();
+    }
+}
+
+/// Write a configuration to disk, unless the configuration file couldn't be read.
+fn write_config(config: &Config) {
+    if CONFIG_UNREADABLE.get() {
+        warn!("not writing configuration, since the configuration file couldn't be read");
+        ERROR_BROKER.send(AlertMsg::Show {
+            text: String::from(
+                "The configuration file couldn't be read, so changes won't be saved until it's fixed.",
+            ),
+        });
+    } else if let Err(e) = config.write() {
+        warn!("unable to write configuration: {}", e);
+        ERROR_BROKER.send(AlertMsg::Show {
+            text: format!("Unable to save the configuration file: {}", e),
+        });
+    }
+}
+
+/// Replace the configuration, and notify every window if it changed.
+fn apply_config(config: Config) {
+    let changed = CONFIG.with(|current| {
+        let mut current = current.borrow_mut();
+
+        if **current == config {
+            false
+        } else {
+            *current = Rc::new(config);
+            true
+        }
+    });
+
+    if changed {
+        let config = config();
+        file_manager1::set_enabled(config.default_file_manager);
+        shortcuts::apply(&config);
+
+        WINDOWS.with(|windows| {
+            for open_window in windows.borrow().iter() {
+                open_window.sender.emit(AppMsg::ConfigChanged);
+            }
+        });
+    }
+}
+
+/// Reload the configuration whenever the configuration file changes. If the new configuration
+/// can't be read, the previous configuration is kept.
+fn watch_config() {
+    let monitor = config::config_path().and_then(|path| {
+        gio::File::for_path(path)
+            .monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE)
+            .map_err(Into::into)
+    });
+
+    let monitor = match monitor {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            warn!("unable to watch configuration file: {}", e);
+            return;
+        }
+    };
+
+    monitor.connect_changed(|_, _, _, event| {
+        if matches!(
+            event,
+            gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Deleted
+        ) {
+            info!("configuration file changed: {:?}", event);
+
+            if let Some(config) = read_config() {
+                apply_config(config);
+            }
+        }
+    });
+
+    CONFIG_MONITOR.with(|config_monitor| config_monitor.replace(Some(monitor)));
+}
+
+/// Returns the number of windows that are open.
+pub fn count() -> usize {
+    WINDOWS.with(|windows| windows.borrow().len())
+}
+
+/// Keep track of a newly opened window.
+pub fn register(window: &adw::Window, sender: relm4::Sender<AppMsg>) {
+    STARTUP.call_once(|| {
+        launch_dialogs();
+        let config = config();
+        file_manager1::set_enabled(config.default_file_manager);
+        shortcuts::apply(&config);
+        watch_config();
+        relm4::main_application().connect_shutdown(|_| {
+            flush_deferred_write();
+            write_state();
+        });
+    });
+
+    LAST_CLOSED.with(RefCell::take);
+
+    WINDOWS.with(|windows| {
+        windows.borrow_mut().push(OpenWindow {
+            window: window.clone(),
+            sender,
+            state: WindowState::default(),
+        })
+    });
+}
+
+/// Record the state of a window, so that it can be persisted along with every other window.
+pub fn update(window: &adw::Window, state: WindowState) {
+    WINDOWS.with(|windows| {
+        if let Some(open_window) = windows
+            .borrow_mut()
+            .iter_mut()
+            .find(|open_window| &open_window.window == window)
+        {
+            open_window.state = state;
+        }
+    });
+}
+
+/// Stop tracking a window that is about to be destroyed. If it's the last window, its state is
+/// kept as the session to restore.
+pub fn unregister(window: &adw::Window) {
+    WINDOWS.with(|windows| {
+        let mut windows = windows.borrow_mut();
+
+        if let Some(index) = windows
+            .iter()
+            .position(|open_window| &open_window.window == window)
+        {
+            let open_window = windows.remove(index);
+
+            if windows.is_empty() {
+                LAST_CLOSED.with(|last_closed| last_closed.replace(Some(open_window.state)));
+            }
+        }
+    });
+}
+
+/// Returns the most recently focused window that isn't a dialog.
+pub fn active_window() -> Option<adw::Window> {
+    WINDOWS.with(|windows| {
+        let windows = windows.borrow();
+
+        relm4::main_application()
+            .windows()
+            .into_iter()
+            .find_map(|window| {
+                windows
+                    .iter()
+                    .find(|open_window| open_window.window.upcast_ref::<gtk::Window>() == &window)
+                    .map(|open_window| open_window.window.clone())
+            })
+            .or_else(|| windows.last().map(|open_window| open_window.window.clone()))
+    })
+}
+
+/// Returns the sender of the component of the most recently focused window.
+pub fn active_sender() -> Option<relm4::Sender<AppMsg>> {
+    let active_window = active_window()?;
+
+    WINDOWS.with(|windows| {
+        windows
+            .borrow()
+            .iter()
+            .find(|open_window| open_window.window == active_window)
+            .map(|open_window| open_window.sender.clone())
+    })
+}
+
+/// Returns the recorded state of every open window, most recently focused first. If every window
+/// was closed, the last window that was closed is returned.
+fn session() -> Vec<WindowState> {
+    if let Some(state) = LAST_CLOSED.with(|last_closed| last_closed.borrow().clone()) {
+        return vec![state];
+    }
+
+    // The application lists its windows in the order they were focused.
+    let focus_order = relm4::main_application().windows();
+
+    WINDOWS.with(|windows| {
+        let windows = windows.borrow();
+
+        // Windows that the application doesn't list, such as those that are being closed, are
+        // placed last.
+        let mut windows = windows.iter().collect::<Vec<_>>();
+        windows.sort_by_key(|open_window| {
+            focus_order
+                .iter()
+                .position(|window| window == open_window.window.upcast_ref::<gtk::Window>())
+                .unwrap_or(usize::MAX)
+        });
+
+        windows
+            .into_iter()
+            .map(|open_window| open_window.state.clone())
+            .collect()
+    })
+}
+
+/// Persist the application state, along with the session.
+fn write_state() {
+    let state = state();
+    let mut state = state.borrow_mut();
+    state.windows = session();
+
+    if let Err(e) = state.write() {
+        warn!("unable to write application state: {}", e);
+    }
+}
+
+/// Launch the dialogs that receive messages through brokers, which may only be launched once.
+fn launch_dialogs() {
+    let mut alert = AlertModel::builder()
+        .launch_with_broker((), &ERROR_BROKER)
+        .detach();
+    alert.detach_runtime();
+
+    let mut conflict_dialog = ConflictDialog::builder()
+        .launch_with_broker((), &CONFLICT_BROKER)
+        .detach();
+    conflict_dialog.detach_runtime();
+
+    let mut delete_dialog = DeleteDialog::builder()
+        .launch_with_broker((), &DELETE_BROKER)
+        .connect_receiver(|_, msg| match active_sender() {
+            Some(sender) => sender.emit(msg),
+            None => warn!("no window to handle message: {:?}", msg),
+        });
+    delete_dialog.detach_runtime();
+}
@@ -0,0 +1,754 @@
+//! A single tab of the main window: the directory listings beneath a root, the preview of the
+//! selection, and the navigation history.
+//!
+//! Navigation messages from the listings and the search are handled by the browser that contains
+//! them. All other messages are passed on to the application.
+
+use std::cell::RefCell;
+use std::convert::identity;
+use std::path::{self, PathBuf};
+use std::rc::Rc;
+
+use gtk::{gio, glib, prelude::*};
+use relm4::factory::FactoryVecDeque;
+use relm4::prelude::*;
+use tracing::*;
+
+use super::app::{windows, AppMsg};
+use super::directory_list::{Directory, DirectoryInit, DirectoryMessage, Selection};
+use super::file_preview::{FilePreviewModel, FilePreviewMsg};
+use super::search::{SearchModel, SearchMsg};
+use crate::config::{State, TabState, ViewMode};
+use crate::util::NaturalWidthBin;
+
+mod history;
+
+use history::{History, Location};
+
+#[derive(Debug)]
+pub struct BrowserInit {
+    /// The directory listed by the leftmost column.
+    pub root: gio::File,
+
+    /// Files beneath the root to select once the directories leading to them are listed. The files
+    /// must all be in the same directory.
+    pub selection: Vec<gio::File>,
+
+    /// The widths of the directory listings and the preview, restored from a previous session.
+    pub column_widths: Vec<i32>,
+    pub preview_width: Option<i32>,
+
+    /// How the directories are displayed.
+    pub view_mode: ViewMode,
+}
+
+impl BrowserInit {
+    /// Initializes a browser of the root, displayed in the default view mode from the
+    /// configuration.
+    pub fn new(root: gio::File) -> Self {
+        BrowserInit {
+            root,
+            selection: vec![],
+            column_widths: vec![],
+            preview_width: None,
+            view_mode: windows::config().view_mode,
+        }
+    }
+
+    /// Select the file once the directories leading to it are listed.
+    pub fn selecting(mut self, file: gio::File) -> Self {
+        self.selection.push(file);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct Browser {
+    /// The directory listed by the leftmost column.
+    root: gio::File,
+
+    /// The directory listings. This factory acts as a stack, where new directories are pushed and
+    /// popped relative to the root as the user clicks on new directory entries.
+    directories: FactoryVecDeque<Directory>,
+
+    file_preview: Controller<FilePreviewModel>,
+    search: Controller<SearchModel>,
+
+    /// Whether the search results are displayed in place of the directory listings.
+    searching: bool,
+
+    /// Whether hidden files were included when the search was last started.
+    search_show_hidden: bool,
+
+    /// How the directories are displayed.
+    view_mode: ViewMode,
+
+    /// A file that is being revealed in the directory listings. As each directory on the path to
+    /// the file is listed, the next directory (or the file itself) is selected.
+    reveal_target: Option<gio::File>,
+
+    /// Other files in the same directory as the reveal target, which are selected along with it.
+    reveal_siblings: Vec<gio::File>,
+
+    /// The files selected in the deepest listing that has a selection.
+    selection: Vec<gio::File>,
+
+    /// Widths restored from a previous session, which are applied to each directory listing as it
+    /// is added.
+    restored_column_widths: Vec<Option<i32>>,
+
+    /// Previously visited locations.
+    history: History,
+
+    /// Whether the directory panes scroll window should update its scroll position to the upper
+    /// bound on the next view update.
+    update_directory_scroll_position: bool,
+
+    state: Rc<RefCell<State>>,
+}
+
+impl Browser {
+    /// Returns the directory listed by the leftmost column.
+    pub fn root(&self) -> gio::File {
+        self.root.clone()
+    }
+
+    /// Returns the deepest directory that is listed (the rightmost listing).
+    pub fn last_dir(&self) -> gio::File {
+        self.directories
+            .back()
+            .expect("there must be at least one directory listed")
+            .dir()
+    }
+
+    /// Returns the directory that is displayed: the deepest directory that is listed, or if a
+    /// single directory is listed at a time, the directory whose listing is shown.
+    pub fn current_dir(&self) -> gio::File {
+        if self.view_mode.lists_single_directory() {
+            self.directories
+                .get(self.current_index())
+                .expect("there must be at least one directory listed")
+                .dir()
+        } else {
+            self.last_dir()
+        }
+    }
+
+    /// Returns the position of the listing that is shown if a single directory is listed at a
+    /// time. This is the listing that the selection was made in, so that selecting a directory
+    /// doesn't leave the listing. Otherwise, such as when a directory was opened or navigated to
+    /// from the path bar, it's the deepest listing.
+    fn current_index(&self) -> usize {
+        self.directories
+            .iter()
+            .position(|directory| match directory.selection() {
+                Selection::Files(selection) => {
+                    selection.files.len() == self.selection.len()
+                        && selection
+                            .files
+                            .iter()
+                            .zip(&self.selection)
+                            .all(|(a, b)| a.equal(b))
+                }
+                Selection::None => false,
+            })
+            .unwrap_or(self.directories.len() - 1)
+    }
+
+    /// Returns whether the search results are displayed.
+    pub fn searching(&self) -> bool {
+        self.searching
+    }
+
+    /// Returns how the directories are displayed.
+    pub fn view_mode(&self) -> ViewMode {
+        self.view_mode
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.history.can_go_back()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.history.can_go_forward()
+    }
+
+    /// Returns the location displayed by the browser, so that it can be restored later.
+    pub fn tab_state(&self) -> TabState {
+        let paned = self.directories.widget();
+        let preview = self.file_preview.widget().upcast_ref::<gtk::Widget>();
+
+        let mut column_widths = vec![];
+        let mut preview_width = None;
+
+        for child in (0..paned.n_children()).filter_map(|n| paned.nth_child(n)) {
+            if &child == preview {
+                preview_width = Some(child.width()).filter(|_| !self.selection.is_empty());
+            } else if !child.is_visible() || child.hexpands() {
+                // Listings that are hidden or fill the tab keep the width they have in the columns
+                // view.
+                let natural_width = child
+                    .downcast_ref::<NaturalWidthBin>()
+                    .map_or(0, NaturalWidthBin::natural_width);
+                column_widths.push(child.width_request().max(natural_width));
+            } else {
+                column_widths.push(child.width());
+            }
+        }
+
+        TabState {
+            root: self.root.uri().into(),
+            last_dir: self.last_dir().uri().into(),
+            selection: self
+                .selection
+                .iter()
+                .map(|file| file.uri().into())
+                .collect(),
+            column_widths,
+            preview_width,
+            view_mode: Some(self.view_mode),
+        }
+    }
+
+    /// Open the search results, searching beneath the current root.
+    fn start_search(&mut self) {
+        self.searching = true;
+        self.search_show_hidden = windows::config().show_hidden;
+        self.search.emit(SearchMsg::Start {
+            root: self.root.clone(),
+            show_hidden: self.search_show_hidden,
+        });
+    }
+
+    /// Replace the directory listings with those leading from the root of the location to its
+    /// selection, and select it along with its siblings.
+    fn show_location(&mut self, location: Location, siblings: Vec<gio::File>) {
+        info!(
+            "showing location: root {}, selection {:?}",
+            location.root.uri(),
+            location.selection.as_ref().map(|file| file.uri())
+        );
+
+        self.searching = false;
+        self.search.emit(SearchMsg::Stop);
+
+        self.root = location.root;
+        self.reveal_target = location.selection;
+        self.reveal_siblings = siblings;
+        self.selection.clear();
+
+        let init = reveal_init(
+            self.root.clone(),
+            &self.state.borrow(),
+            self.view_mode,
+            self.reveal_target.as_ref(),
+            &self.reveal_siblings,
+        );
+
+        let mut directories = self.directories.guard();
+        directories.clear();
+        directories.push_back(init);
+        drop(directories);
+
+        self.file_preview.emit(FilePreviewMsg::Hide);
+
+        self.update_directory_scroll_position = true;
+    }
+
+    /// Apply the restored widths to any directory listings that were added since they were last
+    /// applied. The restored width becomes the natural width of the listing, so that it can still
+    /// be shrunk.
+    fn restore_column_widths(&mut self) {
+        let paned = self.directories.widget();
+        let preview = self.file_preview.widget().upcast_ref::<gtk::Widget>();
+
+        let columns = (0..paned.n_children())
+            .filter_map(|n| paned.nth_child(n))
+            .filter(|child| child != preview);
+
+        for (column, width) in columns.zip(&mut self.restored_column_widths) {
+            if let Some(width) = width.take() {
+                if let Some(column) = column.downcast_ref::<NaturalWidthBin>() {
+                    column.set_natural_width(width);
+                }
+            }
+        }
+    }
+
+    /// Show every directory listing, or if a single directory is listed at a time, only the
+    /// current listing, filling the tab.
+    fn update_visible_listings(&self) {
+        let paned = self.directories.widget();
+        let preview = self.file_preview.widget().upcast_ref::<gtk::Widget>();
+
+        let current = self
+            .view_mode
+            .lists_single_directory()
+            .then(|| self.current_index());
+
+        let columns = (0..paned.n_children())
+            .filter_map(|n| paned.nth_child(n))
+            .filter(|child| child != preview);
+
+        for (i, column) in columns.enumerate() {
+            column.set_visible(current.map_or(true, |current| i == current));
+            column.set_hexpand(current.is_some());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BrowserMsg {
+    /// See [`AppMsg::NewRoot`].
+    NewRoot(gio::File),
+
+    /// See [`AppMsg::NewSelection`].
+    NewSelection(Selection),
+
+    /// See [`AppMsg::RevealFile`].
+    RevealFile(gio::File),
+
+    /// See [`AppMsg::OpenDirectory`].
+    OpenDirectory(gio::File),
+
+    /// See [`AppMsg::StartSearch`].
+    StartSearch,
+
+    /// See [`AppMsg::StopSearch`].
+    StopSearch,
+
+    /// See [`AppMsg::Back`].
+    Back,
+
+    /// See [`AppMsg::Forward`].
+    Forward,
+
+    /// Display the directories in the given view mode.
+    SetViewMode(ViewMode),
+
+    /// The configuration changed.
+    ConfigChanged,
+}
+
+/// Navigation messages are handled by a browser. Returns the message unchanged if it should be
+/// handled by the application instead.
+impl TryFrom<AppMsg> for BrowserMsg {
+    type Error = AppMsg;
+
+    fn try_from(msg: AppMsg) -> Result<Self, Self::Error> {
+        Ok(match msg {
+            AppMsg::NewRoot(root) => BrowserMsg::NewRoot(root),
+            AppMsg::NewSelection(selection) => BrowserMsg::NewSelection(selection),
+            AppMsg::RevealFile(file) => BrowserMsg::RevealFile(file),
+            AppMsg::OpenDirectory(dir) => BrowserMsg::OpenDirectory(dir),
+            AppMsg::StartSearch => BrowserMsg::StartSearch,
+            AppMsg::StopSearch => BrowserMsg::StopSearch,
+            AppMsg::Back => BrowserMsg::Back,
+            AppMsg::Forward => BrowserMsg::Forward,
+            msg => return Err(msg),
+        })
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for Browser {
+    type Init = BrowserInit;
+    type Input = BrowserMsg;
+    type Output = AppMsg;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Stack {
+            #[name = "directory_panes_scroller"]
+            add_child = &gtk::ScrolledWindow {
+                set_hexpand: true,
+                set_vexpand: true,
+
+                #[name = "directory_panes"]
+                panel::Paned {
+                    append: file_preview.widget(),
+                },
+            } -> { set_name: "columns" },
+
+            add_named: (search.widget(), Some("search")),
+        }
+    }
+
+    fn init(
+        init: BrowserInit,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let BrowserInit {
+            root: root_dir,
+            selection,
+            column_widths,
+            preview_width,
+            view_mode,
+        } = init;
+
+        // Messages from the listings and the search are routed to this browser if they're about
+        // navigation, and to the application otherwise. The router outlives the browser as long
+        // as there are operations holding a sender, so that their progress is still reported
+        // after the tab is closed.
+        let (router, receiver) = relm4::channel::<AppMsg>();
+        let input = sender.input_sender().clone();
+        let output = sender.output_sender().clone();
+        relm4::spawn_local(async move {
+            while let Some(msg) = receiver.recv().await {
+                match BrowserMsg::try_from(msg) {
+                    Ok(msg) => {
+                        let _ = input.send(msg);
+                    }
+                    Err(msg) => {
+                        let _ = output.send(msg);
+                    }
+                }
+            }
+        });
+
+        let file_preview = FilePreviewModel::builder().launch(()).detach();
+
+        if let Some(width) = preview_width {
+            file_preview.emit(FilePreviewMsg::RestoreWidth(width));
+        }
+
+        let search = SearchModel::builder()
+            .launch(root_dir.clone())
+            .forward(&router, identity);
+
+        let widgets = view_output!();
+
+        let mut model = Browser {
+            root: root_dir.clone(),
+            directories: FactoryVecDeque::builder()
+                .launch(widgets.directory_panes.clone())
+                .forward(&router, identity),
+            file_preview,
+            search,
+            searching: false,
+            search_show_hidden: false,
+            view_mode,
+            reveal_target: None,
+            reveal_siblings: vec![],
+            selection: vec![],
+            restored_column_widths: column_widths.into_iter().map(Some).collect(),
+            history: History::new(Location {
+                root: root_dir.clone(),
+                selection: selection.first().cloned(),
+            }),
+            update_directory_scroll_position: false,
+            state: windows::state(),
+        };
+
+        let mut selection = selection.into_iter();
+        model.show_location(
+            Location {
+                root: root_dir,
+                selection: selection.next(),
+            },
+            selection.collect(),
+        );
+        model.restore_column_widths();
+        model.update_visible_listings();
+
+        // TODO: There's sometimes a delay in updating the adjustment upper bound when a new pane
+        // is added, causing this code to not trigger at the right time. Needs more investigation.
+        widgets
+            .directory_panes_scroller
+            .hadjustment()
+            .connect_notify(Some("upper"), |this, _| {
+                set_adjustment_to_upper_bound(this);
+            });
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        msg: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        self.update_directory_scroll_position = false;
+
+        match msg {
+            BrowserMsg::NewSelection(Selection::Files(selection)) => {
+                let mut last_dir = self.last_dir();
+
+                let file = if selection.files.len() == 1 {
+                    selection.files.first().unwrap()
+                } else {
+                    &selection.parent
+                };
+
+                let file_path = match glib::Uri::split(&file.uri(), glib::UriFlags::NONE) {
+                    Ok((_, _, _, _, path, _, _)) => PathBuf::from(&path),
+                    Err(e) => {
+                        warn!("unable to parse URI: {}", e);
+                        return;
+                    }
+                };
+
+                let last_dir_path = glib::Uri::split(&last_dir.uri(), glib::UriFlags::NONE)
+                    .map(|(_, _, _, _, path, _, _)| path)
+                    .expect("last visited directory must be a valid URI");
+
+                let diff = pathdiff::diff_paths(file_path, &last_dir_path)
+                    .expect("new selection must be relative to the listed directories");
+
+                info!(
+                    "new selection: {:?}, last dir: {}, diff: {}",
+                    selection,
+                    last_dir.uri(),
+                    diff.display()
+                );
+
+                // Stop revealing the target if the user selected something else.
+                if let Some(target) = &self.reveal_target {
+                    if !target.has_prefix(file) {
+                        self.reveal_target = None;
+                        self.reveal_siblings.clear();
+                    }
+                }
+
+                self.selection = selection.files.clone();
+
+                // The directories leading to a target are selected along the way, but only the
+                // target itself is worth returning to.
+                if self.reveal_target.is_none() {
+                    self.history.visit(Location {
+                        root: self.root.clone(),
+                        selection: Some(file.clone()),
+                    });
+                }
+
+                let state = self.state.borrow();
+                let mut directories = self.directories.guard();
+
+                for component in diff.components() {
+                    match component {
+                        path::Component::ParentDir => {
+                            directories.pop_back();
+                            last_dir = last_dir.parent().unwrap();
+                        }
+                        path::Component::Normal(name) => {
+                            let component_file = last_dir.child(name);
+                            if component_file.query_file_type(
+                                gio::FileQueryInfoFlags::NONE,
+                                gio::Cancellable::NONE,
+                            ) == gio::FileType::Directory
+                            {
+                                directories.push_back(reveal_init(
+                                    component_file.clone(),
+                                    &state,
+                                    self.view_mode,
+                                    self.reveal_target.as_ref(),
+                                    &self.reveal_siblings,
+                                ));
+                                last_dir = component_file;
+                            }
+                        }
+                        _ => unreachable!("unexpected path component: {:?}", component),
+                    }
+                }
+
+                drop(directories);
+                drop(state);
+
+                self.file_preview
+                    .emit(FilePreviewMsg::NewSelection(selection));
+
+                self.update_directory_scroll_position = true;
+            }
+            BrowserMsg::NewSelection(Selection::None) => {
+                self.selection.clear();
+                self.file_preview.emit(FilePreviewMsg::Hide);
+
+                self.update_directory_scroll_position = true;
+            }
+            BrowserMsg::NewRoot(new_root) => {
+                info!("new root: {:?}", new_root);
+
+                let mut directories = self.directories.guard();
+
+                directories.clear();
+
+                self.root = new_root;
+                directories.push_back(DirectoryInit::new(
+                    self.root.clone(),
+                    &self.state.borrow(),
+                    self.view_mode,
+                ));
+                drop(directories);
+
+                self.history.visit(Location {
+                    root: self.root.clone(),
+                    selection: None,
+                });
+
+                self.reveal_target = None;
+                self.reveal_siblings.clear();
+                self.selection.clear();
+                self.file_preview.emit(FilePreviewMsg::Hide);
+
+                if self.searching {
+                    self.start_search();
+                }
+
+                self.update_directory_scroll_position = true;
+            }
+            BrowserMsg::RevealFile(file) => self.show_location(
+                Location {
+                    root: self.root.clone(),
+                    selection: Some(file),
+                },
+                vec![],
+            ),
+            BrowserMsg::OpenDirectory(dir) => {
+                let Some(position) = dir.parent().and_then(|parent| {
+                    self.directories
+                        .iter()
+                        .position(|directory| directory.dir().equal(&parent))
+                }) else {
+                    warn!("directory to open isn't listed: {}", dir.uri());
+                    return;
+                };
+
+                info!("opening directory: {}", dir.uri());
+
+                self.history.visit(Location {
+                    root: self.root.clone(),
+                    selection: Some(dir.clone()),
+                });
+
+                self.reveal_target = None;
+                self.reveal_siblings.clear();
+                self.selection.clear();
+
+                // The directory may already be listed, if it was selected before it was opened.
+                let mut directories = self.directories.guard();
+                let listed = directories
+                    .get(position + 1)
+                    .is_some_and(|directory| directory.dir().equal(&dir));
+                let len = position + if listed { 2 } else { 1 };
+
+                while directories.len() > len {
+                    directories.pop_back();
+                }
+
+                if !listed {
+                    directories.push_back(DirectoryInit::new(
+                        dir,
+                        &self.state.borrow(),
+                        self.view_mode,
+                    ));
+                }
+
+                drop(directories);
+
+                self.file_preview.emit(FilePreviewMsg::Hide);
+
+                self.update_directory_scroll_position = true;
+            }
+            BrowserMsg::StartSearch => self.start_search(),
+            BrowserMsg::StopSearch => {
+                self.searching = false;
+                self.search.emit(SearchMsg::Stop);
+            }
+            BrowserMsg::Back => {
+                if let Some(location) = self.history.back() {
+                    self.show_location(location, vec![]);
+                }
+            }
+            BrowserMsg::Forward => {
+                if let Some(location) = self.history.forward() {
+                    self.show_location(location, vec![]);
+                }
+            }
+            BrowserMsg::SetViewMode(view_mode) => {
+                self.view_mode = view_mode;
+                self.directories
+                    .broadcast(DirectoryMessage::SetViewMode(view_mode));
+            }
+            BrowserMsg::ConfigChanged => {
+                self.directories.broadcast(DirectoryMessage::ConfigChanged);
+                self.file_preview.emit(FilePreviewMsg::ConfigChanged);
+
+                // The search only depends on whether hidden files are shown, and restarting it
+                // would walk the whole tree again.
+                if self.searching && self.search_show_hidden != windows::config().show_hidden {
+                    self.start_search();
+                }
+            }
+        }
+
+        self.restore_column_widths();
+        self.update_visible_listings();
+
+        root.set_visible_child_name(if self.searching { "search" } else { "columns" });
+
+        sender.output(AppMsg::TabChanged).unwrap();
+
+        self.update_view(widgets, sender);
+    }
+
+    fn post_view(&self, widgets: &mut Self::Widgets) {
+        if self.update_directory_scroll_position {
+            // Although this function is already called whenever the hadjustment changes, we also
+            // sometimes want to scroll when the adjustment doesn't change.
+            //
+            // Consider the user selecting a new directory entry on a partially obscured panel. The
+            // adjustment won't change, because the total number of panels is the same. However,
+            // we still want to scroll over to it because it's new information that the user wants
+            // to see.
+            set_adjustment_to_upper_bound(&widgets.directory_panes_scroller.hadjustment());
+        }
+    }
+}
+
+/// Updates the value of an adjustment to its upper bound.
+///
+/// This is used to keep new directories and file information visible inside the directory panes
+/// scroll window as user interacts with the application.
+fn set_adjustment_to_upper_bound(adjustment: &gtk::Adjustment) {
+    adjustment.set_value(adjustment.upper());
+}
+
+/// Returns the initial state of a listing of `dir` in the given view mode. If the reveal target is
+/// beneath `dir`, the next file on the path to it is selected, along with the target's siblings if
+/// the target is a child of `dir`.
+fn reveal_init(
+    dir: gio::File,
+    state: &State,
+    view_mode: ViewMode,
+    target: Option<&gio::File>,
+    siblings: &[gio::File],
+) -> DirectoryInit {
+    let mut init = DirectoryInit::new(dir.clone(), state, view_mode);
+
+    let Some(target) = target else {
+        return init;
+    };
+
+    if let Some(next) = next_path_component(&dir, target) {
+        let is_child = next.equal(target);
+        init = init.selecting(next);
+
+        if is_child {
+            init.select.extend(siblings.iter().cloned());
+        }
+    }
+
+    init
+}
+
+/// Returns the child of `dir` that is on the path to `target`, or `None` if `target` is not
+/// beneath `dir`.
+fn next_path_component(dir: &gio::File, target: &gio::File) -> Option<gio::File> {
+    let relative_path = dir.relative_path(target)?;
+    let first = relative_path.components().next()?;
+
+    Some(dir.child(first))
+}
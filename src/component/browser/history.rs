@@ -0,0 +1,195 @@
+//! Back and forward navigation between previously visited locations.
+
+use relm4::gtk::{gio, prelude::*};
+
+/// The maximum number of locations remembered.
+const MAX_ENTRIES: usize = 100;
+
+/// A place in the directory listings that can be returned to.
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// The directory listed by the leftmost column.
+    pub root: gio::File,
+
+    /// The file selected in the deepest column, if any. Every directory between the root and this
+    /// file is listed.
+    pub selection: Option<gio::File>,
+}
+
+impl Location {
+    /// Returns the directory whose listing contains the selection.
+    fn listed_dir(&self) -> gio::File {
+        self.selection
+            .as_ref()
+            .and_then(|selection| selection.parent())
+            .unwrap_or_else(|| self.root.clone())
+    }
+
+    fn equal(&self, other: &Location) -> bool {
+        self.root.equal(&other.root)
+            && match (&self.selection, &other.selection) {
+                (Some(a), Some(b)) => a.equal(b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+/// The list of visited locations, and the position of the current location within it.
+#[derive(Debug)]
+pub struct History {
+    entries: Vec<Location>,
+    current: usize,
+}
+
+impl History {
+    pub fn new(location: Location) -> Self {
+        History {
+            entries: vec![location],
+            current: 0,
+        }
+    }
+
+    /// Record a visit to a new location. Locations that can be navigated forward to are
+    /// forgotten.
+    ///
+    /// Selecting a different file in the same directory replaces the current location rather than
+    /// adding a new one, so that going back returns to the previous directory.
+    pub fn visit(&mut self, location: Location) {
+        let current = &self.entries[self.current];
+
+        if current.equal(&location) {
+            return;
+        }
+
+        let same_dir = current.root.equal(&location.root)
+            && current.listed_dir().equal(&location.listed_dir());
+
+        self.entries.truncate(self.current + 1);
+
+        if same_dir {
+            self.entries[self.current] = location;
+        } else {
+            self.entries.push(location);
+
+            if self.entries.len() > MAX_ENTRIES {
+                self.entries.remove(0);
+            }
+
+            self.current = self.entries.len() - 1;
+        }
+    }
+
+    /// Move to the previous location, and return it.
+    pub fn back(&mut self) -> Option<Location> {
+        self.current = self.current.checked_sub(1)?;
+        Some(self.entries[self.current].clone())
+    }
+
+    /// Move to the next location, and return it.
+    pub fn forward(&mut self) -> Option<Location> {
+        if !self.can_go_forward() {
+            return None;
+        }
+
+        self.current += 1;
+        Some(self.entries[self.current].clone())
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.current > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.current + 1 < self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use relm4::gtk::{gio, prelude::*};
+
+    use super::{History, Location};
+
+    fn location(selection: Option<&str>) -> Location {
+        Location {
+            root: gio::File::for_path("/"),
+            selection: selection.map(gio::File::for_path),
+        }
+    }
+
+    fn selection(location: Option<Location>) -> Option<String> {
+        location
+            .and_then(|location| location.selection)
+            .and_then(|file| file.path())
+            .map(|path| path.display().to_string())
+    }
+
+    #[test]
+    fn back_and_forward() {
+        let mut history = History::new(location(None));
+        history.visit(location(Some("/a/x")));
+        history.visit(location(Some("/a/x/y")));
+
+        assert!(history.can_go_back());
+        assert!(!history.can_go_forward());
+
+        assert_eq!(selection(history.back()), Some(String::from("/a/x")));
+        assert_eq!(selection(history.back()), None);
+        assert!(!history.can_go_back());
+        assert!(history.back().is_none());
+
+        assert_eq!(selection(history.forward()), Some(String::from("/a/x")));
+        assert_eq!(selection(history.forward()), Some(String::from("/a/x/y")));
+        assert!(history.forward().is_none());
+    }
+
+    #[test]
+    fn visiting_the_current_location_is_ignored() {
+        let mut history = History::new(location(None));
+        history.visit(location(Some("/a/x")));
+        history.visit(location(Some("/a/x")));
+
+        assert_eq!(selection(history.back()), None);
+        assert!(!history.can_go_back());
+    }
+
+    #[test]
+    fn selecting_in_the_same_directory_replaces_the_location() {
+        let mut history = History::new(location(None));
+        history.visit(location(Some("/a/x")));
+        history.visit(location(Some("/a/x/b")));
+        history.visit(location(Some("/a/x/c")));
+
+        assert_eq!(selection(history.back()), Some(String::from("/a/x")));
+        assert_eq!(selection(history.forward()), Some(String::from("/a/x/c")));
+    }
+
+    #[test]
+    fn visiting_forgets_forward_locations() {
+        let mut history = History::new(location(None));
+        history.visit(location(Some("/a/x")));
+        history.visit(location(Some("/a/x/y")));
+        history.back();
+        history.visit(location(Some("/c/x")));
+
+        assert!(!history.can_go_forward());
+        assert_eq!(selection(history.back()), Some(String::from("/a/x")));
+    }
+
+    #[test]
+    fn oldest_locations_are_forgotten() {
+        let mut history = History::new(location(None));
+
+        for i in 0..super::MAX_ENTRIES {
+            history.visit(location(Some(&format!("/{}/child", i))));
+        }
+
+        let mut count = 0;
+        while history.back().is_some() {
+            count += 1;
+        }
+
+        assert_eq!(count, super::MAX_ENTRIES - 1);
+    }
+}
@@ -0,0 +1,272 @@
+//! Dialog asking the user how to resolve a file transfer whose destination already exists.
+//!
+//! Conflicts are sent to the dialog through [`CONFLICT_BROKER`] by the file operations in
+//! [`crate::ops`], which wait for the response. Conflicts arriving while the dialog is already
+//! shown are queued and displayed in order.
+
+use std::collections::VecDeque;
+
+use futures::channel::oneshot;
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use relm4::prelude::*;
+use relm4::MessageBroker;
+
+use super::app::windows;
+use super::file_preview::format_datetime;
+use crate::ops::ConflictResolution;
+
+pub static CONFLICT_BROKER: MessageBroker<ConflictDialogMsg> = MessageBroker::new();
+
+/// A transfer whose destination already exists.
+#[derive(Debug)]
+pub struct Conflict {
+    /// Info of the file being transferred.
+    pub source: gio::FileInfo,
+
+    /// Info of the existing file at the destination.
+    pub destination: gio::FileInfo,
+
+    /// Display name of the directory that contains the destination.
+    pub destination_dir_name: String,
+
+    /// Whether the conflict may be resolved by merging two directories.
+    pub can_merge: bool,
+
+    /// Whether the user may apply their choice to the rest of the operation.
+    pub can_apply_to_all: bool,
+
+    /// Receives the chosen resolution, and whether it should be applied to all further conflicts.
+    pub responder: oneshot::Sender<(ConflictResolution, bool)>,
+}
+
+#[derive(Debug)]
+pub struct ConflictDialog {
+    /// Conflicts awaiting a response. The conflict at the front is displayed.
+    conflicts: VecDeque<Conflict>,
+
+    apply_to_all: bool,
+}
+
+impl ConflictDialog {
+    fn heading(&self) -> String {
+        match self.conflicts.front() {
+            Some(conflict) if conflict.can_merge => {
+                format!("Merge folder '{}'?", conflict.source.display_name())
+            }
+            Some(conflict) => format!("Replace '{}'?", conflict.source.display_name()),
+            None => String::new(),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self.conflicts.front() {
+            Some(conflict) => format!(
+                "An item with the same name already exists in '{}'.",
+                conflict.destination_dir_name
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConflictDialogMsg {
+    /// Ask the user to resolve a new conflict.
+    Show(Conflict),
+
+    /// Whether the "Apply to all" check button is active.
+    SetApplyToAll(bool),
+
+    /// The user chose how to resolve the displayed conflict.
+    Resolve(ConflictResolution),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for ConflictDialog {
+    type Init = ();
+    type Input = ConflictDialogMsg;
+    type Output = ();
+
+    view! {
+        gtk::Dialog::builder()
+            .title("File Conflict")
+            .use_header_bar(gtk::Settings::default().unwrap().is_gtk_dialogs_use_header() as i32)
+            .build() {
+
+            #[watch]
+            set_transient_for: windows::active_window().as_ref(),
+            #[watch]
+            set_visible: !model.conflicts.is_empty(),
+            set_modal: true,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(ConflictDialogMsg::Resolve(ConflictResolution::Skip));
+                glib::signal::Propagation::Stop
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 12,
+                set_margin_all: 12,
+
+                gtk::Label {
+                    add_css_class: "title-2",
+                    set_halign: gtk::Align::Start,
+                    #[watch]
+                    set_text: &model.heading(),
+                },
+
+                gtk::Label {
+                    set_halign: gtk::Align::Start,
+                    set_wrap: true,
+                    #[watch]
+                    set_text: &model.description(),
+                },
+
+                gtk::Grid {
+                    set_column_spacing: 24,
+                    set_row_spacing: 6,
+                    set_column_homogeneous: true,
+
+                    attach[0, 0, 1, 1] = &gtk::Label {
+                        set_label: "Existing",
+                        add_css_class: "heading",
+                        set_halign: gtk::Align::Start,
+                    },
+                    attach[0, 1, 1, 1] = &gtk::Label {
+                        add_css_class: "dim-label",
+                        set_halign: gtk::Align::Start,
+                        #[watch]
+                        set_text: &model
+                            .conflicts
+                            .front()
+                            .map(|c| format_size(&c.destination))
+                            .unwrap_or_default(),
+                    },
+                    attach[0, 2, 1, 1] = &gtk::Label {
+                        add_css_class: "dim-label",
+                        set_halign: gtk::Align::Start,
+                        #[watch]
+                        set_text: &model
+                            .conflicts
+                            .front()
+                            .map(|c| format_modified(&c.destination))
+                            .unwrap_or_default(),
+                    },
+                    attach[1, 0, 1, 1] = &gtk::Label {
+                        set_label: "Incoming",
+                        add_css_class: "heading",
+                        set_halign: gtk::Align::Start,
+                    },
+                    attach[1, 1, 1, 1] = &gtk::Label {
+                        add_css_class: "dim-label",
+                        set_halign: gtk::Align::Start,
+                        #[watch]
+                        set_text: &model
+                            .conflicts
+                            .front()
+                            .map(|c| format_size(&c.source))
+                            .unwrap_or_default(),
+                    },
+                    attach[1, 2, 1, 1] = &gtk::Label {
+                        add_css_class: "dim-label",
+                        set_halign: gtk::Align::Start,
+                        #[watch]
+                        set_text: &model
+                            .conflicts
+                            .front()
+                            .map(|c| format_modified(&c.source))
+                            .unwrap_or_default(),
+                    },
+                },
+
+                gtk::CheckButton {
+                    set_label: Some("Apply to all"),
+                    #[watch]
+                    set_visible: model.conflicts.front().map_or(false, |c| c.can_apply_to_all),
+                    #[watch]
+                    #[block_signal(apply_to_all_handler)]
+                    set_active: model.apply_to_all,
+                    connect_toggled[sender] => move |this| {
+                        sender.input(ConflictDialogMsg::SetApplyToAll(this.is_active()));
+                    } @apply_to_all_handler,
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_halign: gtk::Align::End,
+                    set_spacing: 6,
+
+                    gtk::Button {
+                        set_label: "Skip",
+                        connect_clicked =>
+                            ConflictDialogMsg::Resolve(ConflictResolution::Skip),
+                    },
+
+                    gtk::Button {
+                        set_label: "Keep Both",
+                        connect_clicked =>
+                            ConflictDialogMsg::Resolve(ConflictResolution::KeepBoth),
+                    },
+
+                    gtk::Button {
+                        set_label: "Merge",
+                        #[watch]
+                        set_visible: model.conflicts.front().map_or(false, |c| c.can_merge),
+                        connect_clicked =>
+                            ConflictDialogMsg::Resolve(ConflictResolution::Merge),
+                    },
+
+                    gtk::Button {
+                        set_label: "Replace",
+                        add_css_class: "destructive-action",
+                        connect_clicked =>
+                            ConflictDialogMsg::Resolve(ConflictResolution::Replace),
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(_: (), root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let model = ConflictDialog {
+            conflicts: VecDeque::new(),
+            apply_to_all: false,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _: ComponentSender<Self>) {
+        match msg {
+            ConflictDialogMsg::Show(conflict) => self.conflicts.push_back(conflict),
+            ConflictDialogMsg::SetApplyToAll(apply_to_all) => self.apply_to_all = apply_to_all,
+            ConflictDialogMsg::Resolve(resolution) => {
+                if let Some(conflict) = self.conflicts.pop_front() {
+                    let _ = conflict.responder.send((resolution, self.apply_to_all));
+                }
+
+                self.apply_to_all = false;
+            }
+        }
+    }
+}
+
+/// Describes the size of a file, or that it is a folder.
+fn format_size(info: &gio::FileInfo) -> String {
+    if info.file_type() == gio::FileType::Directory {
+        String::from("Folder")
+    } else {
+        glib::format_size(info.size() as u64).to_string()
+    }
+}
+
+/// Describes when a file was last modified.
+fn format_modified(info: &gio::FileInfo) -> String {
+    info.modification_date_time()
+        .map(|dt| format!("Modified {}", format_datetime(&dt)))
+        .unwrap_or_default()
+}
@@ -0,0 +1,194 @@
+//! Dialog asking the user to confirm that files should be permanently deleted.
+//!
+//! Requests arriving while the dialog is already shown are queued and displayed in order.
+
+use std::collections::VecDeque;
+
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use relm4::prelude::*;
+use relm4::MessageBroker;
+
+use super::app::{windows, AppMsg};
+use crate::util::pluralize;
+
+pub static DELETE_BROKER: MessageBroker<DeleteDialogMsg> = MessageBroker::new();
+
+/// Why the files are being deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteReason {
+    /// The user asked to delete the files.
+    Requested,
+
+    /// The files are on a volume that doesn't support the trash.
+    TrashUnsupported,
+
+    /// The user asked to empty the trash, and the files are its contents.
+    EmptyTrash,
+}
+
+/// Files awaiting confirmation of their deletion.
+#[derive(Debug)]
+pub struct DeleteRequest {
+    files: Vec<gio::File>,
+
+    reason: DeleteReason,
+
+    /// Display name of the file, if only one file is being deleted.
+    display_name: Option<String>,
+}
+
+impl DeleteRequest {
+    fn text(&self) -> String {
+        let count = self.files.len();
+
+        match (self.reason, &self.display_name) {
+            (DeleteReason::EmptyTrash, _) => String::from("Empty the trash?"),
+            (DeleteReason::TrashUnsupported, Some(name)) => {
+                format!(
+                    "'{}' can't be moved to the trash. Delete it permanently?",
+                    name
+                )
+            }
+            (DeleteReason::TrashUnsupported, None) => format!(
+                "{} items can't be moved to the trash. Delete them permanently?",
+                count
+            ),
+            (DeleteReason::Requested, Some(name)) => format!("Permanently delete '{}'?", name),
+            (DeleteReason::Requested, None) => format!("Permanently delete {} items?", count),
+        }
+    }
+
+    fn secondary_text(&self) -> String {
+        match self.reason {
+            DeleteReason::EmptyTrash => format!(
+                "All {} item{} in the trash will be permanently deleted.",
+                self.files.len(),
+                pluralize!(self.files.len()),
+            ),
+            _ => String::from("Deleted items can't be restored."),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteDialog {
+    /// Requests awaiting confirmation. The request at the front is displayed, and the dialog is
+    /// hidden if there are none.
+    requests: VecDeque<DeleteRequest>,
+}
+
+#[derive(Debug)]
+pub enum DeleteDialogMsg {
+    /// Ask the user to confirm deleting the files, unless confirmation is disabled in the
+    /// configuration.
+    Confirm {
+        files: Vec<gio::File>,
+        reason: DeleteReason,
+    },
+
+    /// Display a request once the display name of its file is known.
+    Enqueue(DeleteRequest),
+
+    Response(gtk::ResponseType),
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for DeleteDialog {
+    type Init = ();
+    type Input = DeleteDialogMsg;
+    type Output = AppMsg;
+
+    view! {
+        gtk::MessageDialog {
+            set_message_type: gtk::MessageType::Warning,
+            set_modal: true,
+            #[watch]
+            set_transient_for: windows::active_window().as_ref(),
+            #[watch]
+            set_visible: !model.requests.is_empty(),
+            #[watch]
+            set_text: model.requests.front().map(DeleteRequest::text).as_deref(),
+            #[watch]
+            set_secondary_text: model
+                .requests
+                .front()
+                .map(DeleteRequest::secondary_text)
+                .as_deref(),
+
+            add_button: ("Cancel", gtk::ResponseType::Cancel),
+            add_button: ("Delete", gtk::ResponseType::Accept),
+            set_default_response: gtk::ResponseType::Cancel,
+
+            connect_response[sender] => move |_, response| {
+                sender.input(DeleteDialogMsg::Response(response));
+            },
+        }
+    }
+
+    fn init(_: (), root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let model = DeleteDialog {
+            requests: VecDeque::new(),
+        };
+
+        let widgets = view_output!();
+
+        root.widget_for_response(gtk::ResponseType::Accept)
+            .unwrap()
+            .add_css_class("destructive-action");
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            DeleteDialogMsg::Confirm { files, reason } => {
+                let confirm = windows::config().confirm;
+                // Files that were meant to be trashed are always confirmed, since the user didn't
+                // ask for them to be deleted permanently.
+                let needs_confirmation = match reason {
+                    DeleteReason::Requested => confirm.delete,
+                    DeleteReason::TrashUnsupported => true,
+                    DeleteReason::EmptyTrash => confirm.empty_trash,
+                };
+
+                if !needs_confirmation {
+                    sender.output(AppMsg::Delete(files)).unwrap();
+                    return;
+                }
+
+                relm4::spawn_local(async move {
+                    let display_name = match &files[..] {
+                        [file] => Some(
+                            file.query_info_future(
+                                gio::FILE_ATTRIBUTE_STANDARD_DISPLAY_NAME,
+                                gio::FileQueryInfoFlags::NONE,
+                                glib::Priority::DEFAULT,
+                            )
+                            .await
+                            .map_or_else(
+                                |_| file.parse_name().into(),
+                                |info| info.display_name().into(),
+                            ),
+                        ),
+                        _ => None,
+                    };
+
+                    sender.input(DeleteDialogMsg::Enqueue(DeleteRequest {
+                        files,
+                        reason,
+                        display_name,
+                    }));
+                });
+            }
+            DeleteDialogMsg::Enqueue(request) => self.requests.push_back(request),
+            DeleteDialogMsg::Response(response) => {
+                if let Some(request) = self.requests.pop_front() {
+                    if response == gtk::ResponseType::Accept {
+                        sender.output(AppMsg::Delete(request.files)).unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
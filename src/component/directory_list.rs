@@ -1,45 +1,139 @@
 //! Factory widget that displays a listing of the contents of a directory.
 
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
+use adw::prelude::*;
 use anyhow::bail;
 use educe::Educe;
 use futures::prelude::*;
 use glib::clone;
 use glib::translate::{from_glib_full, IntoGlib};
-use relm4::actions::{ActionGroupName, RelmAction, RelmActionGroup};
+use relm4::actions::{ActionGroupName, ActionName, RelmAction, RelmActionGroup};
 use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
 use relm4::gtk::{gdk, gio, glib, pango, prelude::*};
 use relm4::prelude::*;
 use relm4::view;
 use tracing::*;
 
-use super::app::AppMsg;
+use super::app::{windows, AppMsg};
+use super::delete_dialog::{DeleteDialogMsg, DeleteReason, DELETE_BROKER};
 use super::new_folder_dialog::{NewFolderDialog, NewFolderDialogMsg};
-use crate::ops;
+use crate::config::{ClickToOpen, DirectorySort, SortKey, State, ViewMode};
+use crate::ops::journal::{self, Operation};
+use crate::ops::{self, clipboard};
+use crate::shortcuts::{self, DirectoryAction};
+use crate::thumbnail;
 use crate::util::{self, fmt_files_as_uris, BitsetExt, GFileInfoExt};
 
 mod actions;
+mod search;
 
 use actions::*;
-
-/// The requested minimum width of the widget.
-const WIDTH: i32 = 200;
+use search::Match;
 
 /// The spacing between elements of a list item.
 const SPACING: i32 = 2;
 
+/// The size of the icon or thumbnail of a list item.
+const ICON_SIZE: i32 = 16;
+
+/// The range of sizes of the icons and thumbnails in the icon view. Thumbnails aren't generated
+/// any larger than the maximum.
+const MIN_ICON_SIZE: i32 = 32;
+const MAX_ICON_SIZE: i32 = 128;
+
+/// The amount that the icon view is zoomed in or out by at a time.
+const ICON_SIZE_STEP: i32 = 16;
+
 /// Button number identifying the right click button on a mouse.
 const BUTTON_RIGHT_CLICK: u32 = 3;
 
+/// Button number identifying the middle click button on a mouse.
+const BUTTON_MIDDLE_CLICK: u32 = 2;
+
+/// Initial state of a directory listing.
+#[derive(Debug)]
+pub struct DirectoryInit {
+    pub dir: gio::File,
+    pub sort: DirectorySort,
+    pub show_hidden: bool,
+    pub view_mode: ViewMode,
+
+    /// Children of the directory to select once the listing has loaded. The first is scrolled
+    /// into view.
+    pub select: Vec<gio::File>,
+}
+
+impl DirectoryInit {
+    /// Initializes a listing of the directory in the given view mode, using its sort order from
+    /// the application state and the visibility of hidden files from the configuration.
+    pub fn new(dir: gio::File, state: &State, view_mode: ViewMode) -> Self {
+        let config = windows::config();
+
+        DirectoryInit {
+            sort: state.directory_sort(&dir.uri(), config.default_sort),
+            show_hidden: config.show_hidden,
+            view_mode,
+            select: vec![],
+            dir,
+        }
+    }
+
+    /// Select the given child of the directory once the listing has loaded, in addition to any
+    /// children that are already being selected.
+    pub fn selecting(mut self, file: gio::File) -> Self {
+        self.select.push(file);
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct Directory {
-    /// The sorted list model (with a selection) that is displayed in the list view.
+    /// The sorted and filtered list model (with a selection) that is displayed in the list view,
+    /// the column view or the grid view.
     list_model: gtk::MultiSelection,
 
+    /// How the entries are ordered.
+    sort: DirectorySort,
+
+    /// Whether the entries are displayed in the list view, the column view or the grid view.
+    view_mode: ViewMode,
+
+    /// The size of the icons in the grid view.
+    icon_size: i32,
+
+    /// The columns of the column view, along with the key that each column sorts by, if any.
+    columns: Vec<(Option<SortKey>, gtk::ColumnViewColumn)>,
+
+    /// Whether hidden and backup files are listed.
+    show_hidden: bool,
+
+    /// The type-ahead search query. Empty if there is no search in progress.
+    search_query: String,
+
+    /// Whether the search hides entries that don't match, rather than selecting matching entries.
+    search_filter: bool,
+
+    /// The configured width of the listing.
+    column_width: i32,
+
+    /// Files to select once the listing has loaded.
+    pending_selection: Vec<gio::File>,
+
+    /// The controllers binding the keyboard shortcuts of each view, which are replaced when the
+    /// configured shortcuts change.
+    shortcut_controllers: Vec<gtk::ShortcutController>,
+
+    /// The list items of the views that are bound to an entry, so that the entry displaying a
+    /// file can be found.
+    bound_items: Rc<RefCell<Vec<gtk::ListItem>>>,
+
     new_folder_dialog: Option<Controller<NewFolderDialog>>,
 }
 
@@ -56,13 +150,152 @@ impl Directory {
 
     /// Returns the underlying directory list model.
     fn directory_list(&self) -> gtk::DirectoryList {
-        self.list_model
-            .model()
-            .and_downcast::<gtk::SortListModel>()
-            .unwrap()
-            .model()
-            .and_downcast()
-            .unwrap()
+        self.filter_model().model().and_downcast().unwrap()
+    }
+
+    /// Returns the model that hides entries of the directory list.
+    fn filter_model(&self) -> gtk::FilterListModel {
+        self.sort_model().model().and_downcast().unwrap()
+    }
+
+    /// Returns the model that sorts the directory list.
+    fn sort_model(&self) -> gtk::SortListModel {
+        self.list_model.model().and_downcast().unwrap()
+    }
+
+    /// Update the filter of the listing to match the visibility of hidden files and the search.
+    fn update_filter(&self) {
+        let query = (self.search_filter && !self.search_query.is_empty())
+            .then(|| self.search_query.clone());
+
+        self.filter_model()
+            .set_filter(file_filter(self.show_hidden, query).as_ref());
+    }
+
+    /// Returns the positions of the listed entries matching the search query, in display order.
+    fn search_matches(&self) -> Vec<(u32, Match)> {
+        (0..self.list_model.n_items())
+            .filter_map(|pos| {
+                let info = self.list_model.item(pos).and_downcast::<gio::FileInfo>()?;
+                search::match_name(&self.search_query, &info.display_name()).map(|m| (pos, m))
+            })
+            .collect()
+    }
+
+    /// Select a single entry and scroll it into view.
+    fn select_and_reveal(&self, widgets: &DirectoryWidgets, pos: u32) {
+        self.list_model.select_item(pos, true);
+        self.scroll_to(widgets, pos);
+    }
+
+    /// Scroll an entry into view.
+    fn scroll_to(&self, widgets: &DirectoryWidgets, pos: u32) {
+        // The column view can't be scrolled to an item in the supported GTK versions, but the
+        // list view that displays its rows (its last child) can.
+        let view = match self.view_mode {
+            ViewMode::Columns => Some(widgets.list_view.clone().upcast()),
+            ViewMode::Details => widgets.column_view.last_child(),
+            ViewMode::Icons => Some(widgets.grid_view.clone().upcast()),
+        };
+
+        if let Some(view) = view {
+            let _ = view.activate_action("list.scroll-to-item", Some(&pos.to_variant()));
+        }
+    }
+
+    /// Returns the view that displays the entries in the current view mode.
+    fn view<'a>(&self, widgets: &'a DirectoryWidgets) -> &'a gtk::Widget {
+        match self.view_mode {
+            ViewMode::Columns => widgets.list_view.upcast_ref(),
+            ViewMode::Details => widgets.column_view.upcast_ref(),
+            ViewMode::Icons => widgets.grid_view.upcast_ref(),
+        }
+    }
+
+    /// Display the entries in the view of the current view mode. Only that view is given the
+    /// model, so that the hidden views don't create widgets for the entries.
+    fn update_view_mode(&self, widgets: &DirectoryWidgets) {
+        let model_for = |view_mode| (self.view_mode == view_mode).then_some(&self.list_model);
+
+        widgets.list_view.set_model(model_for(ViewMode::Columns));
+        widgets.column_view.set_model(model_for(ViewMode::Details));
+        widgets.grid_view.set_model(model_for(ViewMode::Icons));
+        widgets.views.set_visible_child_name(self.view_mode.id());
+        widgets
+            .search_bar
+            .set_key_capture_widget(Some(self.view(widgets)));
+
+        self.update_sorter(widgets);
+    }
+
+    /// Sort the entries in the configured order. In the column view, the entries are sorted by
+    /// the column whose header was clicked last, which starts out as the column of the sort key.
+    ///
+    /// Clicking a header only reorders the entries until the listing is closed, since the column
+    /// that is sorted by can't be read back in the supported GTK versions.
+    fn update_sorter(&self, widgets: &DirectoryWidgets) {
+        let sorter = match self.view_mode {
+            ViewMode::Columns | ViewMode::Icons => file_sorter(self.sort),
+            ViewMode::Details => {
+                let column = self
+                    .columns
+                    .iter()
+                    .find(|(key, _)| *key == Some(self.sort.key))
+                    .map(|(_, column)| column);
+
+                widgets.column_view.sort_by_column(
+                    column,
+                    if self.sort.descending {
+                        gtk::SortType::Descending
+                    } else {
+                        gtk::SortType::Ascending
+                    },
+                );
+
+                let sorter = gtk::MultiSorter::new();
+
+                if self.sort.folders_first {
+                    sorter.append(gtk::CustomSorter::new(|a, b| {
+                        let a = a.downcast_ref::<gio::FileInfo>().unwrap();
+                        let b = b.downcast_ref::<gio::FileInfo>().unwrap();
+
+                        cmp_folders_first(a, b).into()
+                    }));
+                }
+
+                if let Some(column_sorter) = widgets.column_view.sorter() {
+                    sorter.append(column_sorter);
+                }
+
+                // Entries that the column doesn't distinguish, or every entry if the sort key has
+                // no column, are left in the configured order.
+                sorter.append(file_sorter(self.sort));
+
+                sorter.upcast()
+            }
+        };
+
+        self.sort_model().set_sorter(Some(&sorter));
+    }
+
+    /// Reorder the entries, and notify the parent widget so that the order can be restored later.
+    fn set_sort(
+        &mut self,
+        widgets: &DirectoryWidgets,
+        sort: DirectorySort,
+        sender: &FactorySender<Self>,
+    ) {
+        info!("sorting {} by {:?}", self.dir().uri(), sort);
+
+        self.sort = sort;
+        self.update_sorter(widgets);
+
+        sender
+            .output(AppMsg::DirectorySortChanged {
+                dir: self.dir(),
+                sort,
+            })
+            .unwrap();
     }
 
     /// Returns the file info for the files that are currently selected.
@@ -79,6 +312,44 @@ impl Directory {
             })
             .collect()
     }
+
+    /// Show the rename popover of the entry displaying the first selected file. Nothing happens if
+    /// the entry isn't displayed by the current view.
+    fn rename_selection(&self, widgets: &DirectoryWidgets) {
+        let position = self.list_model.selection().minimum();
+        let Some(file) = self
+            .list_model
+            .item(position)
+            .and_downcast::<gio::FileInfo>()
+            .and_then(|info| info.file())
+        else {
+            return;
+        };
+
+        let view = self.view(widgets);
+        let entry = self
+            .bound_items
+            .borrow()
+            .iter()
+            .filter(|item| item.position() == position)
+            .filter_map(|item| item.child())
+            .find(|entry| entry.is_ancestor(view));
+
+        let Some(entry) = entry else {
+            warn!("no entry is displayed for {}", file.uri());
+            return;
+        };
+
+        let action = format!(
+            "{}.{}",
+            <DirectoryListRightClickActionGroup as ActionGroupName>::NAME,
+            <RenameAction as ActionName>::NAME,
+        );
+
+        if let Err(e) = entry.activate_action(&action, Some(&file.uri().to_variant())) {
+            warn!("unable to activate {}: {}", action, e);
+        }
+    }
 }
 
 /// Used to communicate the file selection status to the parent widget.
@@ -105,68 +376,266 @@ pub struct FileSelection {
     pub files: Vec<gio::File>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DirectoryMessage {
     OpenItemAtPosition(u32),
 
     /// Open the application launcher dialog for the given file.
     ChooseAndLaunchApp(gio::File),
 
+    /// Open the application launcher dialog for the first file in the current selection.
+    ChooseAppForSelection,
+
+    /// Show the rename popover of the first file in the current selection.
+    RenameSelection,
+
     /// Send the files in the current selection to the trash.
     TrashSelection,
 
     /// Restore files in the current selection from the trash.
     RestoreSelectionFromTrash,
 
+    /// Permanently delete the files in the current selection, after asking for confirmation.
+    DeleteSelection,
+
     ShowNewFolderDialog,
+
+    /// The configuration changed.
+    ConfigChanged,
+
+    /// The type-ahead search query changed.
+    SearchChanged(String),
+
+    /// Select the next entry matching the search query.
+    SearchNext,
+
+    /// Select the previous entry matching the search query.
+    SearchPrevious,
+
+    /// Whether the search hides entries that don't match.
+    SetSearchFilter(bool),
+
+    /// The search was closed, and all entries should be listed again.
+    StopSearch,
+
+    /// Sort the entries by a different key.
+    SetSortKey(SortKey),
+
+    /// Whether the entries are sorted in descending order.
+    SetSortDescending(bool),
+
+    /// Whether directories are listed before all other files.
+    SetFoldersFirst(bool),
+
+    /// Place the files in the current selection on the clipboard, to be moved when pasted.
+    CutSelection,
+
+    /// Place the files in the current selection on the clipboard, to be copied when pasted.
+    CopySelection,
+
+    /// Paste the files on the clipboard into the directory.
+    Paste,
+
+    /// The listing has loaded, so the file that was requested to be selected can be found.
+    SelectPending,
+
+    /// Zoom the grid view to show icons of the given size, which is rounded to the nearest step.
+    SetIconSize(i32),
+
+    /// Display the entries in the view of the given view mode.
+    SetViewMode(ViewMode),
 }
 
 #[relm4::factory(pub)]
 impl FactoryComponent for Directory {
     type ParentWidget = panel::Paned;
     type Widgets = DirectoryWidgets;
-    type Init = gio::File;
+    type Init = DirectoryInit;
     type Input = DirectoryMessage;
     type Output = AppMsg;
     type CommandOutput = ();
 
     view! {
-        root = gtk::Stack {
-            set_width_request: WIDTH,
-
-            add_child = &gtk::Spinner {
-                set_halign: gtk::Align::Center,
-                set_valign: gtk::Align::Center,
-                set_spinning: true,
-            } -> { set_name: "spinner" },
-
-            add_child = &gtk::ScrolledWindow {
-                set_hscrollbar_policy: gtk::PolicyType::Never,
-
-                #[wrap(Some)]
-                set_child = &gtk::Box {
-                    set_layout_manager: Some(gtk::BinLayout::new()),
-
-                    #[name = "list_view"]
-                    gtk::ListView {
-                        set_factory: Some(&factory),
-                        set_model: Some(&self.list_model),
+        root = util::NaturalWidthBin {
+            set_width_request: self.column_width,
+
+            #[name = "stack"]
+            #[wrap(Some)]
+            set_child = &gtk::Stack {
+                add_child = &gtk::Spinner {
+                    set_halign: gtk::Align::Center,
+                    set_valign: gtk::Align::Center,
+                    set_spinning: true,
+                } -> { set_name: "spinner" },
+
+                add_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+
+                    // Opened by typing while the list view has focus.
+                    #[name = "search_bar"]
+                    gtk::SearchBar {
+                        set_show_close_button: true,
+                        connect_search_mode_enabled_notify[sender] => move |this| {
+                            if !this.is_search_mode() {
+                                sender.input(DirectoryMessage::StopSearch);
+                            }
+                        },
 
-                        connect_activate[sender] => move |_, position| {
-                            sender.input(DirectoryMessage::OpenItemAtPosition(position))
+                        #[wrap(Some)]
+                        set_child = &gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_spacing: 6,
+
+                            #[name = "search_entry"]
+                            gtk::SearchEntry {
+                                set_hexpand: true,
+                                connect_search_changed[sender] => move |this| {
+                                    sender.input(DirectoryMessage::SearchChanged(this.text().into()));
+                                },
+                            },
+
+                            gtk::ToggleButton {
+                                set_label: "Filter",
+                                set_tooltip_text: Some("Only list matching files"),
+                                #[watch]
+                                #[block_signal(search_filter_handler)]
+                                set_active: self.search_filter,
+                                connect_toggled[sender] => move |this| {
+                                    sender.input(DirectoryMessage::SetSearchFilter(this.is_active()));
+                                } @search_filter_handler,
+                            },
                         },
                     },
 
-                    #[name = "context_menu"]
-                    gtk::PopoverMenu::from_model(gio::MenuModel::NONE) {
-                        set_has_arrow: false,
+                    // The children are named after the view modes.
+                    #[name = "views"]
+                    gtk::Stack {
+                        set_vexpand: true,
+
+                        add_child = &gtk::ScrolledWindow {
+                            set_hscrollbar_policy: gtk::PolicyType::Never,
+
+                            #[wrap(Some)]
+                            set_child = &gtk::Box {
+                                set_layout_manager: Some(gtk::BinLayout::new()),
+
+                                #[name = "list_view"]
+                                gtk::ListView {
+                                    set_factory: Some(&factory),
+                                    #[watch]
+                                    set_single_click_activate:
+                                        windows::config().click_to_open == ClickToOpen::Single,
+
+                                    connect_activate[sender] => move |_, position| {
+                                        sender.input(DirectoryMessage::OpenItemAtPosition(position))
+                                    },
+                                },
+
+                                #[name = "context_menu"]
+                                gtk::PopoverMenu::from_model(gio::MenuModel::NONE) {
+                                    set_has_arrow: false,
+                                },
+                            },
+                        } -> { set_name: "columns" },
+
+                        add_child = &gtk::ScrolledWindow {
+                            #[wrap(Some)]
+                            set_child = &gtk::Box {
+                                set_layout_manager: Some(gtk::BinLayout::new()),
+
+                                #[name = "column_view"]
+                                gtk::ColumnView {
+                                    add_css_class: "data-table",
+                                    #[watch]
+                                    set_single_click_activate:
+                                        windows::config().click_to_open == ClickToOpen::Single,
+
+                                    connect_activate[sender] => move |_, position| {
+                                        sender.input(DirectoryMessage::OpenItemAtPosition(position))
+                                    },
+                                },
+
+                                #[name = "details_context_menu"]
+                                gtk::PopoverMenu::from_model(gio::MenuModel::NONE) {
+                                    set_has_arrow: false,
+                                },
+                            },
+                        } -> { set_name: "details" },
+
+                        add_child = &gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+
+                            gtk::ScrolledWindow {
+                                set_hscrollbar_policy: gtk::PolicyType::Never,
+                                set_vexpand: true,
+
+                                #[wrap(Some)]
+                                set_child = &gtk::Box {
+                                    set_layout_manager: Some(gtk::BinLayout::new()),
+
+                                    #[name = "grid_view"]
+                                    gtk::GridView {
+                                        set_factory: Some(&entry_factory(
+                                            &self.list_model,
+                                            &self.bound_items,
+                                            &sender,
+                                            EntryLayout::Tile(self.icon_size),
+                                        )),
+                                        set_enable_rubberband: true,
+                                        set_max_columns: 32,
+                                        #[watch]
+                                        set_single_click_activate:
+                                            windows::config().click_to_open == ClickToOpen::Single,
+
+                                        connect_activate[sender] => move |_, position| {
+                                            sender.input(DirectoryMessage::OpenItemAtPosition(position))
+                                        },
+                                    },
+
+                                    #[name = "icons_context_menu"]
+                                    gtk::PopoverMenu::from_model(gio::MenuModel::NONE) {
+                                        set_has_arrow: false,
+                                    },
+                                },
+                            },
+
+                            #[name = "zoom_scale"]
+                            gtk::Scale::with_range(
+                                gtk::Orientation::Horizontal,
+                                f64::from(MIN_ICON_SIZE),
+                                f64::from(MAX_ICON_SIZE),
+                                f64::from(ICON_SIZE_STEP),
+                            ) {
+                                set_halign: gtk::Align::End,
+                                set_width_request: 150,
+                                set_tooltip_text: Some("Zoom (Ctrl+Scroll)"),
+                                #[watch]
+                                #[block_signal(zoom_handler)]
+                                set_value: f64::from(self.icon_size),
+                                connect_value_changed[sender] => move |scale| {
+                                    sender.input(DirectoryMessage::SetIconSize(
+                                        scale.value().round() as i32,
+                                    ));
+                                } @zoom_handler,
+                            },
+                        } -> { set_name: "icons" },
                     },
-                },
-            } -> { set_name: "listing" },
+                } -> { set_name: "listing" },
+            },
         }
     }
 
-    fn init_model(dir: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+    fn init_model(init: Self::Init, _index: &DynamicIndex, _sender: FactorySender<Self>) -> Self {
+        let DirectoryInit {
+            dir,
+            sort,
+            show_hidden,
+            view_mode,
+            select,
+        } = init;
+
+        let config = windows::config();
+
         debug_assert!(
             dir.query_file_type(gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
                 == gio::FileType::Directory
@@ -181,18 +650,44 @@ impl FactoryComponent for Directory {
                     &**gio::FILE_ATTRIBUTE_STANDARD_TYPE,
                     &**gio::FILE_ATTRIBUTE_STANDARD_CONTENT_TYPE,
                     &**gio::FILE_ATTRIBUTE_STANDARD_IS_SYMLINK,
+                    &**gio::FILE_ATTRIBUTE_STANDARD_IS_HIDDEN,
+                    &**gio::FILE_ATTRIBUTE_STANDARD_IS_BACKUP,
+                    &**gio::FILE_ATTRIBUTE_STANDARD_SIZE,
+                    &**gio::FILE_ATTRIBUTE_TIME_MODIFIED,
+                    &**gio::FILE_ATTRIBUTE_UNIX_MODE,
+                    &**gio::FILE_ATTRIBUTE_OWNER_USER,
+                    &**gio::FILE_ATTRIBUTE_THUMBNAIL_PATH,
+                    &**gio::FILE_ATTRIBUTE_THUMBNAIL_IS_VALID,
                 ]
                 .join(","),
             ),
             Some(&dir),
         );
 
-        let list_model = gtk::SortListModel::new(Some(directory_list.clone()), Some(file_sorter()));
+        // Hidden files are filtered out rather than excluded from the listing, so that the
+        // directory monitor still reports them and they can be revealed without reloading.
+        directory_list.set_monitored(true);
+
+        let list_model =
+            gtk::FilterListModel::new(Some(directory_list.clone()), file_filter(show_hidden, None));
+
+        let list_model = gtk::SortListModel::new(Some(list_model), Some(file_sorter(sort)));
 
         let list_model = gtk::MultiSelection::new(Some(list_model));
 
         Directory {
             list_model,
+            sort,
+            view_mode,
+            icon_size: round_icon_size(config.icon_size),
+            columns: vec![],
+            show_hidden,
+            search_query: String::new(),
+            search_filter: config.type_ahead_filter,
+            column_width: config.column_width,
+            pending_selection: select,
+            shortcut_controllers: vec![],
+            bound_items: Rc::default(),
 
             // This can't be initialized here, since we need make the dialog transient for
             // something but we don't have a reference to a widget here.
@@ -207,51 +702,12 @@ impl FactoryComponent for Directory {
         _returned_widget: &gtk::Widget,
         sender: FactorySender<Self>,
     ) -> Self::Widgets {
-        let factory = gtk::SignalListItemFactory::new();
-
-        factory.connect_setup(clone!(
-            #[strong]
-            sender,
-            #[weak(rename_to = selection)]
-            self.list_model,
-            move |_, item| {
-                let item = item.downcast_ref::<gtk::ListItem>().unwrap();
-                build_list_item_view(&selection, item, &sender);
-            }
-        ));
-
-        // Store the drop controllers we add by widget so that we can remove them on unbind.
-        #[allow(clippy::arc_with_non_send_sync)]
-        let controllers = Arc::new(Mutex::new(HashMap::new()));
-
-        factory.connect_bind(clone!(
-            #[strong]
-            sender,
-            #[strong]
-            controllers,
-            move |_, list_item| {
-                let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
-                let widget = list_item.child().unwrap();
-
-                let info = list_item.item().and_downcast::<gio::FileInfo>().unwrap();
-
-                if matches!(info.file_type(), gio::FileType::Directory) {
-                    let dir = info.file().unwrap();
-                    let target = new_drop_target_for_dir(dir, sender.clone());
-                    widget.add_controller(target.clone());
-                    controllers.lock().unwrap().insert(widget, target);
-                }
-            }
-        ));
-
-        factory.connect_unbind(move |_, list_item| {
-            let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
-            let widget = list_item.child().unwrap();
-
-            if let Some(controller) = controllers.lock().unwrap().remove(&widget) {
-                widget.remove_controller(&controller);
-            }
-        });
+        let factory = entry_factory(
+            &self.list_model,
+            &self.bound_items,
+            &sender,
+            EntryLayout::Row,
+        );
 
         let sender_ = sender.clone();
         self.list_model
@@ -264,37 +720,94 @@ impl FactoryComponent for Directory {
                 send_new_selection(selection, &sender_);
             });
 
+        self.columns = build_columns(&factory);
+
         let widgets = view_output!();
 
-        let click_controller = gtk::GestureClick::builder()
-            .button(BUTTON_RIGHT_CLICK)
-            .build();
-        let dir = self.dir();
+        for (_, column) in &self.columns {
+            widgets.column_view.append_column(column);
+        }
 
-        let menu = &widgets.context_menu;
+        widgets.search_bar.connect_entry(&widgets.search_entry);
 
-        click_controller.connect_pressed(clone!(
+        let search_key_controller = gtk::EventControllerKey::new();
+        search_key_controller.connect_key_pressed(clone!(
             #[strong]
-            menu,
-            move |_, _, x, y| {
-                let model = populate_directory_menu_model();
+            sender,
+            move |_, key, _, _| match key {
+                gdk::Key::Down => {
+                    sender.input(DirectoryMessage::SearchNext);
+                    glib::Propagation::Stop
+                }
+                gdk::Key::Up => {
+                    sender.input(DirectoryMessage::SearchPrevious);
+                    glib::Propagation::Stop
+                }
+                _ => glib::Propagation::Proceed,
+            }
+        ));
+        widgets.search_entry.add_controller(search_key_controller);
+
+        let dir = self.dir();
+
+        add_directory_context_menu(widgets.list_view.upcast_ref(), &widgets.context_menu);
+        add_directory_context_menu(
+            widgets.column_view.upcast_ref(),
+            &widgets.details_context_menu,
+        );
+        add_directory_context_menu(widgets.grid_view.upcast_ref(), &widgets.icons_context_menu);
 
-                menu.set_menu_model(Some(&model));
-                menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
-                menu.popup();
+        // The steps are accumulated, so that scrolling a touchpad zooms gradually.
+        let zoom_controller = gtk::EventControllerScroll::new(
+            gtk::EventControllerScrollFlags::VERTICAL | gtk::EventControllerScrollFlags::DISCRETE,
+        );
+        zoom_controller.connect_scroll(clone!(
+            #[strong(rename_to = zoom_scale)]
+            widgets.zoom_scale,
+            move |controller, _, dy| {
+                if !controller
+                    .current_event_state()
+                    .contains(gdk::ModifierType::CONTROL_MASK)
+                {
+                    return glib::Propagation::Proceed;
+                }
+
+                // Scrolling up zooms in.
+                zoom_scale.set_value(zoom_scale.value() - dy * f64::from(ICON_SIZE_STEP));
+                glib::Propagation::Stop
             }
         ));
-        register_directory_context_actions(widgets.list_view.upcast_ref(), sender.clone());
-        widgets.list_view.add_controller(click_controller);
+        widgets.grid_view.add_controller(zoom_controller);
+
+        for view in views(&widgets) {
+            register_directory_context_actions(view, self.sort, sender.clone());
+            self.shortcut_controllers
+                .push(add_directory_shortcuts(view, sender.clone()));
+            view.add_controller(new_drop_target_for_dir(self.dir(), sender.clone()));
+        }
+
+        self.update_view_mode(&widgets);
 
         self.directory_list()
-            .bind_property("loading", &widgets.root, "visible-child-name")
+            .bind_property("loading", &widgets.stack, "visible-child-name")
             .transform_to(|_, loading| Some(if loading { "spinner" } else { "listing" }))
             .sync_create()
             .build();
 
-        let drop_target = new_drop_target_for_dir(self.dir(), sender);
-        widgets.list_view.add_controller(drop_target);
+        if !self.pending_selection.is_empty() {
+            let directory_list = self.directory_list();
+
+            if directory_list.is_loading() {
+                let sender = sender.clone();
+                directory_list.connect_loading_notify(move |directory_list| {
+                    if !directory_list.is_loading() {
+                        sender.input(DirectoryMessage::SelectPending);
+                    }
+                });
+            } else {
+                sender.input(DirectoryMessage::SelectPending);
+            }
+        }
 
         self.new_folder_dialog = Some(
             NewFolderDialog::builder()
@@ -326,7 +839,15 @@ impl FactoryComponent for Directory {
                     file_info.display_name()
                 );
 
-                open_application_for_file(&file_info.file().unwrap(), &sender);
+                if self.view_mode.lists_single_directory()
+                    && file_info.file_type() == gio::FileType::Directory
+                {
+                    sender
+                        .output(AppMsg::OpenDirectory(file_info.file().unwrap()))
+                        .unwrap();
+                } else {
+                    open_application_for_file(&file_info.file().unwrap(), &sender);
+                }
             }
             DirectoryMessage::ChooseAndLaunchApp(file) => {
                 let dialog = gtk::AppChooserDialog::new(
@@ -352,6 +873,16 @@ impl FactoryComponent for Directory {
 
                 dialog.show();
             }
+            DirectoryMessage::ChooseAppForSelection => {
+                if let Some(file) = self
+                    .selected_file_info()
+                    .first()
+                    .and_then(|info| info.file())
+                {
+                    sender.input(DirectoryMessage::ChooseAndLaunchApp(file));
+                }
+            }
+            DirectoryMessage::RenameSelection => self.rename_selection(widgets),
             DirectoryMessage::TrashSelection => {
                 let selected_file_info = self.selected_file_info();
 
@@ -367,10 +898,17 @@ impl FactoryComponent for Directory {
                     }))
                     .await;
 
+                    // Files on volumes without a trash can only be deleted permanently.
+                    let mut untrashable_files = vec![];
+
                     let trashed_files = results
                         .into_iter()
                         .flat_map(|(result, info)| match result {
                             Ok(_) => Some(info),
+                            Err(e) if e.matches(gio::IOErrorEnum::NotSupported) => {
+                                untrashable_files.push(info.file().unwrap());
+                                None
+                            }
                             Err(e) => {
                                 sender.output(AppMsg::Error(Box::new(e))).unwrap();
                                 None
@@ -378,12 +916,29 @@ impl FactoryComponent for Directory {
                         })
                         .collect::<Vec<_>>();
 
+                    if !untrashable_files.is_empty() {
+                        DELETE_BROKER.send(DeleteDialogMsg::Confirm {
+                            files: untrashable_files,
+                            reason: DeleteReason::TrashUnsupported,
+                        });
+                    }
+
                     if !trashed_files.is_empty() {
+                        let operation = journal::record(Operation::Trash(
+                            trashed_files
+                                .iter()
+                                .map(|info| info.file().unwrap())
+                                .collect(),
+                        ));
+
                         sender
-                            .output(AppMsg::Toast(match &trashed_files[..] {
-                                [info] => format!("'{}' moved to trash", info.display_name()),
-                                _ => format!("{} files moved to trash", trashed_files.len()),
-                            }))
+                            .output(AppMsg::UndoToast {
+                                message: match &trashed_files[..] {
+                                    [info] => format!("'{}' moved to trash", info.display_name()),
+                                    _ => format!("{} files moved to trash", trashed_files.len()),
+                                },
+                                operation,
+                            })
                             .unwrap();
                     }
                 });
@@ -395,7 +950,11 @@ impl FactoryComponent for Directory {
 
                 let sender = sender.clone();
                 relm4::spawn_local(async move {
-                    future::join_all(selected_file_info.iter().map(|info| async {
+                    let batch = ops::Batch::new(selected_file_info.len());
+
+                    // The files are restored one after another, so that a resolution chosen for
+                    // all conflicts applies to the rest of them.
+                    for info in &selected_file_info {
                         let file = info.file().unwrap();
 
                         let info = file
@@ -410,7 +969,7 @@ impl FactoryComponent for Directory {
                             Ok(info) => info,
                             Err(err) => {
                                 sender.output(AppMsg::Error(Box::new(err))).unwrap();
-                                return;
+                                continue;
                             }
                         };
 
@@ -419,30 +978,354 @@ impl FactoryComponent for Directory {
                             .unwrap();
                         let original_path = gio::File::for_parse_name(&original_path);
 
-                        ops::move_(file, original_path, sender.output_sender().clone()).await;
-                    }))
-                    .await;
+                        ops::move_(
+                            file,
+                            original_path,
+                            batch.clone(),
+                            sender.output_sender().clone(),
+                        )
+                        .await;
+                    }
                 });
             }
+            DirectoryMessage::DeleteSelection => {
+                let files = self
+                    .selected_file_info()
+                    .iter()
+                    .map(|info| info.file().unwrap())
+                    .collect::<Vec<_>>();
+
+                if !files.is_empty() {
+                    DELETE_BROKER.send(DeleteDialogMsg::Confirm {
+                        files,
+                        reason: DeleteReason::Requested,
+                    });
+                }
+            }
+            DirectoryMessage::ConfigChanged => {
+                let config = windows::config();
+
+                // Listings that haven't been sorted explicitly follow the default sort order.
+                let sort = windows::state()
+                    .borrow()
+                    .directory_sort(&self.dir().uri(), config.default_sort);
+                if sort != self.sort {
+                    self.sort = sort;
+                    self.update_sorter(widgets);
+
+                    for view in views(widgets) {
+                        register_directory_context_actions(view, self.sort, sender.clone());
+                    }
+                }
+
+                let icon_size = round_icon_size(config.icon_size);
+                if icon_size != self.icon_size {
+                    self.icon_size = icon_size;
+                    widgets.grid_view.set_factory(Some(&entry_factory(
+                        &self.list_model,
+                        &self.bound_items,
+                        &sender,
+                        EntryLayout::Tile(self.icon_size),
+                    )));
+                }
+
+                if config.column_width != self.column_width {
+                    self.column_width = config.column_width;
+                    widgets.root.set_width_request(self.column_width);
+                }
+
+                if self.search_query.is_empty() {
+                    self.search_filter = config.type_ahead_filter;
+                }
+
+                self.show_hidden = config.show_hidden;
+                self.update_filter();
+
+                for (view, controller) in views(widgets)
+                    .into_iter()
+                    .zip(self.shortcut_controllers.drain(..))
+                {
+                    view.remove_controller(&controller);
+                }
+                for view in views(widgets) {
+                    self.shortcut_controllers
+                        .push(add_directory_shortcuts(view, sender.clone()));
+                }
+            }
+            DirectoryMessage::SearchChanged(query) => {
+                self.search_query = query;
+
+                if self.search_filter {
+                    self.update_filter();
+                } else if !self.search_query.is_empty() {
+                    // Prefer entries that start with the query over those that only contain it.
+                    let best_match = self.search_matches().into_iter().min_by_key(|&(_, m)| m);
+
+                    if let Some((pos, _)) = best_match {
+                        self.select_and_reveal(widgets, pos);
+                    }
+                }
+            }
+            DirectoryMessage::SearchNext | DirectoryMessage::SearchPrevious => {
+                let positions = self
+                    .search_matches()
+                    .into_iter()
+                    .map(|(pos, _)| pos)
+                    .collect::<Vec<_>>();
+
+                let selection = self.list_model.selection();
+                let current = (!selection.is_empty()).then(|| selection.minimum());
+
+                let next = if matches!(msg, DirectoryMessage::SearchNext) {
+                    positions
+                        .iter()
+                        .find(|&&pos| current.map_or(true, |current| pos > current))
+                        .or(positions.first())
+                } else {
+                    positions
+                        .iter()
+                        .rev()
+                        .find(|&&pos| current.map_or(true, |current| pos < current))
+                        .or(positions.last())
+                };
+
+                if let Some(&pos) = next {
+                    self.select_and_reveal(widgets, pos);
+                }
+            }
+            DirectoryMessage::SetSearchFilter(search_filter) => {
+                self.search_filter = search_filter;
+                self.update_filter();
+            }
+            DirectoryMessage::StopSearch => {
+                self.search_query.clear();
+                self.search_filter = windows::config().type_ahead_filter;
+                self.update_filter();
+
+                widgets.search_entry.set_text("");
+                self.view(widgets).grab_focus();
+            }
+            DirectoryMessage::SetSortKey(key) => {
+                self.set_sort(widgets, DirectorySort { key, ..self.sort }, &sender)
+            }
+            DirectoryMessage::SetSortDescending(descending) => self.set_sort(
+                widgets,
+                DirectorySort {
+                    descending,
+                    ..self.sort
+                },
+                &sender,
+            ),
+            DirectoryMessage::SetFoldersFirst(folders_first) => self.set_sort(
+                widgets,
+                DirectorySort {
+                    folders_first,
+                    ..self.sort
+                },
+                &sender,
+            ),
             DirectoryMessage::ShowNewFolderDialog => self
                 .new_folder_dialog
                 .as_ref()
                 .unwrap()
                 .emit(NewFolderDialogMsg::Show),
+            DirectoryMessage::CutSelection | DirectoryMessage::CopySelection => {
+                let files = self
+                    .selected_file_info()
+                    .iter()
+                    .map(|info| info.file().unwrap())
+                    .collect::<Vec<_>>();
+
+                if !files.is_empty() {
+                    let cut = matches!(msg, DirectoryMessage::CutSelection);
+                    clipboard::set_files(&widgets.root.clipboard(), &files, cut);
+                }
+            }
+            DirectoryMessage::Paste => {
+                relm4::spawn_local(clipboard::paste(
+                    widgets.root.clipboard(),
+                    self.dir(),
+                    sender.output_sender().clone(),
+                ));
+            }
+            DirectoryMessage::SelectPending => {
+                let positions = std::mem::take(&mut self.pending_selection)
+                    .into_iter()
+                    .filter_map(|file| {
+                        let pos = (0..self.list_model.n_items()).find(|&pos| {
+                            self.list_model
+                                .item(pos)
+                                .and_downcast::<gio::FileInfo>()
+                                .and_then(|info| info.file())
+                                .map_or(false, |child| child.equal(&file))
+                        });
+
+                        if pos.is_none() {
+                            warn!("unable to select {}: not listed", file.uri());
+                        }
+
+                        pos
+                    })
+                    .collect::<Vec<_>>();
+
+                match positions[..] {
+                    [] => (),
+                    [pos] => self.select_and_reveal(widgets, pos),
+                    [first, ..] => {
+                        // Select every file at once, so that the selection only changes once.
+                        let selected = gtk::Bitset::new_empty();
+                        for &pos in &positions {
+                            selected.add(pos);
+                        }
+
+                        let mask = gtk::Bitset::new_range(0, self.list_model.n_items());
+                        self.list_model.set_selection(&selected, &mask);
+                        self.scroll_to(widgets, first);
+                    }
+                }
+            }
+            DirectoryMessage::SetIconSize(icon_size) => {
+                let icon_size = round_icon_size(icon_size);
+
+                // The grid view is zoomed right away, rather than when the new configuration is
+                // applied, so that the slider follows the pointer.
+                if icon_size != self.icon_size {
+                    self.icon_size = icon_size;
+                    widgets.grid_view.set_factory(Some(&entry_factory(
+                        &self.list_model,
+                        &self.bound_items,
+                        &sender,
+                        EntryLayout::Tile(self.icon_size),
+                    )));
+
+                    sender.output(AppMsg::SetIconSize(icon_size)).unwrap();
+                }
+            }
+            DirectoryMessage::SetViewMode(view_mode) => {
+                if view_mode != self.view_mode {
+                    self.view_mode = view_mode;
+                    self.update_view_mode(widgets);
+                }
+            }
         }
 
         self.update_view(widgets, sender);
     }
 }
 
+/// How the entries of a view are laid out.
+#[derive(Debug, Clone, Copy)]
+enum EntryLayout {
+    /// A row with a small icon, the name of the file, and an arrow indicating if the entry is a
+    /// directory.
+    Row,
+
+    /// A tile with an icon of the given size, and the name of the file beneath it.
+    Tile(i32),
+}
+
+impl EntryLayout {
+    fn icon_size(self) -> i32 {
+        match self {
+            EntryLayout::Row => ICON_SIZE,
+            EntryLayout::Tile(size) => size,
+        }
+    }
+}
+
+/// Builds a factory that displays the entries of a listing with the given layout.
+///
+/// The entries behave the same in every layout: they have the same context menu and actions, they
+/// can be dragged, and files can be dropped on directories.
+fn entry_factory(
+    selection: &gtk::MultiSelection,
+    bound_items: &Rc<RefCell<Vec<gtk::ListItem>>>,
+    sender: &FactorySender<Directory>,
+    layout: EntryLayout,
+) -> gtk::SignalListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+
+    factory.connect_setup(clone!(
+        #[strong]
+        sender,
+        #[weak]
+        selection,
+        move |_, item| {
+            let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+            build_list_item_view(&selection, item, &sender, layout);
+        }
+    ));
+
+    // Store the drop controllers we add by widget so that we can remove them on unbind.
+    #[allow(clippy::arc_with_non_send_sync)]
+    let controllers = Arc::new(Mutex::new(HashMap::new()));
+
+    // Thumbnails being loaded, by list item, so that loading stops when the item is unbound.
+    let thumbnails = Rc::new(RefCell::new(HashMap::new()));
+
+    factory.connect_bind(clone!(
+        #[strong]
+        sender,
+        #[strong]
+        controllers,
+        #[strong]
+        thumbnails,
+        #[strong]
+        bound_items,
+        move |_, list_item| {
+            let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+            let widget = list_item.child().unwrap();
+            bound_items.borrow_mut().push(list_item.clone());
+
+            let info = list_item.item().and_downcast::<gio::FileInfo>().unwrap();
+
+            if matches!(info.file_type(), gio::FileType::Directory) {
+                let dir = info.file().unwrap();
+                let target = new_drop_target_for_dir(dir, sender.clone());
+                widget.add_controller(target.clone());
+                controllers.lock().unwrap().insert(widget, target);
+            } else if let Some(thumbnail) = show_thumbnail(list_item, &info, layout.icon_size()) {
+                thumbnails.borrow_mut().insert(list_item.clone(), thumbnail);
+            }
+        }
+    ));
+
+    factory.connect_unbind(clone!(
+        #[strong]
+        bound_items,
+        move |_, list_item| {
+            let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+            let widget = list_item.child().unwrap();
+            bound_items.borrow_mut().retain(|item| item != list_item);
+
+            if let Some(controller) = controllers.lock().unwrap().remove(&widget) {
+                widget.remove_controller(&controller);
+            }
+
+            if let Some(thumbnail) = thumbnails.borrow_mut().remove(list_item) {
+                thumbnail.abort();
+            }
+        }
+    ));
+
+    factory
+}
+
+/// Returns the nearest icon size that the grid view can be zoomed to.
+fn round_icon_size(size: i32) -> i32 {
+    ((size + ICON_SIZE_STEP / 2) / ICON_SIZE_STEP * ICON_SIZE_STEP)
+        .clamp(MIN_ICON_SIZE, MAX_ICON_SIZE)
+}
+
 /// Construct the view for an uninitialized list item, and set it as the item's child.
 ///
-/// This view displays an icon, the name of the file, and an arrow indicating if the item is a file
-/// or directory.
+/// In a row, this view displays an icon, the name of the file, and an arrow indicating if the item
+/// is a file or directory. In a tile, the name is displayed beneath a larger icon.
 fn build_list_item_view(
     selection: &gtk::MultiSelection,
     list_item: &gtk::ListItem,
     sender: &FactorySender<Directory>,
+    layout: EntryLayout,
 ) {
     view! {
         #[name = "root"]
@@ -493,12 +1376,12 @@ fn build_list_item_view(
 
     list_item
         .bind_property("item", &icon, "paintable")
-        .transform_to(|_, item: Option<gio::FileInfo>| {
+        .transform_to(move |_, item: Option<gio::FileInfo>| {
             item.map(|info| {
                 // FIXME: How inefficient is it to query this every time?
                 let icon_theme = gtk::IconTheme::for_display(&gdk::Display::default().unwrap());
 
-                util::icon_for_file(&icon_theme, 16, &info)
+                util::icon_for_file(&icon_theme, layout.icon_size(), &info)
             })
         })
         .build();
@@ -520,6 +1403,21 @@ fn build_list_item_view(
         })
         .build();
 
+    if let EntryLayout::Tile(size) = layout {
+        root.set_orientation(gtk::Orientation::Vertical);
+        root.set_width_request(size * 3 / 2);
+        icon.set_pixel_size(size);
+        directory_icon.set_visible(false);
+
+        // The label is only as wide as the tile, wrapping long names onto a second line. Without a
+        // small maximum width, its natural width would be the whole name on one line.
+        file_name.set_wrap(true);
+        file_name.set_wrap_mode(pango::WrapMode::WordChar);
+        file_name.set_lines(2);
+        file_name.set_justify(gtk::Justification::Center);
+        file_name.set_max_width_chars(1);
+    }
+
     let click_controller = gtk::GestureClick::builder()
         .button(BUTTON_RIGHT_CLICK)
         .build();
@@ -550,8 +1448,29 @@ fn build_list_item_view(
     ));
     root.add_controller(click_controller);
 
+    let middle_click_controller = gtk::GestureClick::builder()
+        .button(BUTTON_MIDDLE_CLICK)
+        .build();
+    let sender_ = sender.clone();
+    middle_click_controller.connect_pressed(clone!(
+        #[weak]
+        list_item,
+        move |_, _, _, _| {
+            let Some(info) = list_item.item().and_downcast::<gio::FileInfo>() else {
+                return;
+            };
+
+            if info.file_type() == gio::FileType::Directory {
+                sender_
+                    .output(AppMsg::OpenInNewTab(info.file().unwrap()))
+                    .unwrap();
+            }
+        }
+    ));
+    root.add_controller(middle_click_controller);
+
     let drag_source_controller = gtk::DragSource::builder()
-        .actions(gdk::DragAction::MOVE)
+        .actions(gdk::DragAction::COPY | gdk::DragAction::MOVE | gdk::DragAction::LINK)
         .build();
 
     // TODO: The documentation seems pretty adamant that you need to listen to `drag-end` if you're
@@ -584,6 +1503,185 @@ fn build_list_item_view(
     list_item.set_child(Some(&root));
 }
 
+/// Replace the icon of a list item with a thumbnail of its file, if one is cached or can be
+/// generated.
+///
+/// Returns the task that loads the thumbnail, which must be aborted if the list item is rebound to
+/// another file.
+fn show_thumbnail(
+    list_item: &gtk::ListItem,
+    info: &gio::FileInfo,
+    size: i32,
+) -> Option<glib::JoinHandle<()>> {
+    let icon = list_item
+        .child()
+        .and_then(|root| root.first_child())
+        .and_downcast::<gtk::Image>()
+        .unwrap();
+
+    let cached = thumbnail::cached(info);
+    let file = info.file()?;
+    let info = info.clone();
+
+    Some(relm4::spawn_local(async move {
+        let path = match cached {
+            Some(path) => path,
+            None => match thumbnail::generate(&file, &info).await {
+                Some(path) => path,
+                None => return,
+            },
+        };
+
+        let texture = gio::spawn_blocking(clone!(
+            #[strong]
+            path,
+            move || gdk::Texture::from_filename(path)
+        ))
+        .await
+        .expect("loading a thumbnail panicked");
+
+        match texture {
+            Ok(texture) => {
+                let icon_theme = gtk::IconTheme::for_display(&icon.display());
+                icon.set_paintable(Some(&util::with_emblems(
+                    &icon_theme,
+                    size,
+                    &info,
+                    texture.upcast(),
+                )));
+            }
+            Err(e) => warn!("unable to load thumbnail {}: {}", path.display(), e),
+        }
+    }))
+}
+
+/// Builds the columns of the column view. The name column displays each entry with the same
+/// factory as the list view, so that entries behave the same in both views.
+///
+/// Returns each column along with the key that it sorts by, if the key is one that the listing
+/// can be sorted by.
+fn build_columns(
+    name_factory: &gtk::SignalListItemFactory,
+) -> Vec<(Option<SortKey>, gtk::ColumnViewColumn)> {
+    let name = gtk::ColumnViewColumn::new(Some("Name"), Some(name_factory.clone()));
+    name.set_expand(true);
+    name.set_resizable(true);
+    name.set_sorter(Some(&file_sorter(DirectorySort::default())));
+
+    let size = label_column("Size", 1.0, |info| {
+        if info.file_type() == gio::FileType::Directory {
+            String::new()
+        } else {
+            glib::format_size(info.size() as u64).into()
+        }
+    });
+
+    let modified = label_column("Modified", 0.0, |info| {
+        info.modification_date_time()
+            .and_then(|dt| dt.format(&windows::config().date_format.short).ok())
+            .map(String::from)
+            .unwrap_or_default()
+    });
+
+    let type_ = label_column("Type", 0.0, |info| {
+        info.content_type()
+            .map(|content_type| gio::content_type_get_description(&content_type).into())
+            .unwrap_or_default()
+    });
+
+    let permissions = label_column("Permissions", 0.0, format_permissions);
+    permissions.set_sorter(Some(&attribute_sorter(|info| {
+        info.attribute_uint32(gio::FILE_ATTRIBUTE_UNIX_MODE)
+    })));
+
+    let owner = label_column("Owner", 0.0, |info| {
+        info.attribute_string(gio::FILE_ATTRIBUTE_OWNER_USER)
+            .map(String::from)
+            .unwrap_or_default()
+    });
+    owner.set_sorter(Some(&attribute_sorter(|info| {
+        info.attribute_string(gio::FILE_ATTRIBUTE_OWNER_USER)
+    })));
+
+    let mut columns = vec![(Some(SortKey::Name), name)];
+
+    for (key, column) in [
+        (SortKey::Size, size),
+        (SortKey::Modified, modified),
+        (SortKey::Type, type_),
+    ] {
+        column.set_sorter(Some(&file_sorter(DirectorySort {
+            key,
+            ..DirectorySort::default()
+        })));
+        columns.push((Some(key), column));
+    }
+
+    columns.push((None, permissions));
+    columns.push((None, owner));
+
+    columns
+}
+
+/// Builds a resizable column that displays a line of text for each entry.
+fn label_column(
+    title: &str,
+    xalign: f32,
+    text: fn(&gio::FileInfo) -> String,
+) -> gtk::ColumnViewColumn {
+    let factory = gtk::SignalListItemFactory::new();
+
+    factory.connect_setup(move |_, list_item| {
+        let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+
+        let label = gtk::Label::builder()
+            .xalign(xalign)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build();
+
+        list_item.set_child(Some(&label));
+    });
+
+    factory.connect_bind(move |_, list_item| {
+        let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+        let label = list_item.child().and_downcast::<gtk::Label>().unwrap();
+        let info = list_item.item().and_downcast::<gio::FileInfo>().unwrap();
+
+        label.set_label(&text(&info));
+    });
+
+    let column = gtk::ColumnViewColumn::new(Some(title), Some(factory));
+    column.set_resizable(true);
+    column
+}
+
+/// Formats the type and permissions of a file the way `ls -l` does, such as `drwxr-xr-x`.
+fn format_permissions(info: &gio::FileInfo) -> String {
+    if !info.has_attribute(gio::FILE_ATTRIBUTE_UNIX_MODE) {
+        return String::new();
+    }
+
+    let mode = info.attribute_uint32(gio::FILE_ATTRIBUTE_UNIX_MODE);
+
+    let file_type = if info.is_symlink() {
+        'l'
+    } else if info.file_type() == gio::FileType::Directory {
+        'd'
+    } else {
+        '-'
+    };
+
+    let permissions = (0..9).map(|i| {
+        if mode & (0o400 >> i) != 0 {
+            ['r', 'w', 'x'][i % 3]
+        } else {
+            '-'
+        }
+    });
+
+    std::iter::once(file_type).chain(permissions).collect()
+}
+
 /// Register right-click context menu actions and handlers.
 fn register_entry_context_actions(
     list_item_view: &gtk::Widget,
@@ -663,7 +1761,12 @@ fn register_entry_context_actions(
                             bail!("File name cannot be empty.");
                         }
 
-                        file.set_display_name(&new_name, gio::Cancellable::NONE)?;
+                        let renamed = file.set_display_name(&new_name, gio::Cancellable::NONE)?;
+
+                        journal::record(Operation::Rename {
+                            from: file.clone(),
+                            to: renamed,
+                        });
 
                         Ok(())
                     })();
@@ -682,17 +1785,32 @@ fn register_entry_context_actions(
         }
     )));
 
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<CutSelectionAction>::new_stateless(move |_| {
+        sender_.input(DirectoryMessage::CutSelection)
+    }));
+
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<CopySelectionAction>::new_stateless(
+        move |_| sender_.input(DirectoryMessage::CopySelection),
+    ));
+
     let sender_ = sender.clone();
     group.add_action(RelmAction::<TrashSelectionAction>::new_stateless(
         move |_| sender_.input(DirectoryMessage::TrashSelection),
     ));
 
+    let sender_ = sender.clone();
     group.add_action(
         RelmAction::<RestoreSelectionFromTrashAction>::new_stateless(move |_| {
-            sender.input(DirectoryMessage::RestoreSelectionFromTrash)
+            sender_.input(DirectoryMessage::RestoreSelectionFromTrash)
         }),
     );
 
+    group.add_action(RelmAction::<DeleteSelectionAction>::new_stateless(
+        move |_| sender.input(DirectoryMessage::DeleteSelection),
+    ));
+
     let actions = group.into_action_group();
     list_item_view.insert_action_group(
         <DirectoryListRightClickActionGroup as ActionGroupName>::NAME,
@@ -700,45 +1818,200 @@ fn register_entry_context_actions(
     );
 }
 
+/// Returns every view of a listing. Only one is shown at a time, but they share their controllers
+/// and actions so that any of them can be switched to.
+fn views(widgets: &DirectoryWidgets) -> [&gtk::Widget; 3] {
+    [
+        widgets.list_view.upcast_ref(),
+        widgets.column_view.upcast_ref(),
+        widgets.grid_view.upcast_ref(),
+    ]
+}
+
+/// Show the directory's right-click context menu when a view is right-clicked.
+fn add_directory_context_menu(view: &gtk::Widget, menu: &gtk::PopoverMenu) {
+    let click_controller = gtk::GestureClick::builder()
+        .button(BUTTON_RIGHT_CLICK)
+        .build();
+
+    click_controller.connect_pressed(clone!(
+        #[strong]
+        menu,
+        move |_, _, x, y| {
+            let model = populate_directory_menu_model();
+
+            menu.set_menu_model(Some(&model));
+            menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+            menu.popup();
+        }
+    ));
+
+    view.add_controller(click_controller);
+}
+
 fn register_directory_context_actions(
     directory_list_view: &gtk::Widget,
+    sort: DirectorySort,
     sender: FactorySender<Directory>,
 ) {
     let mut group = RelmActionGroup::<DirectoryListRightClickActionGroup>::new();
 
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<SortByAction>::new_stateful_with_target_value(
+        &sort.key.id().to_owned(),
+        move |_, state: &mut String, id: String| {
+            if let Some(key) = SortKey::from_id(&id) {
+                *state = id;
+                sender_.input(DirectoryMessage::SetSortKey(key));
+            }
+        },
+    ));
+
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<SortDescendingAction>::new_stateful(
+        &sort.descending,
+        move |_, state: &mut bool| {
+            *state = !*state;
+            sender_.input(DirectoryMessage::SetSortDescending(*state));
+        },
+    ));
+
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<FoldersFirstAction>::new_stateful(
+        &sort.folders_first,
+        move |_, state: &mut bool| {
+            *state = !*state;
+            sender_.input(DirectoryMessage::SetFoldersFirst(*state));
+        },
+    ));
+
+    let sender_ = sender.clone();
     group.add_action(RelmAction::<NewFolderAction>::new_stateless(move |_| {
-        sender.input(DirectoryMessage::ShowNewFolderDialog)
+        sender_.input(DirectoryMessage::ShowNewFolderDialog)
+    }));
+
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<CutSelectionAction>::new_stateless(move |_| {
+        sender_.input(DirectoryMessage::CutSelection)
+    }));
+
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<CopySelectionAction>::new_stateless(
+        move |_| sender_.input(DirectoryMessage::CopySelection),
+    ));
+
+    let sender_ = sender.clone();
+    group.add_action(RelmAction::<PasteAction>::new_stateless(move |_| {
+        sender_.input(DirectoryMessage::Paste)
     }));
 
+    group.add_action(RelmAction::<DeleteSelectionAction>::new_stateless(
+        move |_| sender.input(DirectoryMessage::DeleteSelection),
+    ));
+
     directory_list_view.insert_action_group(
         <DirectoryListRightClickActionGroup as ActionGroupName>::NAME,
         Some(&group.into_action_group()),
     );
 }
 
-/// Builds a new drop target that copies files to the given directory.
+/// Add the keyboard shortcuts for the actions of a directory listing to one of its views.
+///
+/// The shortcuts are handled by the view rather than the application, so that they apply to the
+/// directory that has focus. Returns the controller, so that it can be replaced when the
+/// configured accelerators change.
+fn add_directory_shortcuts(
+    view: &gtk::Widget,
+    sender: FactorySender<Directory>,
+) -> gtk::ShortcutController {
+    let controller = gtk::ShortcutController::new();
+    let config = windows::config();
+
+    for shortcut in shortcuts::DIRECTORY
+        .iter()
+        .flat_map(|group| group.shortcuts)
+    {
+        let shortcuts::Action::Directory(action) = shortcut.action else {
+            continue;
+        };
+
+        for accel in shortcuts::accels(shortcut, &config) {
+            let sender = sender.clone();
+
+            controller.add_shortcut(gtk::Shortcut::new(
+                gtk::ShortcutTrigger::parse_string(&accel),
+                Some(gtk::CallbackAction::new(move |_, _| {
+                    let message = match action {
+                        DirectoryAction::OpenChooser => DirectoryMessage::ChooseAppForSelection,
+                        DirectoryAction::NewFolder => DirectoryMessage::ShowNewFolderDialog,
+                        DirectoryAction::Rename => DirectoryMessage::RenameSelection,
+                        DirectoryAction::CutSelection => DirectoryMessage::CutSelection,
+                        DirectoryAction::CopySelection => DirectoryMessage::CopySelection,
+                        DirectoryAction::Paste => DirectoryMessage::Paste,
+                        // Whether the trash is used is decided when the shortcut is pressed, so
+                        // that it follows the configuration.
+                        DirectoryAction::TrashSelection if windows::config().use_trash => {
+                            DirectoryMessage::TrashSelection
+                        }
+                        DirectoryAction::RestoreSelectionFromTrash => {
+                            DirectoryMessage::RestoreSelectionFromTrash
+                        }
+                        DirectoryAction::TrashSelection | DirectoryAction::DeleteSelection => {
+                            DirectoryMessage::DeleteSelection
+                        }
+                    };
+
+                    sender.input(message);
+                    glib::Propagation::Stop
+                })),
+            ));
+        }
+    }
+
+    view.add_controller(controller.clone());
+
+    controller
+}
+
+/// Builds a new drop target that moves, copies, or links files to the given directory.
 ///
 /// The drop target accepts [`gio::File`]s and rejects files that are already in the same
-/// directory.
+/// directory. The action is chosen by the modifiers held at the time of the drop.
 fn new_drop_target_for_dir(dir: gio::File, sender: FactorySender<Directory>) -> gtk::DropTarget {
     let drop_target = gtk::DropTarget::builder()
-        .actions(gdk::DragAction::MOVE)
+        .actions(gdk::DragAction::COPY | gdk::DragAction::MOVE | gdk::DragAction::LINK)
         .preload(true)
         .build();
 
-    drop_target.set_types(&[gio::File::static_type()]);
+    drop_target.set_types(&[gdk::FileList::static_type(), gio::File::static_type()]);
+
+    drop_target
+        .connect_enter(|this, _, _| ops::drop_action_for_modifiers(this.current_event_state()));
+    drop_target
+        .connect_motion(|this, _, _| ops::drop_action_for_modifiers(this.current_event_state()));
 
     drop_target.connect_value_notify(clone!(
         #[strong]
         dir,
         move |this| {
             if let Some(value) = this.value() {
-                let file = value.get::<gio::File>().unwrap();
+                let files = ops::dropped_files(&value);
+
+                info!(
+                    "attempting to drop files {:?}",
+                    files.iter().map(|file| file.uri()).collect::<Vec<_>>()
+                );
 
-                info!("attempting to drop file {}", file.uri());
+                // Copying files into their own directory creates numbered copies of them, but any
+                // other action would do nothing.
+                let action = ops::drop_action_for_modifiers(this.current_event_state());
 
-                if file.parent().as_ref() == Some(&dir) {
-                    info!("rejecting drop; file is already in directory");
+                if action != gdk::DragAction::COPY
+                    && files
+                        .iter()
+                        .all(|file| file.parent().map_or(false, |parent| parent.equal(&dir)))
+                {
+                    info!("rejecting drop; files are already in directory");
                     this.reject();
                 }
             }
@@ -748,8 +2021,9 @@ fn new_drop_target_for_dir(dir: gio::File, sender: FactorySender<Directory>) ->
     drop_target.connect_drop(clone!(
         #[strong]
         dir,
-        move |_, value, _, _| {
-            ops::handle_drop(value, &dir, sender.output_sender().clone());
+        move |this, value, _, _| {
+            let action = ops::drop_action_for_modifiers(this.current_event_state());
+            ops::handle_drop(value, &dir, action, sender.output_sender().clone());
 
             true
         }
@@ -772,6 +2046,10 @@ fn build_selection(selection: &gtk::MultiSelection) -> Selection {
             .unwrap()
             .model()
             .unwrap()
+            .downcast::<gtk::FilterListModel>()
+            .unwrap()
+            .model()
+            .unwrap()
             .downcast::<gtk::DirectoryList>()
             .unwrap();
         let dir = directory_list.file().unwrap();
@@ -826,6 +2104,13 @@ fn populate_entry_menu_model(file_info: &gio::FileInfo) -> gio::Menu {
         &RelmAction::<OpenChooserAction>::to_menu_item_with_target_value("Open with...", &uri),
     );
 
+    let clipboard_section = gio::Menu::new();
+
+    menu_model.append_section(None, &clipboard_section);
+
+    clipboard_section.append_item(&RelmAction::<CutSelectionAction>::to_menu_item("Cut"));
+    clipboard_section.append_item(&RelmAction::<CopySelectionAction>::to_menu_item("Copy"));
+
     let modify_section = gio::Menu::new();
 
     menu_model.append_section(None, &modify_section);
@@ -845,6 +2130,10 @@ fn populate_entry_menu_model(file_info: &gio::FileInfo) -> gio::Menu {
         );
     }
 
+    modify_section.append_item(&RelmAction::<DeleteSelectionAction>::to_menu_item(
+        "Delete Permanently...",
+    ));
+
     menu_model.freeze();
 
     menu_model
@@ -862,6 +2151,38 @@ fn populate_directory_menu_model() -> gio::Menu {
         "New Folder...",
     ));
 
+    let clipboard_section = gio::Menu::new();
+
+    model.append_section(None, &clipboard_section);
+
+    clipboard_section.append_item(&RelmAction::<PasteAction>::to_menu_item("Paste"));
+
+    let sort_menu = gio::Menu::new();
+
+    let sort_key_section = gio::Menu::new();
+
+    for key in SortKey::ALL {
+        sort_key_section.append_item(&RelmAction::<SortByAction>::to_menu_item_with_target_value(
+            key.label(),
+            &key.id().to_owned(),
+        ));
+    }
+
+    sort_menu.append_section(None, &sort_key_section);
+
+    let sort_options_section = gio::Menu::new();
+
+    sort_options_section.append_item(&RelmAction::<SortDescendingAction>::to_menu_item(
+        "Descending",
+    ));
+    sort_options_section.append_item(&RelmAction::<FoldersFirstAction>::to_menu_item(
+        "Folders First",
+    ));
+
+    sort_menu.append_section(None, &sort_options_section);
+
+    model.append_submenu(Some("Sort By"), &sort_menu);
+
     model.freeze();
     model
 }
@@ -878,19 +2199,114 @@ fn open_application_for_file(file: &gio::File, sender: &FactorySender<Directory>
 }
 
 /// Constructs a new sorter used to sort directory entries.
-fn file_sorter() -> gtk::Sorter {
+fn file_sorter(sort: DirectorySort) -> gtk::Sorter {
+    gtk::CustomSorter::new(move |a, b| {
+        let a = a.downcast_ref::<gio::FileInfo>().unwrap();
+        let b = b.downcast_ref::<gio::FileInfo>().unwrap();
+
+        if sort.folders_first {
+            let ordering = cmp_folders_first(a, b);
+
+            if ordering != Ordering::Equal {
+                return ordering.into();
+            }
+        }
+
+        let ordering = match sort.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size().cmp(&b.size()),
+            SortKey::Modified => a
+                .attribute_uint64(gio::FILE_ATTRIBUTE_TIME_MODIFIED)
+                .cmp(&b.attribute_uint64(gio::FILE_ATTRIBUTE_TIME_MODIFIED)),
+            SortKey::Type => a.content_type().cmp(&b.content_type()),
+            SortKey::Extension => extension(a).cmp(&extension(b)),
+        }
+        .then_with(|| cmp_names(a, b));
+
+        if sort.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+        .into()
+    })
+    .upcast()
+}
+
+/// Constructs a new sorter that sorts directory entries by an attribute, and then by name.
+fn attribute_sorter<K: Ord>(key: fn(&gio::FileInfo) -> K) -> gtk::Sorter {
     gtk::CustomSorter::new(move |a, b| {
         let a = a.downcast_ref::<gio::FileInfo>().unwrap();
         let b = b.downcast_ref::<gio::FileInfo>().unwrap();
 
-        a.display_name()
-            .to_lowercase()
-            .cmp(&b.display_name().to_lowercase())
-            .into()
+        key(a).cmp(&key(b)).then_with(|| cmp_names(a, b)).into()
     })
     .upcast()
 }
 
+/// Orders directories before all other files.
+fn cmp_folders_first(a: &gio::FileInfo, b: &gio::FileInfo) -> Ordering {
+    let a_is_dir = a.file_type() == gio::FileType::Directory;
+    let b_is_dir = b.file_type() == gio::FileType::Directory;
+
+    b_is_dir.cmp(&a_is_dir)
+}
+
+/// Orders entries by their display names, comparing numbers within the names by their value.
+fn cmp_names(a: &gio::FileInfo, b: &gio::FileInfo) -> Ordering {
+    collation_key(a).cmp(collation_key(b))
+}
+
+/// Returns the collation key of an entry's display name. Keys are expensive to compute, so the key
+/// is cached on the entry the first time it's needed, rather than on every comparison.
+fn collation_key(info: &gio::FileInfo) -> &glib::FilenameCollationKey {
+    const KEY: &str = "fm-collation-key";
+
+    // SAFETY: The data is only set here, always with the same type, and is never replaced or
+    // removed afterwards, so the reference lives as long as the entry. Entries are replaced rather
+    // than modified when their file changes, so the key can't become stale.
+    unsafe {
+        if info.data::<glib::FilenameCollationKey>(KEY).is_none() {
+            info.set_data(
+                KEY,
+                glib::FilenameCollationKey::from(info.display_name().as_str()),
+            );
+        }
+
+        info.data::<glib::FilenameCollationKey>(KEY)
+            .unwrap()
+            .as_ref()
+    }
+}
+
+/// Returns a filter that hides hidden and backup files unless `show_hidden` is set, and files that
+/// don't match the search query, if any. Returns `None` if all files should be listed.
+fn file_filter(show_hidden: bool, query: Option<String>) -> Option<gtk::Filter> {
+    if show_hidden && query.is_none() {
+        return None;
+    }
+
+    let filter = gtk::CustomFilter::new(move |item| {
+        let info = item.downcast_ref::<gio::FileInfo>().unwrap();
+
+        let is_visible = show_hidden || !info.is_hidden() && !info.is_backup();
+        let is_match = query.as_ref().map_or(true, |query| {
+            search::match_name(query, &info.display_name()).is_some()
+        });
+
+        is_visible && is_match
+    });
+
+    Some(filter.upcast())
+}
+
+/// Returns the lowercase extension of a file's display name, if any.
+fn extension(info: &gio::FileInfo) -> Option<String> {
+    Path::new(info.display_name().as_str())
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
 /// Returns a formattable object for a list of [`gio::FileInfo`] objects. Used to log the return
 /// value of [`Directory::selected_file_info`].
 fn fmt_file_info(info: &[gio::FileInfo]) -> impl Debug + '_ {
@@ -63,3 +63,75 @@ impl ActionName for RestoreSelectionFromTrashAction {
 
     const NAME: &'static str = "restore-selection-from-trash";
 }
+
+pub struct CutSelectionAction;
+
+impl ActionName for CutSelectionAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = ();
+    type State = ();
+
+    const NAME: &'static str = "cut-selection";
+}
+
+pub struct CopySelectionAction;
+
+impl ActionName for CopySelectionAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = ();
+    type State = ();
+
+    const NAME: &'static str = "copy-selection";
+}
+
+pub struct PasteAction;
+
+impl ActionName for PasteAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = ();
+    type State = ();
+
+    const NAME: &'static str = "paste";
+}
+
+pub struct DeleteSelectionAction;
+
+impl ActionName for DeleteSelectionAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = ();
+    type State = ();
+
+    const NAME: &'static str = "delete-selection";
+}
+
+pub struct SortByAction;
+
+impl ActionName for SortByAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = String;
+
+    /// The ID of the current [`SortKey`](crate::config::SortKey).
+    type State = String;
+
+    const NAME: &'static str = "sort-by";
+}
+
+pub struct SortDescendingAction;
+
+impl ActionName for SortDescendingAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = ();
+    type State = bool;
+
+    const NAME: &'static str = "sort-descending";
+}
+
+pub struct FoldersFirstAction;
+
+impl ActionName for FoldersFirstAction {
+    type Group = DirectoryListRightClickActionGroup;
+    type Target = ();
+    type State = bool;
+
+    const NAME: &'static str = "folders-first";
+}
@@ -0,0 +1,59 @@
+//! Matching directory entries against a type-ahead search query.
+
+/// How the name of an entry matched a query.
+///
+/// Variants are ordered from the best kind of match to the worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Match {
+    /// The name starts with the query.
+    Prefix,
+
+    /// The characters of the query appear in order within the name, though not necessarily next
+    /// to each other.
+    Fuzzy,
+}
+
+/// Matches a name against a query, ignoring case. Returns `None` if the name doesn't match.
+pub fn match_name(query: &str, name: &str) -> Option<Match> {
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+
+    if name.starts_with(&query) {
+        return Some(Match::Prefix);
+    }
+
+    let mut name_chars = name.chars();
+
+    query
+        .chars()
+        .all(|q| name_chars.any(|c| c == q))
+        .then_some(Match::Fuzzy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_name, Match};
+
+    #[test]
+    fn prefix() {
+        assert_eq!(match_name("car", "Cargo.toml"), Some(Match::Prefix));
+        assert_eq!(match_name("", "anything"), Some(Match::Prefix));
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(match_name("ctl", "Cargo.toml"), Some(Match::Fuzzy));
+        assert_eq!(match_name("TOML", "Cargo.toml"), Some(Match::Fuzzy));
+    }
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert_eq!(match_name("tc", "Cargo.toml"), None);
+        assert_eq!(match_name("oo", "Cargo"), None);
+    }
+
+    #[test]
+    fn prefix_is_better_than_fuzzy() {
+        assert!(Match::Prefix < Match::Fuzzy);
+    }
+}
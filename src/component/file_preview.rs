@@ -2,10 +2,11 @@
 
 use std::error::Error;
 use std::io;
+use std::rc::Rc;
 
 use futures::stream::{AbortHandle, Abortable, Aborted};
 use futures::{future, prelude::*};
-use glib::GString;
+use glib::{clone, GString};
 use gtk::{gdk, gio, glib};
 use itertools::{Itertools, MinMaxResult};
 use mime::Mime;
@@ -15,23 +16,18 @@ use sourceview::{prelude::*, Language};
 use sourceview5 as sourceview;
 use tracing::*;
 
+use super::app::windows;
 use super::directory_list::FileSelection;
 use crate::util::{self, pluralize};
 
 mod pdf;
 
-use pdf::{Pdf, PdfPageChange};
+use pdf::{Pdf, PdfMatchChange, PdfMetadata, PdfPageChange};
 
 /// The buffer size used to read the beginning of a file to predict its mime type and preview its
 /// contents.
 const PREVIEW_BUFFER_SIZE: usize = 4096;
 
-/// Date format used when a single file is selected.
-const LONG_DATE_FORMAT: &str = "%A, %B %-d, %Y at %-I:%M %p";
-
-/// Date format used when multiple files are selected.
-const SHORT_DATE_FORMAT: &str = "%b %-d, %Y";
-
 /// String displayed if some information was unable to be determined, such as the creation time.
 const MISSING_INFO: &str = "—";
 
@@ -46,6 +42,10 @@ enum FilePreview {
     /// PDF document.
     Pdf(Pdf),
 
+    /// The preview of the file is loading in the background, and the spinner is displayed until
+    /// it's ready.
+    Loading(gio::File),
+
     /// Non-text, non-image file to be previewed as an icon in [`FilePreviewWidgets::image`].
     Icon(gdk::Paintable),
 
@@ -66,6 +66,10 @@ pub struct FilePreviewModel {
     info: Vec<FileInfo>,
     preview: Option<FilePreview>,
     abort_preview: Option<AbortHandle>,
+
+    /// Cancels work on other threads that loads the preview, which aborting it doesn't stop.
+    cancel_preview: Option<gio::Cancellable>,
+    abort_pdf_search: Option<AbortHandle>,
     file_name_text: String,
     file_type_text: String,
     created_text: String,
@@ -73,6 +77,25 @@ pub struct FilePreviewModel {
 }
 
 impl FilePreviewModel {
+    /// Stop loading the preview, including any work that was started on other threads.
+    fn stop_loading_preview(&mut self) {
+        if let Some(handle) = self.abort_preview.take() {
+            handle.abort();
+        }
+
+        if let Some(cancellable) = self.cancel_preview.take() {
+            cancellable.cancel();
+        }
+    }
+
+    /// Returns the metadata of the previewed PDF document, if there is one.
+    fn pdf_metadata(&self) -> Option<&PdfMetadata> {
+        match &self.preview {
+            Some(FilePreview::Pdf(pdf)) => Some(pdf.metadata()),
+            _ => None,
+        }
+    }
+
     fn update_single_file_preview(
         &mut self,
         widgets: &mut FilePreviewWidgets,
@@ -117,15 +140,33 @@ impl FilePreviewModel {
                 FilePreview::Image(file.file.clone())
             }
             (_, mime::PDF) => {
-                // TODO: This should be async.
-                match poppler::Document::from_gfile(&file.file, None, gio::Cancellable::NONE) {
-                    Ok(document) => FilePreview::Pdf(Pdf::new(document)),
-                    Err(e) => {
-                        error!("error loading PDF: {}", e);
+                let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                let cancellable = gio::Cancellable::new();
 
-                        FilePreview::Error(Box::new(e))
+                self.stop_loading_preview();
+                self.abort_preview = Some(abort_handle);
+                self.cancel_preview = Some(cancellable.clone());
+
+                let load_fut = Abortable::new(
+                    pdf::load(file.file.clone(), cancellable),
+                    abort_registration,
+                );
+
+                widgets.spinner.start();
+                widgets.stack.set_visible_child(&widgets.spinner);
+
+                let gfile = file.file.clone();
+                relm4::spawn_local(async move {
+                    match load_fut.await {
+                        Err(Aborted) => (),
+                        Ok(document) => sender.input(FilePreviewMsg::PdfLoaded {
+                            file: gfile,
+                            document,
+                        }),
                     }
-                }
+                });
+
+                FilePreview::Loading(file.file.clone())
             }
             _ => match &file.contents {
                 Some(contents) if !contents.contains(&b'\0') => {
@@ -193,165 +234,324 @@ impl Component for FilePreviewModel {
     type CommandOutput = FilePreviewCommand;
 
     view! {
-        adw::Clamp {
-            gtk::Box {
-                add_css_class: "file-preview-widget",
-                set_orientation: gtk::Orientation::Vertical,
-                set_vexpand: true,
-                #[watch]
-                set_visible: !model.info.is_empty(),
-
-                #[name = "stack"]
-                gtk::Stack {
-                    add_css_class: "file-preview",
-                    set_vhomogeneous: false,
-
-                    #[name = "spinner"]
-                    gtk::Spinner {
-                        set_halign: gtk::Align::Center,
-                        set_valign: gtk::Align::Center,
-                    },
+        util::NaturalWidthBin {
+            #[watch]
+            set_visible: !model.info.is_empty(),
+
+            #[wrap(Some)]
+            set_child = &adw::Clamp {
+                gtk::Box {
+                    add_css_class: "file-preview-widget",
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_vexpand: true,
+
+                    #[name = "stack"]
+                    gtk::Stack {
+                        add_css_class: "file-preview",
+                        set_vhomogeneous: false,
+
+                        #[name = "spinner"]
+                        gtk::Spinner {
+                            set_halign: gtk::Align::Center,
+                            set_valign: gtk::Align::Center,
+                        },
 
-                    #[name = "icon"]
-                    adw::Clamp {
-                        set_maximum_size: 256,
+                        #[name = "icon"]
+                        adw::Clamp {
+                            set_maximum_size: 256,
 
-                        #[name = "icon_picture"]
+                            #[name = "icon_picture"]
+                            gtk::Picture {
+                                set_hexpand: true,
+                            },
+                        },
+
+                        #[name = "picture"]
                         gtk::Picture {
+                            add_css_class: "bordered",
+                            set_halign: gtk::Align::Center,
                             set_hexpand: true,
+                            set_valign: gtk::Align::Center,
+                            set_vexpand: true,
                         },
-                    },
 
-                    #[name = "picture"]
-                    gtk::Picture {
-                        add_css_class: "bordered",
-                        set_halign: gtk::Align::Center,
-                        set_hexpand: true,
-                        set_valign: gtk::Align::Center,
-                        set_vexpand: true,
-                    },
-
-                    #[name = "text_container"]
-                    gtk::ScrolledWindow {
-                        add_css_class: "bordered",
-                        set_hexpand: true,
-                        set_propagate_natural_height: true,
-                        set_overflow: gtk::Overflow::Hidden,
-                        set_valign: gtk::Align::Center,
-
-                        #[name = "text"]
-                        sourceview::View {
-                            add_css_class: "file-preview-source",
-                            set_cursor_visible: false,
-                            set_editable: false,
-                            set_monospace: true,
+                        #[name = "text_container"]
+                        gtk::ScrolledWindow {
+                            add_css_class: "bordered",
+                            set_hexpand: true,
+                            set_propagate_natural_height: true,
+                            set_overflow: gtk::Overflow::Hidden,
                             set_valign: gtk::Align::Center,
-                        }
-                    },
 
-                    #[name = "pdf_container"]
-                    gtk::AspectFrame {
+                            #[name = "text"]
+                            sourceview::View {
+                                add_css_class: "file-preview-source",
+                                set_cursor_visible: false,
+                                set_editable: false,
+                                set_monospace: true,
+                                set_valign: gtk::Align::Center,
+                            }
+                        },
+
+                        #[name = "pdf_view"]
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 6,
+
+                            #[name = "pdf_container"]
+                            gtk::AspectFrame {
+
+                                set_obey_child: false,
+
+                                #[wrap(Some)]
+                                set_child = &gtk::Overlay {
+
+                                    #[name = "pdf"]
+                                    gtk::DrawingArea {
+                                        add_css_class: "bordered",
+                                        set_focusable: true,
+                                        set_hexpand: true,
+                                        set_vexpand: true,
+                                    },
+
+                                    #[name = "pdf_previous_button"]
+                                    add_overlay = &gtk::Button {
+                                        set_icon_name: "go-previous-symbolic",
+                                        add_css_class: "osd",
+                                        set_margin_start: 5,
+                                        set_halign: gtk::Align::Start,
+                                        set_valign: gtk::Align::Center,
+                                        connect_clicked =>
+                                            FilePreviewMsg::ChangePdfPage(PdfPageChange::Previous),
+                                    },
+
+                                    #[name = "pdf_next_button"]
+                                    add_overlay = &gtk::Button {
+                                        set_icon_name: "go-next-symbolic",
+                                        add_css_class: "osd",
+                                        set_margin_end: 5,
+                                        set_halign: gtk::Align::End,
+                                        set_valign: gtk::Align::Center,
+                                        connect_clicked =>
+                                            FilePreviewMsg::ChangePdfPage(PdfPageChange::Next),
+                                    },
+                                }
+                            },
 
-                        set_obey_child: false,
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Horizontal,
+                                set_spacing: 6,
+                                set_halign: gtk::Align::Center,
+
+                                gtk::Label {
+                                    set_label: "Page",
+                                },
+
+                                #[name = "pdf_page_entry"]
+                                gtk::Entry {
+                                    set_width_chars: 4,
+                                    set_xalign: 1.0,
+                                    set_input_purpose: gtk::InputPurpose::Digits,
+                                    connect_activate[sender] => move |this| {
+                                        sender.input(FilePreviewMsg::GoToPdfPage(this.text().into()));
+                                    },
+                                },
+
+                                #[name = "pdf_page_count"]
+                                gtk::Label {},
+                            },
 
-                        #[wrap(Some)]
-                        set_child = &gtk::Overlay {
+                            gtk::ScrolledWindow {
+                                set_vscrollbar_policy: gtk::PolicyType::Never,
+                                set_propagate_natural_height: true,
 
-                            #[name = "pdf"]
-                            gtk::DrawingArea {
-                                add_css_class: "bordered",
-                                set_hexpand: true,
-                                set_vexpand: true,
+                                #[name = "pdf_thumbnails"]
+                                gtk::ListView {
+                                    set_orientation: gtk::Orientation::Horizontal,
+                                },
                             },
 
-                            #[name = "pdf_previous_button"]
-                            add_overlay = &gtk::Button {
-                                set_icon_name: "go-previous-symbolic",
-                                add_css_class: "osd",
-                                set_margin_start: 5,
-                                set_halign: gtk::Align::Start,
-                                set_valign: gtk::Align::Center,
-                                connect_clicked =>
-                                    FilePreviewMsg::ChangePdfPage(PdfPageChange::Previous),
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Horizontal,
+                                add_css_class: "linked",
+
+                                #[name = "pdf_search_entry"]
+                                gtk::SearchEntry {
+                                    set_hexpand: true,
+                                    set_placeholder_text: Some("Find in document"),
+                                    connect_search_changed[sender] => move |this| {
+                                        sender.input(FilePreviewMsg::SearchPdf(this.text().into()));
+                                    },
+                                    connect_activate =>
+                                        FilePreviewMsg::ChangePdfMatch(PdfMatchChange::Next),
+                                    connect_next_match =>
+                                        FilePreviewMsg::ChangePdfMatch(PdfMatchChange::Next),
+                                    connect_previous_match =>
+                                        FilePreviewMsg::ChangePdfMatch(PdfMatchChange::Previous),
+                                },
+
+                                #[name = "pdf_previous_match_button"]
+                                gtk::Button {
+                                    set_icon_name: "go-up-symbolic",
+                                    set_tooltip_text: Some("Previous match"),
+                                    connect_clicked =>
+                                        FilePreviewMsg::ChangePdfMatch(PdfMatchChange::Previous),
+                                },
+
+                                #[name = "pdf_next_match_button"]
+                                gtk::Button {
+                                    set_icon_name: "go-down-symbolic",
+                                    set_tooltip_text: Some("Next match"),
+                                    connect_clicked =>
+                                        FilePreviewMsg::ChangePdfMatch(PdfMatchChange::Next),
+                                },
                             },
 
-                            #[name = "pdf_next_button"]
-                            add_overlay = &gtk::Button {
-                                set_icon_name: "go-next-symbolic",
-                                add_css_class: "osd",
-                                set_margin_end: 5,
-                                set_halign: gtk::Align::End,
-                                set_valign: gtk::Align::Center,
-                                connect_clicked =>
-                                    FilePreviewMsg::ChangePdfPage(PdfPageChange::Next),
+                            #[name = "pdf_matches"]
+                            gtk::Label {
+                                add_css_class: "dim-label",
+                                set_halign: gtk::Align::Start,
                             },
-                        }
-                    },
+                        },
 
-                    #[name = "error"]
-                    adw::StatusPage {
-                        set_icon_name: Some("dialog-warning-symbolic"),
-                        set_title: "Cannot Display Preview",
-                        set_description: Some(""),
+                        #[name = "error"]
+                        adw::StatusPage {
+                            set_icon_name: Some("dialog-warning-symbolic"),
+                            set_title: "Cannot Display Preview",
+                            set_description: Some(""),
+                        },
                     },
-                },
 
-                gtk::Grid {
-                    add_css_class: "file-preview-info",
-                    attach[0, 0, 2, 1]: file_name = &gtk::Label {
-                        #[watch]
-                        set_text: &model.file_name_text,
+                    gtk::Grid {
+                        add_css_class: "file-preview-info",
+                        attach[0, 0, 2, 1]: file_name = &gtk::Label {
+                            #[watch]
+                            set_text: &model.file_name_text,
 
-                        add_css_class: "file-name",
-                        set_hexpand: true,
-                        set_halign: gtk::Align::Start,
-                    },
-                    attach[0, 1, 2, 1]: file_type = &gtk::Label {
-                        #[watch]
-                        set_text: &model.file_type_text,
+                            add_css_class: "file-name",
+                            set_hexpand: true,
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[0, 1, 2, 1]: file_type = &gtk::Label {
+                            #[watch]
+                            set_text: &model.file_type_text,
 
-                        #[iterate]
-                        add_css_class: ["file-type", "dim-label"],
-                        set_halign: gtk::Align::Start,
-                    },
-                    attach[0, 2, 2, 1] = &gtk::Label {
-                        set_label: "Information",
-                        add_css_class: "section-title",
-                        set_halign: gtk::Align::Start,
-                    },
-                    attach[0, 3, 1, 1] = &gtk::Label {
-                        set_label: "Created",
-                        #[iterate]
-                        add_css_class: ["info-name", "dim-label"],
-                        set_halign: gtk::Align::Start,
-                    },
-                    attach[1, 3, 1, 1]: created = &gtk::Label {
-                        #[watch]
-                        set_text: &model.created_text,
-                        add_css_class: "info-value",
-                        set_halign: gtk::Align::End,
-                    },
-                    attach[0, 4, 1, 1] = &gtk::Label {
-                        set_label: "Modified",
-                        #[iterate]
-                        add_css_class: ["info-name", "dim-label"],
-                        set_halign: gtk::Align::Start,
+                            #[iterate]
+                            add_css_class: ["file-type", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[0, 2, 2, 1] = &gtk::Label {
+                            set_label: "Information",
+                            add_css_class: "section-title",
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[0, 3, 1, 1] = &gtk::Label {
+                            set_label: "Created",
+                            #[iterate]
+                            add_css_class: ["info-name", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[1, 3, 1, 1]: created = &gtk::Label {
+                            #[watch]
+                            set_text: &model.created_text,
+                            add_css_class: "info-value",
+                            set_halign: gtk::Align::End,
+                        },
+                        attach[0, 4, 1, 1] = &gtk::Label {
+                            set_label: "Modified",
+                            #[iterate]
+                            add_css_class: ["info-name", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[1, 4, 1, 1]: modified = &gtk::Label {
+                            #[watch]
+                            set_text: &model.modified_text,
+                            add_css_class: "info-value",
+                            set_halign: gtk::Align::End,
+                        },
                     },
-                    attach[1, 4, 1, 1]: modified = &gtk::Label {
+
+                    gtk::Grid {
+                        add_css_class: "file-preview-info",
                         #[watch]
-                        set_text: &model.modified_text,
-                        add_css_class: "info-value",
-                        set_halign: gtk::Align::End,
+                        set_visible: model.pdf_metadata().is_some(),
+
+                        attach[0, 0, 2, 1] = &gtk::Label {
+                            set_label: "Document",
+                            add_css_class: "section-title",
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[0, 1, 1, 1] = &gtk::Label {
+                            set_label: "Title",
+                            #[iterate]
+                            add_css_class: ["info-name", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[1, 1, 1, 1] = &gtk::Label {
+                            #[watch]
+                            set_text: model.pdf_metadata().and_then(|metadata| metadata.title.as_deref())
+                                .unwrap_or(MISSING_INFO),
+                            add_css_class: "info-value",
+                            set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            set_halign: gtk::Align::End,
+                            set_hexpand: true,
+                        },
+                        attach[0, 2, 1, 1] = &gtk::Label {
+                            set_label: "Author",
+                            #[iterate]
+                            add_css_class: ["info-name", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[1, 2, 1, 1] = &gtk::Label {
+                            #[watch]
+                            set_text: model.pdf_metadata().and_then(|metadata| metadata.author.as_deref())
+                                .unwrap_or(MISSING_INFO),
+                            add_css_class: "info-value",
+                            set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            set_halign: gtk::Align::End,
+                            set_hexpand: true,
+                        },
+                        attach[0, 3, 1, 1] = &gtk::Label {
+                            set_label: "Pages",
+                            #[iterate]
+                            add_css_class: ["info-name", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[1, 3, 1, 1] = &gtk::Label {
+                            #[watch]
+                            set_text: &model.pdf_metadata().map_or(String::new(), |metadata| metadata.pages.to_string()),
+                            add_css_class: "info-value",
+                            set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            set_halign: gtk::Align::End,
+                            set_hexpand: true,
+                        },
+                        attach[0, 4, 1, 1] = &gtk::Label {
+                            set_label: "Producer",
+                            #[iterate]
+                            add_css_class: ["info-name", "dim-label"],
+                            set_halign: gtk::Align::Start,
+                        },
+                        attach[1, 4, 1, 1] = &gtk::Label {
+                            #[watch]
+                            set_text: model.pdf_metadata().and_then(|metadata| metadata.producer.as_deref())
+                                .unwrap_or(MISSING_INFO),
+                            add_css_class: "info-value",
+                            set_ellipsize: gtk::pango::EllipsizeMode::End,
+                            set_halign: gtk::Align::End,
+                            set_hexpand: true,
+                        },
                     },
                 }
-            }
+            },
         }
     }
 
-    fn init(_: (), root: Self::Root, _sender: ComponentSender<Self>) -> ComponentParts<Self> {
+    fn init(_: (), root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
         let model = FilePreviewModel {
             info: vec![],
             abort_preview: None,
+            cancel_preview: None,
+            abort_pdf_search: None,
             created_text: String::new(),
             file_name_text: String::new(),
             file_type_text: String::new(),
@@ -361,15 +561,32 @@ impl Component for FilePreviewModel {
 
         let widgets = view_output!();
 
-        let buffer = widgets
-            .text
-            .buffer()
-            .downcast::<sourceview::Buffer>()
-            .expect("sourceview was not backed by sourceview buffer");
+        set_style_scheme(&widgets.text, &windows::config().style_scheme);
 
-        if let Some(scheme) = &sourceview::StyleSchemeManager::new().scheme("oblivion") {
-            buffer.set_style_scheme(Some(scheme));
-        }
+        // The page takes focus when it's clicked, so that it can be paged through with the keyboard.
+        let click_controller = gtk::GestureClick::new();
+        click_controller.connect_pressed(clone!(
+            #[weak(rename_to = pdf)]
+            widgets.pdf,
+            move |_, _, _, _| {
+                pdf.grab_focus();
+            }
+        ));
+        widgets.pdf.add_controller(click_controller);
+
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.connect_key_pressed(move |_, key, _, _| {
+            let change = match key {
+                gdk::Key::Page_Up | gdk::Key::Left | gdk::Key::BackSpace => PdfPageChange::Previous,
+                gdk::Key::Page_Down | gdk::Key::Right | gdk::Key::space => PdfPageChange::Next,
+                gdk::Key::Home => PdfPageChange::To(0),
+                _ => return glib::Propagation::Proceed,
+            };
+
+            sender.input(FilePreviewMsg::ChangePdfPage(change));
+            glib::Propagation::Stop
+        });
+        widgets.pdf.add_controller(key_controller);
 
         ComponentParts { model, widgets }
     }
@@ -379,20 +596,46 @@ impl Component for FilePreviewModel {
         widgets: &mut Self::Widgets,
         msg: FilePreviewMsg,
         sender: ComponentSender<Self>,
-        _: &Self::Root,
+        root: &Self::Root,
     ) {
         info!("received message: {:?}", msg);
 
         match msg {
             FilePreviewMsg::Hide => {
+                self.stop_loading_preview();
                 self.info = vec![];
                 self.update_view(widgets, sender);
                 return;
             }
+            FilePreviewMsg::RestoreWidth(width) => {
+                root.set_natural_width(width);
+                return;
+            }
+            FilePreviewMsg::ConfigChanged => {
+                let config = windows::config();
+
+                set_style_scheme(&widgets.text, &config.style_scheme);
+
+                if !config.preview {
+                    self.info = vec![];
+                }
+            }
+            FilePreviewMsg::NewSelection(_) if !windows::config().preview => {
+                self.stop_loading_preview();
+
+                if let Some(handle) = self.abort_pdf_search.take() {
+                    handle.abort();
+                }
+
+                self.info = vec![];
+            }
             FilePreviewMsg::NewSelection(selection) => {
                 let (abort_handle, abort_registration) = AbortHandle::new_pair();
 
-                if let Some(handle) = self.abort_preview.replace(abort_handle) {
+                self.stop_loading_preview();
+                self.abort_preview = Some(abort_handle);
+
+                if let Some(handle) = self.abort_pdf_search.take() {
                     handle.abort();
                 }
 
@@ -412,6 +655,7 @@ impl Component for FilePreviewModel {
             }
             FilePreviewMsg::FileInfoLoaded(Err(e)) => {
                 self.abort_preview.take();
+                self.cancel_preview.take();
 
                 error!("error while loading preview: {}", e);
 
@@ -419,6 +663,7 @@ impl Component for FilePreviewModel {
             }
             FilePreviewMsg::FileInfoLoaded(Ok(info)) => {
                 self.abort_preview.take();
+                self.cancel_preview.take();
 
                 info!("new file info: {:?}", info);
 
@@ -430,16 +675,84 @@ impl Component for FilePreviewModel {
                     _ => self.update_multiple_file_preview(),
                 }
             }
-            FilePreviewMsg::ChangePdfPage(change) => {
+            FilePreviewMsg::PdfLoaded { file, document } => {
+                // The selection may have changed while the document was loading.
+                if !matches!(&self.preview, Some(FilePreview::Loading(f)) if *f == file) {
+                    info!("ignoring document of a previous selection: {}", file.uri());
+                    return;
+                }
+
+                self.abort_preview.take();
+                self.cancel_preview.take();
+
+                self.preview = Some(match document {
+                    Ok(document) => {
+                        set_pdf_thumbnails(&widgets.pdf_thumbnails, &document, &sender);
+                        widgets.pdf_search_entry.set_text("");
+
+                        FilePreview::Pdf(Pdf::new(document))
+                    }
+                    Err(e) => {
+                        error!("error loading PDF: {}", e);
+
+                        FilePreview::Error(Box::new(e))
+                    }
+                });
+            }
+            FilePreviewMsg::GoToPdfPage(number) => {
                 if let Some(FilePreview::Pdf(pdf)) = &mut self.preview {
-                    pdf.update_page(change);
+                    match number.trim().parse::<i32>() {
+                        Ok(number) => pdf.update_page(PdfPageChange::To(number - 1)),
+                        Err(e) => warn!("invalid page number {:?}: {}", number, e),
+                    }
+                }
+            }
+            FilePreviewMsg::SearchPdf(query) => {
+                if let Some(handle) = self.abort_pdf_search.take() {
+                    handle.abort();
+                }
 
-                    if let Some(page) = pdf.current_page() {
-                        let (w, h) = page.size();
-                        widgets.pdf_container.set_ratio((w / h) as f32);
+                if let Some(FilePreview::Pdf(pdf)) = &mut self.preview {
+                    if query.is_empty() {
+                        pdf.set_matches(vec![]);
+                    } else {
+                        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                        self.abort_pdf_search = Some(abort_handle);
+
+                        let search_fut = Abortable::new(
+                            pdf::search(pdf.document().clone(), query),
+                            abort_registration,
+                        );
+
+                        let sender = sender.clone();
+                        relm4::spawn_local(async move {
+                            match search_fut.await {
+                                Err(Aborted) => (),
+                                Ok(matches) => {
+                                    sender.input(FilePreviewMsg::PdfSearchFinished(matches))
+                                }
+                            }
+                        });
                     }
                 }
             }
+            FilePreviewMsg::PdfSearchFinished(matches) => {
+                self.abort_pdf_search.take();
+
+                if let Some(FilePreview::Pdf(pdf)) = &mut self.preview {
+                    pdf.set_matches(matches);
+                }
+            }
+            FilePreviewMsg::ChangePdfMatch(change) => {
+                if let Some(FilePreview::Pdf(pdf)) = &mut self.preview {
+                    pdf.step_match(change);
+                }
+            }
+            FilePreviewMsg::ChangePdfPage(change) => {
+                if let Some(FilePreview::Pdf(pdf)) = &mut self.preview {
+                    pdf.update_page(change);
+                }
+            }
         };
 
         self.update_view(widgets, sender);
@@ -464,7 +777,7 @@ impl Component for FilePreviewModel {
         info!("preview: {:?}", self.preview);
 
         match &self.preview {
-            Some(FilePreview::Image(_)) => (),
+            Some(FilePreview::Image(_) | FilePreview::Loading(_)) => (),
             Some(FilePreview::Icon(paintable)) => {
                 widgets.icon_picture.set_paintable(Some(paintable));
                 widgets.stack.set_visible_child(&widgets.icon);
@@ -492,21 +805,49 @@ impl Component for FilePreviewModel {
                     let (w, h) = page.size();
                     widgets.pdf_container.set_ratio((w / h) as f32);
 
-                    widgets.pdf.set_draw_func(move |_, ctx, w, h| {
-                        ctx.set_source_rgb(1.0, 1.0, 1.0);
-                        ctx.paint().unwrap();
-
-                        let (page_w, page_h) = page.size();
-
-                        ctx.identity_matrix();
-
-                        ctx.scale(f64::from(w) / page_w, f64::from(h) / page_h);
-
-                        page.render(ctx);
+                    let page = Rc::clone(page);
+                    let highlights = pdf.highlights();
+                    widgets.pdf.set_draw_func(move |area, ctx, w, h| {
+                        page.draw(ctx, w, h, area.scale_factor(), &highlights);
                     });
                 }
 
-                widgets.stack.set_visible_child(&widgets.pdf_container);
+                widgets
+                    .pdf_page_entry
+                    .set_text(&(pdf.page_index() + 1).to_string());
+                widgets
+                    .pdf_page_count
+                    .set_text(&format!("of {}", pdf.n_pages()));
+
+                // Keep the thumbnail of the displayed page selected and in view.
+                if let Some(selection) = widgets
+                    .pdf_thumbnails
+                    .model()
+                    .and_downcast::<gtk::SingleSelection>()
+                {
+                    let position = pdf.page_index() as u32;
+
+                    if selection.selected() != position {
+                        selection.set_selected(position);
+
+                        let _ = widgets
+                            .pdf_thumbnails
+                            .activate_action("list.scroll-to-item", Some(&position.to_variant()));
+                    }
+                }
+
+                let (count, current) = pdf.match_count();
+                widgets.pdf_previous_match_button.set_sensitive(count > 0);
+                widgets.pdf_next_match_button.set_sensitive(count > 0);
+                widgets
+                    .pdf_matches
+                    .set_visible(!widgets.pdf_search_entry.text().is_empty());
+                widgets.pdf_matches.set_text(&match current {
+                    Some(current) => format!("Match {} of {}", current + 1, count),
+                    None => String::from("No matches"),
+                });
+
+                widgets.stack.set_visible_child(&widgets.pdf_view);
             }
             Some(FilePreview::Error(e)) => {
                 widgets.error.set_description(Some(&e.to_string()));
@@ -525,11 +866,74 @@ pub enum FilePreviewMsg {
     /// Queried file information is now available.
     FileInfoLoaded(Result<Vec<FileInfo>, glib::Error>),
 
+    /// A PDF document has finished loading.
+    PdfLoaded {
+        /// The file that the document was loaded from.
+        file: gio::File,
+
+        document: Result<poppler::Document, glib::Error>,
+    },
+
     /// Change PDF page.
     ChangePdfPage(PdfPageChange),
 
+    /// Go to the PDF page with the number entered by the user.
+    GoToPdfPage(String),
+
+    /// Search the PDF document for text. An empty query clears the search.
+    SearchPdf(String),
+
+    /// A search of the PDF document has finished.
+    PdfSearchFinished(Vec<pdf::Match>),
+
+    /// Select the next or previous match of the PDF search.
+    ChangePdfMatch(PdfMatchChange),
+
     /// Empty the contents of the preview.
     Hide,
+
+    /// Request a width for the preview while it's shown, restored from a previous session.
+    RestoreWidth(i32),
+
+    /// The configuration changed.
+    ConfigChanged,
+}
+
+/// Highlight previewed text with the named style scheme, if it's installed.
+fn set_style_scheme(text: &sourceview::View, name: &str) {
+    let buffer = text
+        .buffer()
+        .downcast::<sourceview::Buffer>()
+        .expect("sourceview was not backed by sourceview buffer");
+
+    match sourceview::StyleSchemeManager::new().scheme(name) {
+        Some(scheme) => buffer.set_style_scheme(Some(&scheme)),
+        None => warn!("unknown style scheme: {}", name),
+    }
+}
+
+/// Show a thumbnail of every page of a PDF document, and go to a page when its thumbnail is
+/// selected.
+fn set_pdf_thumbnails(
+    list_view: &gtk::ListView,
+    document: &poppler::Document,
+    sender: &ComponentSender<FilePreviewModel>,
+) {
+    let pages = gtk::StringList::new(&[]);
+    for number in 1..=document.n_pages() {
+        pages.append(&number.to_string());
+    }
+
+    let selection = gtk::SingleSelection::new(Some(pages));
+    let sender = sender.clone();
+    selection.connect_selected_notify(move |selection| {
+        sender.input(FilePreviewMsg::ChangePdfPage(PdfPageChange::To(
+            selection.selected() as i32,
+        )));
+    });
+
+    list_view.set_factory(Some(&pdf::thumbnail_factory(document.clone())));
+    list_view.set_model(Some(&selection));
 }
 
 /// Query the relevant file info for the selection. The info will be returned in the same order as
@@ -565,6 +969,7 @@ async fn query_selection_info(selection: FileSelection) -> Result<Vec<FileInfo>,
     .join(",");
 
     let is_single_file = selection.files.len() == 1;
+    let max_preview_bytes = windows::config().max_preview_bytes;
     let selection_info = future::join_all(selection.files.into_iter().map(|file| async {
         let info = file
             .query_info_future(
@@ -590,7 +995,11 @@ async fn query_selection_info(selection: FileSelection) -> Result<Vec<FileInfo>,
 
                 // Binary data will not be previewed.
                 let contents = if is_single_file && is_plain_text(&mime) {
-                    Some(read_start_of_file(&file).await.unwrap_or_default())
+                    Some(
+                        read_start_of_file(&file, max_preview_bytes)
+                            .await
+                            .unwrap_or_default(),
+                    )
                 } else {
                     None
                 };
@@ -612,8 +1021,8 @@ async fn query_selection_info(selection: FileSelection) -> Result<Vec<FileInfo>,
     selection_info.await.into_iter().collect()
 }
 
-/// Return at most a single I/O buffer's worth of a file's contents from the beginning.
-async fn read_start_of_file(file: &gio::File) -> Result<Vec<u8>, io::Error> {
+/// Return at most `max_bytes` of a file's contents from the beginning.
+async fn read_start_of_file(file: &gio::File, max_bytes: u64) -> Result<Vec<u8>, io::Error> {
     let mut contents = Vec::with_capacity(PREVIEW_BUFFER_SIZE);
 
     let reader = file
@@ -622,10 +1031,7 @@ async fn read_start_of_file(file: &gio::File) -> Result<Vec<u8>, io::Error> {
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
         .into_async_buf_read(PREVIEW_BUFFER_SIZE);
 
-    let n = reader
-        .take(PREVIEW_BUFFER_SIZE as u64)
-        .read_to_end(&mut contents)
-        .await?;
+    let n = reader.take(max_bytes).read_to_end(&mut contents).await?;
     contents.truncate(n);
 
     Ok(contents)
@@ -673,9 +1079,16 @@ fn format_item_types(files: &[FileInfo]) -> String {
     }
 }
 
-/// Formats a [`GDateTime`](glib::DateTime) as a human-readable date string.
-fn format_datetime(dt: &glib::DateTime) -> String {
-    dt.format(LONG_DATE_FORMAT).unwrap().into()
+/// Formats a [`GDateTime`](glib::DateTime) as a human-readable date string, using the long date
+/// format from the configuration.
+pub(super) fn format_datetime(dt: &glib::DateTime) -> String {
+    format_date(dt, &windows::config().date_format.long)
+}
+
+/// Formats a date, or returns [`MISSING_INFO`] if the format is invalid.
+fn format_date(dt: &glib::DateTime, format: &str) -> String {
+    dt.format(format)
+        .map_or_else(|_| String::from(MISSING_INFO), String::from)
 }
 
 /// Formats an iterator of [`GDateTime](glib::DateTime) objects as a range between the earliest and
@@ -687,13 +1100,15 @@ fn format_datetime_range(dts: impl Iterator<Item = glib::DateTime>) -> String {
         MinMaxResult::MinMax(min, max) => (min, max),
     };
 
+    let format = &windows::config().date_format.short;
+
     if min.ymd() == max.ymd() {
-        min.format(SHORT_DATE_FORMAT).unwrap().to_string()
+        format_date(&min, format)
     } else {
         format!(
             "{} — {}",
-            min.format(SHORT_DATE_FORMAT).unwrap(),
-            max.format(SHORT_DATE_FORMAT).unwrap()
+            format_date(&min, format),
+            format_date(&max, format)
         )
     }
 }
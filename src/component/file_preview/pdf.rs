@@ -1,34 +1,141 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use relm4::gtk::{self, cairo, gio, glib, prelude::*};
+use relm4::view;
+use tracing::*;
+
+/// The width of the page thumbnails, in pixels. Their height follows the aspect ratio of the page.
+const THUMBNAIL_WIDTH: i32 = 64;
+
 #[derive(Debug)]
 pub struct Pdf {
     document: poppler::Document,
+    metadata: PdfMetadata,
     page_index: i32,
+
+    /// The displayed page, which is shared with the draw function of the preview.
+    page: Option<Rc<RenderedPage>>,
+
+    /// Matches of the search query, in document order.
+    matches: Vec<Match>,
+
+    /// The index of the selected match in `matches`.
+    current_match: Option<usize>,
 }
 
 impl Pdf {
     pub fn new(document: poppler::Document) -> Self {
+        let metadata = PdfMetadata::new(&document);
+        let page = document
+            .page(0)
+            .map(|page| Rc::new(RenderedPage::new(page)));
+
         Pdf {
             document,
+            metadata,
             page_index: 0,
+            page,
+            matches: vec![],
+            current_match: None,
         }
     }
 
+    pub fn document(&self) -> &poppler::Document {
+        &self.document
+    }
+
+    pub fn metadata(&self) -> &PdfMetadata {
+        &self.metadata
+    }
+
+    /// Returns the index of the displayed page.
+    pub fn page_index(&self) -> i32 {
+        self.page_index
+    }
+
+    pub fn n_pages(&self) -> i32 {
+        self.document.n_pages()
+    }
+
     pub fn has_previous_page(&self) -> bool {
         self.page_index > 0
     }
 
     pub fn has_next_page(&self) -> bool {
-        self.page_index < self.document.n_pages() - 1
+        self.page_index < self.n_pages() - 1
     }
 
-    pub fn current_page(&self) -> Option<poppler::Page> {
-        self.document.page(self.page_index)
+    pub fn current_page(&self) -> Option<&Rc<RenderedPage>> {
+        self.page.as_ref()
     }
 
     pub fn update_page(&mut self, change: PdfPageChange) {
-        match change {
-            PdfPageChange::Previous if self.has_previous_page() => self.page_index -= 1,
-            PdfPageChange::Next if self.has_next_page() => self.page_index += 1,
-            _ => (),
+        let page_index = match change {
+            PdfPageChange::Previous if self.has_previous_page() => self.page_index - 1,
+            PdfPageChange::Next if self.has_next_page() => self.page_index + 1,
+            PdfPageChange::To(index) if index != self.page_index && index >= 0 => index,
+            _ => return,
+        };
+
+        if let Some(page) = self.document.page(page_index) {
+            self.page_index = page_index;
+            self.page = Some(Rc::new(RenderedPage::new(page)));
+        }
+    }
+
+    /// Replace the matches of the search query, and show the first match on or after the
+    /// displayed page.
+    pub fn set_matches(&mut self, matches: Vec<Match>) {
+        self.current_match = if matches.is_empty() {
+            None
+        } else {
+            Some(
+                matches
+                    .iter()
+                    .position(|m| m.page_index >= self.page_index)
+                    .unwrap_or(0),
+            )
+        };
+        self.matches = matches;
+
+        self.show_current_match();
+    }
+
+    /// Select the next or previous match, wrapping around the ends of the document.
+    pub fn step_match(&mut self, change: PdfMatchChange) {
+        let len = self.matches.len();
+
+        self.current_match = self.current_match.map(|current| match change {
+            PdfMatchChange::Previous => (current + len - 1) % len,
+            PdfMatchChange::Next => (current + 1) % len,
+        });
+
+        self.show_current_match();
+    }
+
+    /// Returns the number of matches, and the position of the selected match.
+    pub fn match_count(&self) -> (usize, Option<usize>) {
+        (self.matches.len(), self.current_match)
+    }
+
+    /// Returns the matches on the displayed page.
+    pub fn highlights(&self) -> Vec<Highlight> {
+        self.matches
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.page_index == self.page_index)
+            .map(|(i, m)| Highlight {
+                rect: m.rect.clone(),
+                current: Some(i) == self.current_match,
+            })
+            .collect()
+    }
+
+    fn show_current_match(&mut self) {
+        if let Some(current) = self.current_match {
+            self.update_page(PdfPageChange::To(self.matches[current].page_index));
         }
     }
 }
@@ -37,4 +144,282 @@ impl Pdf {
 pub enum PdfPageChange {
     Previous,
     Next,
+
+    /// Go to the page at the given index.
+    To(i32),
+}
+
+#[derive(Debug)]
+pub enum PdfMatchChange {
+    Previous,
+    Next,
+}
+
+/// Information about a document, as displayed in the info grid.
+#[derive(Debug)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub producer: Option<String>,
+    pub pages: i32,
+}
+
+impl PdfMetadata {
+    fn new(document: &poppler::Document) -> Self {
+        PdfMetadata {
+            title: document.title().map(String::from),
+            author: document.author().map(String::from),
+            producer: document.producer().map(String::from),
+            pages: document.n_pages(),
+        }
+    }
+}
+
+/// An occurrence of the search query in the document.
+#[derive(Debug, Clone)]
+pub struct Match {
+    page_index: i32,
+
+    /// The bounds of the match, in PDF coordinates.
+    rect: poppler::Rectangle,
+}
+
+/// A match to be highlighted on the displayed page.
+#[derive(Debug)]
+pub struct Highlight {
+    rect: poppler::Rectangle,
+
+    /// Whether this is the selected match.
+    current: bool,
+}
+
+/// A page that is rendered to an image surface the first time it's drawn. The page is only
+/// rendered again when it's drawn at a different size.
+#[derive(Debug)]
+pub struct RenderedPage {
+    page: poppler::Page,
+    surface: RefCell<Option<cairo::ImageSurface>>,
+}
+
+impl RenderedPage {
+    fn new(page: poppler::Page) -> Self {
+        RenderedPage {
+            page,
+            surface: RefCell::new(None),
+        }
+    }
+
+    /// Returns the width and height of the page, in points.
+    pub fn size(&self) -> (f64, f64) {
+        self.page.size()
+    }
+
+    /// Paint the page to fill an area of the given size, along with the highlighted matches. The
+    /// scale factor is that of the widget, so that the page is rendered at the resolution of the
+    /// display.
+    pub fn draw(
+        &self,
+        ctx: &cairo::Context,
+        width: i32,
+        height: i32,
+        scale_factor: i32,
+        highlights: &[Highlight],
+    ) {
+        let (surface_width, surface_height) = (width * scale_factor, height * scale_factor);
+
+        let mut surface = self.surface.borrow_mut();
+
+        if !matches!(&*surface, Some(s) if s.width() == surface_width && s.height() == surface_height)
+        {
+            *surface = self
+                .render(surface_width, surface_height, scale_factor)
+                .map_err(|e| warn!("unable to render PDF page: {}", e))
+                .ok();
+        }
+
+        if let Some(surface) = &*surface {
+            if let Err(e) = ctx
+                .set_source_surface(surface, 0.0, 0.0)
+                .and_then(|()| ctx.paint())
+            {
+                warn!("unable to paint PDF page: {}", e);
+            }
+        }
+
+        if let Err(e) = self.draw_highlights(ctx, width, height, highlights) {
+            warn!("unable to highlight matches: {}", e);
+        }
+    }
+
+    fn render(
+        &self,
+        width: i32,
+        height: i32,
+        scale_factor: i32,
+    ) -> Result<cairo::ImageSurface, cairo::Error> {
+        let surface = cairo::ImageSurface::create(cairo::Format::Rgb24, width, height)?;
+
+        {
+            let ctx = cairo::Context::new(&surface)?;
+
+            ctx.set_source_rgb(1.0, 1.0, 1.0);
+            ctx.paint()?;
+
+            let (page_width, page_height) = self.page.size();
+            ctx.scale(
+                f64::from(width) / page_width,
+                f64::from(height) / page_height,
+            );
+
+            self.page.render(&ctx);
+        }
+
+        surface.set_device_scale(f64::from(scale_factor), f64::from(scale_factor));
+
+        Ok(surface)
+    }
+
+    fn draw_highlights(
+        &self,
+        ctx: &cairo::Context,
+        width: i32,
+        height: i32,
+        highlights: &[Highlight],
+    ) -> Result<(), cairo::Error> {
+        let (page_width, page_height) = self.page.size();
+
+        ctx.save()?;
+        ctx.scale(
+            f64::from(width) / page_width,
+            f64::from(height) / page_height,
+        );
+
+        for highlight in highlights {
+            let rect = &highlight.rect;
+
+            if highlight.current {
+                ctx.set_source_rgba(1.0, 0.5, 0.0, 0.5);
+            } else {
+                ctx.set_source_rgba(1.0, 0.9, 0.0, 0.4);
+            }
+
+            // Search results are measured from the bottom of the page.
+            ctx.rectangle(
+                rect.x1(),
+                page_height - rect.y2(),
+                rect.x2() - rect.x1(),
+                rect.y2() - rect.y1(),
+            );
+            ctx.fill()?;
+        }
+
+        ctx.restore()
+    }
+}
+
+/// A document that was loaded on a worker thread.
+struct LoadedDocument(poppler::Document);
+
+// SAFETY: Poppler documents aren't safe to use from several threads at once, but they may be moved
+// between threads. The document is never used by the worker thread after it's loaded, and is
+// either sent to the main thread or dropped.
+unsafe impl Send for LoadedDocument {}
+
+/// Load a PDF document.
+///
+/// The file is read and parsed on a worker thread, so that large documents don't block the main
+/// loop. Cancelling the cancellable stops the worker thread, which dropping the future doesn't.
+pub async fn load(
+    file: gio::File,
+    cancellable: gio::Cancellable,
+) -> Result<poppler::Document, glib::Error> {
+    gio::spawn_blocking(move || {
+        poppler::Document::from_gfile(&file, None, Some(&cancellable)).map(LoadedDocument)
+    })
+    .await
+    .expect("loading a PDF panicked")
+    .map(|document| document.0)
+}
+
+/// Find every occurrence of a query in a document, in document order.
+///
+/// Each page is searched separately, and control is returned to the main loop in between so that
+/// searching a long document doesn't freeze the window.
+pub async fn search(document: poppler::Document, query: String) -> Vec<Match> {
+    let mut matches = vec![];
+
+    for page_index in 0..document.n_pages() {
+        if let Some(page) = document.page(page_index) {
+            matches.extend(
+                page.find_text(&query)
+                    .into_iter()
+                    .map(|rect| Match { page_index, rect }),
+            );
+        }
+
+        glib::timeout_future(Duration::ZERO).await;
+    }
+
+    matches
+}
+
+/// Builds a factory that displays a thumbnail of each page of a document, for a list model with
+/// an item for each page. A page is only rendered when its thumbnail is bound, so that long
+/// documents aren't rendered all at once.
+pub fn thumbnail_factory(document: poppler::Document) -> gtk::SignalListItemFactory {
+    let factory = gtk::SignalListItemFactory::new();
+
+    factory.connect_setup(|_, list_item| {
+        let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+
+        view! {
+            #[name = "root"]
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 3,
+
+                gtk::DrawingArea {
+                    add_css_class: "bordered",
+                    set_content_width: THUMBNAIL_WIDTH,
+                    set_valign: gtk::Align::Start,
+                },
+
+                gtk::Label {
+                    add_css_class: "dim-label",
+                },
+            }
+        }
+
+        list_item.set_child(Some(&root));
+    });
+
+    factory.connect_bind(move |_, list_item| {
+        let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+        let root = list_item.child().unwrap();
+        let thumbnail = root
+            .first_child()
+            .and_downcast::<gtk::DrawingArea>()
+            .unwrap();
+        let label = thumbnail
+            .next_sibling()
+            .and_downcast::<gtk::Label>()
+            .unwrap();
+
+        let page_index = list_item.position() as i32;
+        label.set_label(&(page_index + 1).to_string());
+
+        if let Some(page) = document.page(page_index) {
+            let (page_width, page_height) = page.size();
+            thumbnail.set_content_height(
+                (f64::from(THUMBNAIL_WIDTH) * page_height / page_width).round() as i32,
+            );
+
+            let page = RenderedPage::new(page);
+            thumbnail.set_draw_func(move |area, ctx, w, h| {
+                page.draw(ctx, w, h, area.scale_factor(), &[]);
+            });
+        }
+    });
+
+    factory
 }
@@ -2,9 +2,15 @@
 
 mod alert;
 pub mod app;
+mod browser;
+pub mod conflict_dialog;
+mod delete_dialog;
 mod directory_list;
 mod file_preview;
 mod mount;
 mod new_folder_dialog;
+mod path_bar;
 mod places_sidebar;
+mod preferences;
+mod search;
 mod transfer_progress;
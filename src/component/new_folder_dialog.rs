@@ -3,6 +3,7 @@ use gtk::{gio, glib};
 use relm4::prelude::*;
 
 use super::alert::{AlertMsg, ERROR_BROKER};
+use crate::ops::journal::{self, Operation};
 
 #[derive(Debug)]
 pub struct NewFolderDialog {
@@ -94,10 +95,11 @@ impl SimpleComponent for NewFolderDialog {
                 };
 
                 relm4::spawn_local(async move {
-                    if let Err(e) = child.make_directory_future(glib::Priority::default()).await {
-                        ERROR_BROKER.send(AlertMsg::Show {
+                    match child.make_directory_future(glib::Priority::default()).await {
+                        Ok(()) => journal::record(Operation::CreateFolder(child)),
+                        Err(e) => ERROR_BROKER.send(AlertMsg::Show {
                             text: e.to_string(),
-                        });
+                        }),
                     }
                 });
 
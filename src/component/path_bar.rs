@@ -0,0 +1,404 @@
+//! Header bar widget that displays the path to the deepest listed directory.
+//!
+//! Normally the path is shown as a breadcrumb, with a button for each directory between the root
+//! and the deepest listing. The path bar can also be switched to a text entry, which accepts local
+//! paths (including paths relative to the home directory, starting with `~`) and any URI that GIO
+//! understands.
+
+use gtk::{gdk, gio, glib, prelude::*};
+use relm4::prelude::*;
+use tracing::*;
+
+use super::app::AppMsg;
+use super::directory_list::{FileSelection, Selection};
+use crate::ops::ENUMERATE_BATCH_SIZE;
+use crate::util;
+
+#[derive(Debug)]
+pub struct PathBarModel {
+    /// The directory listed by the leftmost column.
+    root: gio::File,
+
+    /// The directories from the root to the deepest listing, inclusive.
+    segments: Vec<gio::File>,
+
+    /// Whether the location entry is shown instead of the breadcrumb.
+    editing: bool,
+}
+
+#[derive(Debug)]
+pub enum PathBarMsg {
+    /// The listed directories changed.
+    Update {
+        root: gio::File,
+        last_dir: gio::File,
+    },
+
+    /// Switch to the location entry.
+    Edit,
+
+    /// Switch back to the breadcrumb.
+    StopEditing,
+
+    /// A breadcrumb segment was clicked.
+    Navigate(usize),
+
+    /// A location was entered.
+    Activate(String),
+
+    /// Complete the name at the end of the entered location.
+    Complete,
+
+    /// A completion for the entered text was found.
+    Completed { text: String, completion: String },
+}
+
+#[relm4::component(pub)]
+impl Component for PathBarModel {
+    type Init = gio::File;
+    type Input = PathBarMsg;
+    type Output = AppMsg;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Stack {
+            set_hexpand: true,
+            #[watch]
+            set_visible_child_name: if model.editing { "entry" } else { "breadcrumb" },
+
+            add_child = &gtk::ScrolledWindow {
+                set_vscrollbar_policy: gtk::PolicyType::Never,
+                set_hscrollbar_policy: gtk::PolicyType::External,
+
+                #[name = "breadcrumb"]
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_halign: gtk::Align::Center,
+                    add_css_class: "linked",
+                },
+            } -> { set_name: "breadcrumb" },
+
+            #[name = "entry"]
+            add_child = &gtk::Entry {
+                set_input_purpose: gtk::InputPurpose::Url,
+                connect_activate[sender] => move |this| {
+                    sender.input(PathBarMsg::Activate(this.text().into()));
+                },
+            } -> { set_name: "entry" },
+        }
+    }
+
+    fn init(root: gio::File, _: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let model = PathBarModel {
+            segments: vec![root.clone()],
+            root,
+            editing: false,
+        };
+
+        let widgets = view_output!();
+
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let sender_ = sender.clone();
+        key_controller.connect_key_pressed(move |_, key, _, _| match key {
+            gdk::Key::Tab => {
+                sender_.input(PathBarMsg::Complete);
+                glib::Propagation::Stop
+            }
+            gdk::Key::Escape => {
+                sender_.input(PathBarMsg::StopEditing);
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        });
+        widgets.entry.add_controller(key_controller);
+
+        let focus_controller = gtk::EventControllerFocus::new();
+        let sender_ = sender.clone();
+        focus_controller.connect_leave(move |_| {
+            sender_.input(PathBarMsg::StopEditing);
+        });
+        widgets.entry.add_controller(focus_controller);
+
+        rebuild_breadcrumb(&widgets.breadcrumb, &model.segments, &sender);
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        msg: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match msg {
+            PathBarMsg::Update { root, last_dir } => {
+                let segments = path_segments(&root, &last_dir);
+
+                let unchanged = self.root.equal(&root)
+                    && segments.len() == self.segments.len()
+                    && segments.iter().zip(&self.segments).all(|(a, b)| a.equal(b));
+
+                if !unchanged {
+                    self.root = root;
+                    self.segments = segments;
+                    rebuild_breadcrumb(&widgets.breadcrumb, &self.segments, &sender);
+                }
+            }
+            PathBarMsg::Edit => {
+                let last_dir = self.segments.last().unwrap();
+                let mut text = String::from(last_dir.parse_name());
+
+                if !text.ends_with('/') {
+                    text.push('/');
+                }
+
+                self.editing = true;
+
+                // The entry must be visible before it can be focused.
+                root.set_visible_child_name("entry");
+                widgets.entry.set_text(&text);
+                widgets.entry.grab_focus();
+                widgets.entry.select_region(0, -1);
+            }
+            PathBarMsg::StopEditing => {
+                self.editing = false;
+            }
+            PathBarMsg::Navigate(index) => {
+                let segment = self.segments[index].clone();
+
+                let msg = match segment.parent() {
+                    Some(parent) if index > 0 => {
+                        AppMsg::NewSelection(Selection::Files(FileSelection {
+                            parent,
+                            files: vec![segment],
+                        }))
+                    }
+                    _ => AppMsg::NewRoot(segment),
+                };
+
+                sender.output(msg).unwrap();
+            }
+            PathBarMsg::Activate(text) => {
+                self.editing = false;
+
+                let file = gio::File::for_parse_name(&expand_home(text.trim()));
+
+                let mount_operation = root
+                    .root()
+                    .and_downcast::<gtk::Window>()
+                    .map(|window| gtk::MountOperation::new(Some(&window)));
+
+                relm4::spawn_local(open_location(
+                    file,
+                    self.root.clone(),
+                    mount_operation,
+                    sender.output_sender().clone(),
+                ));
+            }
+            PathBarMsg::Complete => {
+                let text = String::from(widgets.entry.text());
+                let sender = sender.clone();
+
+                relm4::spawn_local(async move {
+                    if let Some(completion) = complete(&text).await {
+                        sender.input(PathBarMsg::Completed { text, completion });
+                    }
+                });
+            }
+            PathBarMsg::Completed { text, completion } => {
+                // Ignore the completion if the user kept typing while it was being found.
+                if widgets.entry.text() == text {
+                    widgets.entry.set_text(&completion);
+                    widgets.entry.set_position(-1);
+                }
+            }
+        }
+
+        self.update_view(widgets, sender);
+    }
+}
+
+/// Replace the buttons in the breadcrumb with one for each segment.
+fn rebuild_breadcrumb(
+    breadcrumb: &gtk::Box,
+    segments: &[gio::File],
+    sender: &ComponentSender<PathBarModel>,
+) {
+    while let Some(child) = breadcrumb.first_child() {
+        breadcrumb.remove(&child);
+    }
+
+    for (index, segment) in segments.iter().enumerate() {
+        let button = gtk::Button::with_label(&util::file_label(segment));
+        button.set_tooltip_text(Some(&segment.parse_name()));
+
+        let sender = sender.clone();
+        button.connect_clicked(move |_| {
+            sender.input(PathBarMsg::Navigate(index));
+        });
+
+        breadcrumb.append(&button);
+    }
+}
+
+/// Returns the directories from the root to the last directory, inclusive.
+fn path_segments(root: &gio::File, last_dir: &gio::File) -> Vec<gio::File> {
+    let mut segments = vec![last_dir.clone()];
+    let mut dir = last_dir.clone();
+
+    while !dir.equal(root) {
+        match dir.parent() {
+            Some(parent) => {
+                segments.push(parent.clone());
+                dir = parent;
+            }
+            None => {
+                warn!("{} is not beneath the root {}", last_dir.uri(), root.uri());
+                return vec![root.clone()];
+            }
+        }
+    }
+
+    segments.reverse();
+    segments
+}
+
+/// Expand a leading `~` to the home directory.
+fn expand_home(text: &str) -> String {
+    match text.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", glib::home_dir().display(), rest)
+        }
+        _ => String::from(text),
+    }
+}
+
+/// Navigate to an entered location, mounting its enclosing volume first if necessary.
+///
+/// Locations beneath the root are revealed in the existing listings. Other directories become the
+/// new root, and other files are revealed in a listing of their parent.
+async fn open_location(
+    file: gio::File,
+    root: gio::File,
+    mount_operation: Option<gtk::MountOperation>,
+    sender: relm4::Sender<AppMsg>,
+) {
+    info!("opening location: {}", file.uri());
+
+    let query_type = || {
+        file.query_info_future(
+            gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+            gio::FileQueryInfoFlags::NONE,
+            glib::Priority::DEFAULT,
+        )
+    };
+
+    let info = match query_type().await {
+        Err(err) if err.matches(gio::IOErrorEnum::NotMounted) => {
+            let mounted = file
+                .mount_enclosing_volume_future(gio::MountMountFlags::NONE, mount_operation.as_ref())
+                .await;
+
+            match mounted {
+                Ok(()) => query_type().await,
+                Err(err) => Err(err),
+            }
+        }
+        res => res,
+    };
+
+    let info = match info {
+        Ok(info) => info,
+        Err(err) => {
+            if !err.matches(gio::IOErrorEnum::FailedHandled) {
+                let _ = sender.send(AppMsg::Error(Box::new(err)));
+            }
+            return;
+        }
+    };
+
+    let is_dir = info.file_type() == gio::FileType::Directory;
+
+    if file.equal(&root) {
+        let _ = sender.send(AppMsg::NewRoot(file));
+    } else if file.has_prefix(&root) {
+        let _ = sender.send(AppMsg::RevealFile(file));
+    } else if is_dir {
+        let _ = sender.send(AppMsg::NewRoot(file));
+    } else if let Some(parent) = file.parent() {
+        let _ = sender.send(AppMsg::NewRoot(parent));
+        let _ = sender.send(AppMsg::RevealFile(file));
+    }
+}
+
+/// Complete the last segment of a partially entered location from the children of the directory
+/// it names.
+///
+/// Returns the completed text, or `None` if no children match. If several children match, the
+/// text is only completed up to their longest common prefix.
+async fn complete(text: &str) -> Option<String> {
+    let split = text.rfind('/')? + 1;
+    let (dir_text, prefix) = text.split_at(split);
+
+    let dir = gio::File::for_parse_name(&expand_home(dir_text));
+
+    let enumerator = dir
+        .enumerate_children_future(
+            &[
+                &**gio::FILE_ATTRIBUTE_STANDARD_DISPLAY_NAME,
+                &**gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+            ]
+            .join(","),
+            gio::FileQueryInfoFlags::NONE,
+            glib::Priority::DEFAULT,
+        )
+        .await
+        .ok()?;
+
+    let mut matches = vec![];
+
+    loop {
+        let infos = enumerator
+            .next_files_future(ENUMERATE_BATCH_SIZE, glib::Priority::DEFAULT)
+            .await
+            .ok()?;
+
+        if infos.is_empty() {
+            break;
+        }
+
+        matches.extend(infos.into_iter().filter_map(|info| {
+            let name = String::from(info.display_name());
+
+            // Hidden files are only completed if the user has started typing their name.
+            let hidden = name.starts_with('.') && !prefix.starts_with('.');
+
+            (name.starts_with(prefix) && !hidden)
+                .then(|| (name, info.file_type() == gio::FileType::Directory))
+        }));
+    }
+
+    let completion = match &matches[..] {
+        [] => return None,
+        [(name, true)] => format!("{}/", name),
+        [(name, false)] => name.clone(),
+        [(first, _), rest @ ..] => {
+            let mut common = first.as_str();
+
+            for (name, _) in rest {
+                let len = common
+                    .char_indices()
+                    .zip(name.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map_or(common.len().min(name.len()), |((i, _), _)| i);
+                common = &common[..len];
+            }
+
+            String::from(common)
+        }
+    };
+
+    Some(format!("{}{}", dir_text, completion))
+}
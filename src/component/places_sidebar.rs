@@ -9,12 +9,17 @@
 use glib::clone;
 use gtk::prelude::*;
 use gtk::{gdk, gio, glib};
+use relm4::actions::{ActionGroupName, RelmAction, RelmActionGroup};
 use relm4::{gtk, ComponentParts, ComponentSender, SimpleComponent};
 use tracing::*;
 
 use super::app::AppMsg;
+use super::delete_dialog::{DeleteDialogMsg, DeleteReason, DELETE_BROKER};
 use crate::ops;
 
+/// Button number identifying the right click button on a mouse.
+const BUTTON_RIGHT_CLICK: u32 = 3;
+
 mod place;
 
 use place::PlaceObject;
@@ -26,6 +31,9 @@ pub enum PlacesSidebarMsg {
 
     /// The displayed places have changed.
     Update,
+
+    /// Ask the user to confirm permanently deleting everything in the trash.
+    EmptyTrash,
 }
 
 #[derive(Debug)]
@@ -331,21 +339,29 @@ impl SimpleComponent for PlacesSidebarModel {
             icon_expression.bind(&image, "gicon", Some(&image));
 
             let drop_target = gtk::DropTarget::builder()
-                .actions(gdk::DragAction::MOVE)
+                .actions(gdk::DragAction::COPY | gdk::DragAction::MOVE | gdk::DragAction::LINK)
                 .preload(true)
                 .build();
 
-            drop_target.set_types(&[gio::File::static_type()]);
+            drop_target.set_types(&[gdk::FileList::static_type(), gio::File::static_type()]);
+
+            drop_target.connect_enter(|this, _, _| {
+                ops::drop_action_for_modifiers(this.current_event_state())
+            });
+            drop_target.connect_motion(|this, _, _| {
+                ops::drop_action_for_modifiers(this.current_event_state())
+            });
 
             let sender_ = sender_.clone();
             drop_target.connect_drop(clone!(
                 #[strong]
                 item,
-                move |_, value, _, _| {
+                move |this, value, _, _| {
                     let place = item.item().and_downcast::<PlaceObject>().unwrap();
                     let destination = place.property::<gio::File>("file");
+                    let action = ops::drop_action_for_modifiers(this.current_event_state());
 
-                    ops::handle_drop(value, &destination, sender_.output_sender().clone());
+                    ops::handle_drop(value, &destination, action, sender_.output_sender().clone());
 
                     true
                 }
@@ -353,6 +369,28 @@ impl SimpleComponent for PlacesSidebarModel {
 
             root.add_controller(drop_target);
 
+            let context_menu = gtk::PopoverMenu::from_model(Some(&trash_menu_model()));
+            context_menu.set_has_arrow(false);
+            root.append(&context_menu);
+
+            let click_controller = gtk::GestureClick::builder()
+                .button(BUTTON_RIGHT_CLICK)
+                .build();
+            click_controller.connect_pressed(clone!(
+                #[strong]
+                item,
+                move |_, _, x, y| {
+                    let place = item.item().and_downcast::<PlaceObject>().unwrap();
+
+                    if place.property::<gio::File>("file").has_uri_scheme("trash") {
+                        context_menu
+                            .set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                        context_menu.popup();
+                    }
+                }
+            ));
+            root.add_controller(click_controller);
+
             item.set_child(Some(&root));
         });
 
@@ -389,6 +427,17 @@ impl SimpleComponent for PlacesSidebarModel {
             }
         ));
 
+        let mut group = RelmActionGroup::<PlacesSidebarActionGroup>::new();
+
+        group.add_action(RelmAction::<EmptyTrashAction>::new_stateless(move |_| {
+            sender.input(PlacesSidebarMsg::EmptyTrash);
+        }));
+
+        widgets.places.insert_action_group(
+            <PlacesSidebarActionGroup as ActionGroupName>::NAME,
+            Some(&group.into_action_group()),
+        );
+
         widgets.places.set_factory(Some(&factory));
         widgets.places.set_model(Some(&model.places_model));
 
@@ -404,6 +453,29 @@ impl SimpleComponent for PlacesSidebarModel {
                 sender.output(AppMsg::NewRoot(file)).unwrap();
             }
             PlacesSidebarMsg::Update => self.update_mounts(),
+            PlacesSidebarMsg::EmptyTrash => {
+                relm4::spawn_local(async move {
+                    match ops::trash_contents().await {
+                        Ok(files) if files.is_empty() => (),
+                        Ok(files) => DELETE_BROKER.send(DeleteDialogMsg::Confirm {
+                            files,
+                            reason: DeleteReason::EmptyTrash,
+                        }),
+                        Err(e) => sender.output(AppMsg::Error(Box::new(e))).unwrap(),
+                    }
+                });
+            }
         }
     }
 }
+
+relm4::new_action_group!(PlacesSidebarActionGroup, "places");
+relm4::new_stateless_action!(EmptyTrashAction, PlacesSidebarActionGroup, "empty-trash");
+
+/// Constructs the menu model for the trash's right-click context menu.
+fn trash_menu_model() -> gio::Menu {
+    let model = gio::Menu::new();
+    model.append_item(&RelmAction::<EmptyTrashAction>::to_menu_item("Empty Trash"));
+    model.freeze();
+    model
+}
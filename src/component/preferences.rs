@@ -0,0 +1,410 @@
+//! Window for editing the user's configuration.
+//!
+//! Every change is written to the configuration file immediately, and applied to every open
+//! window. The window is refreshed if the configuration file is edited while it's open.
+
+use adw::prelude::*;
+use relm4::prelude::*;
+use sourceview5 as sourceview;
+
+use super::app::windows;
+use crate::config::{ClickToOpen, Config, SortKey, ViewMode};
+
+/// The click policies, in the order they're listed.
+const CLICK_POLICIES: [(ClickToOpen, &str); 2] = [
+    (ClickToOpen::Double, "Double Click"),
+    (ClickToOpen::Single, "Single Click"),
+];
+
+#[derive(Debug)]
+pub struct Preferences {
+    /// The configuration being edited.
+    config: Config,
+
+    /// The IDs of the installed style schemes, in the order they're listed.
+    style_schemes: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PreferencesMsg {
+    /// Present the window.
+    Show,
+
+    /// The configuration changed outside of the window.
+    ConfigChanged,
+
+    SetSortKey(SortKey),
+    SetSortDescending(bool),
+    SetFoldersFirst(bool),
+    SetShowHidden(bool),
+    SetViewMode(ViewMode),
+    SetColumnWidth(i32),
+    SetPreview(bool),
+    SetMaxPreviewBytes(u64),
+    SetStyleScheme(String),
+    SetClickToOpen(ClickToOpen),
+    SetUseTrash(bool),
+    SetConfirmDelete(bool),
+    SetConfirmEmptyTrash(bool),
+    SetTypeAheadFilter(bool),
+    SetDefaultFileManager(bool),
+}
+
+#[relm4::component(pub)]
+impl Component for Preferences {
+    type Init = ();
+    type Input = PreferencesMsg;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        adw::PreferencesWindow {
+            set_title: Some("Preferences"),
+            set_hide_on_close: true,
+            set_search_enabled: false,
+
+            add = &adw::PreferencesPage {
+                add = &adw::PreferencesGroup {
+                    set_title: "Views",
+
+                    add = &adw::ComboRow {
+                        set_title: "View New Tabs As",
+                        set_model: Some(&gtk::StringList::new(
+                            &ViewMode::ALL.map(ViewMode::label),
+                        )),
+                        #[watch]
+                        #[block_signal(view_mode_handler)]
+                        set_selected: ViewMode::ALL
+                            .iter()
+                            .position(|&mode| mode == model.config.view_mode)
+                            .unwrap_or_default() as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(&mode) = ViewMode::ALL.get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::SetViewMode(mode));
+                            }
+                        } @view_mode_handler,
+                    },
+
+                    add = &adw::ComboRow {
+                        set_title: "Sort By",
+                        set_subtitle: "Directories that have been sorted explicitly keep their order",
+                        set_model: Some(&gtk::StringList::new(
+                            &SortKey::ALL.map(SortKey::label),
+                        )),
+                        #[watch]
+                        #[block_signal(sort_key_handler)]
+                        set_selected: SortKey::ALL
+                            .iter()
+                            .position(|&key| key == model.config.default_sort.key)
+                            .unwrap_or_default() as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(&key) = SortKey::ALL.get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::SetSortKey(key));
+                            }
+                        } @sort_key_handler,
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Sort in Descending Order",
+                        set_activatable_widget: Some(&descending_switch),
+
+                        #[name = "descending_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(descending_handler)]
+                            set_active: model.config.default_sort.descending,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetSortDescending(switch.is_active()));
+                            } @descending_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Sort Folders Before Files",
+                        set_activatable_widget: Some(&folders_first_switch),
+
+                        #[name = "folders_first_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(folders_first_handler)]
+                            set_active: model.config.default_sort.folders_first,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetFoldersFirst(switch.is_active()));
+                            } @folders_first_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Show Hidden Files",
+                        set_activatable_widget: Some(&show_hidden_switch),
+
+                        #[name = "show_hidden_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(show_hidden_handler)]
+                            set_active: model.config.show_hidden,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetShowHidden(switch.is_active()));
+                            } @show_hidden_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Column Width",
+                        set_subtitle: "In pixels",
+
+                        add_suffix = &gtk::SpinButton::with_range(100.0, 1000.0, 10.0) {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(column_width_handler)]
+                            set_value: f64::from(model.config.column_width),
+                            connect_value_changed[sender] => move |spin_button| {
+                                sender.input(PreferencesMsg::SetColumnWidth(
+                                    spin_button.value_as_int(),
+                                ));
+                            } @column_width_handler,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Preview",
+
+                    add = &adw::ActionRow {
+                        set_title: "Preview the Selection",
+                        set_activatable_widget: Some(&preview_switch),
+
+                        #[name = "preview_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(preview_handler)]
+                            set_active: model.config.preview,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetPreview(switch.is_active()));
+                            } @preview_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Text Preview Size Limit",
+                        set_subtitle: "In kilobytes",
+                        #[watch]
+                        set_sensitive: model.config.preview,
+
+                        add_suffix = &gtk::SpinButton::with_range(1.0, 1024.0, 1.0) {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(max_preview_bytes_handler)]
+                            set_value: (model.config.max_preview_bytes / 1024) as f64,
+                            connect_value_changed[sender] => move |spin_button| {
+                                sender.input(PreferencesMsg::SetMaxPreviewBytes(
+                                    spin_button.value_as_int() as u64 * 1024,
+                                ));
+                            } @max_preview_bytes_handler,
+                        },
+                    },
+
+                    add = &adw::ComboRow {
+                        set_title: "Text Highlighting Theme",
+                        #[watch]
+                        set_sensitive: model.config.preview,
+                        set_model: Some(&style_scheme_names),
+                        #[watch]
+                        #[block_signal(style_scheme_handler)]
+                        set_selected: model
+                            .style_schemes
+                            .iter()
+                            .position(|id| *id == model.config.style_scheme)
+                            .map_or(gtk::INVALID_LIST_POSITION, |position| position as u32),
+                        connect_selected_notify[sender, style_schemes = model.style_schemes.clone()] => move |row| {
+                            if let Some(id) = style_schemes.get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::SetStyleScheme(id.clone()));
+                            }
+                        } @style_scheme_handler,
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Behavior",
+
+                    add = &adw::ComboRow {
+                        set_title: "Open Files With",
+                        set_model: Some(&gtk::StringList::new(
+                            &CLICK_POLICIES.map(|(_, label)| label),
+                        )),
+                        #[watch]
+                        #[block_signal(click_to_open_handler)]
+                        set_selected: CLICK_POLICIES
+                            .iter()
+                            .position(|&(policy, _)| policy == model.config.click_to_open)
+                            .unwrap_or_default() as u32,
+                        connect_selected_notify[sender] => move |row| {
+                            if let Some(&(policy, _)) = CLICK_POLICIES.get(row.selected() as usize) {
+                                sender.input(PreferencesMsg::SetClickToOpen(policy));
+                            }
+                        } @click_to_open_handler,
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Move Deleted Files to the Trash",
+                        set_subtitle: "Otherwise, the Delete key deletes files permanently",
+                        set_activatable_widget: Some(&use_trash_switch),
+
+                        #[name = "use_trash_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(use_trash_handler)]
+                            set_active: model.config.use_trash,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetUseTrash(switch.is_active()));
+                            } @use_trash_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Confirm Permanent Deletion",
+                        set_activatable_widget: Some(&confirm_delete_switch),
+
+                        #[name = "confirm_delete_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(confirm_delete_handler)]
+                            set_active: model.config.confirm.delete,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetConfirmDelete(switch.is_active()));
+                            } @confirm_delete_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Confirm Emptying the Trash",
+                        set_activatable_widget: Some(&confirm_empty_trash_switch),
+
+                        #[name = "confirm_empty_trash_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(confirm_empty_trash_handler)]
+                            set_active: model.config.confirm.empty_trash,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetConfirmEmptyTrash(
+                                    switch.is_active(),
+                                ));
+                            } @confirm_empty_trash_handler,
+                        },
+                    },
+
+                    add = &adw::ActionRow {
+                        set_title: "Reveal Files From Other Applications",
+                        set_subtitle: "Serve the desktop's file manager interface, which applications use to show downloads",
+                        set_activatable_widget: Some(&default_file_manager_switch),
+
+                        #[name = "default_file_manager_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(default_file_manager_handler)]
+                            set_active: model.config.default_file_manager,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetDefaultFileManager(
+                                    switch.is_active(),
+                                ));
+                            } @default_file_manager_handler,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Keyboard",
+
+                    add = &adw::ActionRow {
+                        set_title: "Filter While Typing",
+                        set_subtitle: "Hide files that don't match the typed text, rather than selecting the best match",
+                        set_activatable_widget: Some(&type_ahead_filter_switch),
+
+                        #[name = "type_ahead_filter_switch"]
+                        add_suffix = &gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            #[block_signal(type_ahead_filter_handler)]
+                            set_active: model.config.type_ahead_filter,
+                            connect_active_notify[sender] => move |switch| {
+                                sender.input(PreferencesMsg::SetTypeAheadFilter(switch.is_active()));
+                            } @type_ahead_filter_handler,
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(_: (), root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let scheme_manager = sourceview::StyleSchemeManager::new();
+
+        let style_schemes = scheme_manager
+            .scheme_ids()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let style_scheme_names = gtk::StringList::new(&[]);
+        for id in &style_schemes {
+            match scheme_manager.scheme(id) {
+                Some(scheme) => style_scheme_names.append(&scheme.name()),
+                None => style_scheme_names.append(id),
+            }
+        }
+
+        let model = Preferences {
+            config: Config::clone(&windows::config()),
+            style_schemes,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, _: ComponentSender<Self>, root: &Self::Root) {
+        let config = &mut self.config;
+
+        match msg {
+            PreferencesMsg::Show => {
+                *config = Config::clone(&windows::config());
+                root.present();
+                return;
+            }
+            PreferencesMsg::ConfigChanged => {
+                *config = Config::clone(&windows::config());
+                return;
+            }
+            PreferencesMsg::SetSortKey(key) => config.default_sort.key = key,
+            PreferencesMsg::SetSortDescending(descending) => {
+                config.default_sort.descending = descending
+            }
+            PreferencesMsg::SetFoldersFirst(folders_first) => {
+                config.default_sort.folders_first = folders_first
+            }
+            PreferencesMsg::SetShowHidden(show_hidden) => config.show_hidden = show_hidden,
+            PreferencesMsg::SetViewMode(mode) => config.view_mode = mode,
+            PreferencesMsg::SetColumnWidth(width) => config.column_width = width,
+            PreferencesMsg::SetPreview(preview) => config.preview = preview,
+            PreferencesMsg::SetMaxPreviewBytes(bytes) => config.max_preview_bytes = bytes,
+            PreferencesMsg::SetStyleScheme(id) => config.style_scheme = id,
+            PreferencesMsg::SetClickToOpen(policy) => config.click_to_open = policy,
+            PreferencesMsg::SetUseTrash(use_trash) => config.use_trash = use_trash,
+            PreferencesMsg::SetConfirmDelete(confirm) => config.confirm.delete = confirm,
+            PreferencesMsg::SetConfirmEmptyTrash(confirm) => config.confirm.empty_trash = confirm,
+            PreferencesMsg::SetTypeAheadFilter(filter) => config.type_ahead_filter = filter,
+            PreferencesMsg::SetDefaultFileManager(enabled) => config.default_file_manager = enabled,
+        }
+
+        windows::set_config(config.clone());
+    }
+}
@@ -0,0 +1,496 @@
+//! Recursive search for files beneath the current root.
+//!
+//! The search walks the directory tree in the background, appending matching files to a list as
+//! they're found. Changing the query or any of the filters cancels the walk in progress and starts
+//! a new one.
+
+use std::collections::VecDeque;
+
+use relm4::gtk::{gdk, gio, glib, pango, prelude::*};
+use relm4::prelude::*;
+use tracing::*;
+
+use super::app::AppMsg;
+use crate::ops::ENUMERATE_BATCH_SIZE;
+use crate::util::{self, pluralize, GFileInfoExt};
+
+mod criteria;
+
+use criteria::{Criteria, FileKind, ModifiedWithin, NameMatch, SearchOptions};
+
+/// The number of bytes in a megabyte, the unit of the size filters.
+const MEGABYTE: u64 = 1000 * 1000;
+
+/// Attributes queried for each file visited by the search.
+const ATTRIBUTES: &[&str] = &[
+    "standard::name",
+    "standard::display-name",
+    "standard::icon",
+    "standard::type",
+    "standard::is-symlink",
+    "standard::is-hidden",
+    "standard::is-backup",
+    "standard::size",
+    "time::modified",
+];
+
+/// The progress of the search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    /// No criteria have been entered.
+    Idle,
+
+    Searching,
+
+    /// Every file beneath the root has been visited.
+    Finished,
+
+    /// The query is not a valid pattern.
+    Invalid(String),
+}
+
+#[derive(Debug)]
+pub struct SearchModel {
+    /// The directory that is searched.
+    root: gio::File,
+
+    /// Whether hidden and backup files are searched.
+    show_hidden: bool,
+
+    options: SearchOptions,
+
+    /// The matching files, as [`gio::FileInfo`]s with the `standard::file` attribute set.
+    results: gio::ListStore,
+
+    /// Cancels the search in progress, if any.
+    cancellable: Option<gio::Cancellable>,
+
+    /// Incremented for every new search, so that a cancelled search can't report that it
+    /// finished.
+    generation: u64,
+
+    status: Status,
+}
+
+impl SearchModel {
+    /// Cancel the search in progress and start a new one with the current options.
+    fn restart(&mut self, sender: &ComponentSender<Self>) {
+        self.cancel();
+        self.results.remove_all();
+        self.generation += 1;
+
+        if !self.options.has_criteria() {
+            self.status = Status::Idle;
+            return;
+        }
+
+        let criteria = match Criteria::new(&self.options) {
+            Ok(criteria) => criteria,
+            Err(err) => {
+                self.status = Status::Invalid(err.to_string());
+                return;
+            }
+        };
+
+        info!(
+            "searching {} with options: {:?}",
+            self.root.uri(),
+            self.options
+        );
+
+        let cancellable = gio::Cancellable::new();
+        self.cancellable = Some(cancellable.clone());
+        self.status = Status::Searching;
+
+        let search = search(
+            self.root.clone(),
+            criteria,
+            self.show_hidden,
+            self.results.clone(),
+        );
+
+        let generation = self.generation;
+        let sender = sender.clone();
+
+        relm4::spawn_local(async move {
+            if gio::CancellableFuture::new(search, cancellable)
+                .await
+                .is_ok()
+            {
+                sender.input(SearchMsg::Finished(generation));
+            }
+        });
+    }
+
+    /// Cancel the search in progress, if any.
+    fn cancel(&mut self) {
+        if let Some(cancellable) = self.cancellable.take() {
+            cancellable.cancel();
+        }
+    }
+
+    fn status_text(&self) -> String {
+        let count = self.results.n_items();
+
+        match &self.status {
+            Status::Idle => String::from("Enter a name or choose a filter to search"),
+            Status::Searching => String::from("Searching..."),
+            Status::Finished if count == 0 => String::from("No files found"),
+            Status::Finished => format!("{} file{} found", count, pluralize!(count)),
+            Status::Invalid(err) => format!("Invalid pattern: {}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SearchMsg {
+    /// Open the search, searching beneath the given root.
+    Start {
+        root: gio::File,
+        show_hidden: bool,
+    },
+
+    /// Close the search, cancelling it if it's still in progress.
+    Stop,
+
+    SetQuery(String),
+    SetNameMatch(NameMatch),
+    SetKind(FileKind),
+
+    /// The minimum size of files, in megabytes. Zero means no limit.
+    SetMinSize(u64),
+
+    /// The maximum size of files, in megabytes. Zero means no limit.
+    SetMaxSize(u64),
+
+    SetModifiedWithin(ModifiedWithin),
+
+    /// A search finished visiting every file beneath the root.
+    Finished(u64),
+
+    /// Reveal the result at the given position in the directory listings.
+    Reveal(u32),
+}
+
+#[relm4::component(pub)]
+impl Component for SearchModel {
+    type Init = gio::File;
+    type Input = SearchMsg;
+    type Output = AppMsg;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+                set_margin_all: 6,
+
+                #[name = "entry"]
+                gtk::SearchEntry {
+                    set_hexpand: true,
+                    set_placeholder_text: Some("Search files"),
+                    connect_search_changed[sender] => move |this| {
+                        sender.input(SearchMsg::SetQuery(this.text().into()));
+                    },
+                    connect_stop_search[sender] => move |_| {
+                        sender.output(AppMsg::StopSearch).unwrap();
+                    },
+                },
+
+                gtk::DropDown::from_strings(&NameMatch::LABELS) {
+                    set_tooltip_text: Some("How names are matched"),
+                    connect_selected_notify[sender] => move |this| {
+                        let name_match = NameMatch::ALL[this.selected() as usize];
+                        sender.input(SearchMsg::SetNameMatch(name_match));
+                    },
+                },
+
+                gtk::DropDown::from_strings(&FileKind::LABELS) {
+                    connect_selected_notify[sender] => move |this| {
+                        sender.input(SearchMsg::SetKind(FileKind::ALL[this.selected() as usize]));
+                    },
+                },
+
+                gtk::DropDown::from_strings(&ModifiedWithin::LABELS) {
+                    set_tooltip_text: Some("Modified"),
+                    connect_selected_notify[sender] => move |this| {
+                        let modified_within = ModifiedWithin::ALL[this.selected() as usize];
+                        sender.input(SearchMsg::SetModifiedWithin(modified_within));
+                    },
+                },
+
+                gtk::SpinButton::with_range(0.0, 1_000_000.0, 1.0) {
+                    set_tooltip_text: Some("Minimum size in MB (0 for no limit)"),
+                    connect_value_changed[sender] => move |this| {
+                        sender.input(SearchMsg::SetMinSize(this.value() as u64));
+                    },
+                },
+
+                gtk::Label {
+                    set_label: "to",
+                },
+
+                gtk::SpinButton::with_range(0.0, 1_000_000.0, 1.0) {
+                    set_tooltip_text: Some("Maximum size in MB (0 for no limit)"),
+                    connect_value_changed[sender] => move |this| {
+                        sender.input(SearchMsg::SetMaxSize(this.value() as u64));
+                    },
+                },
+
+                gtk::Label {
+                    set_label: "MB",
+                },
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+                set_margin_start: 6,
+                set_margin_end: 6,
+
+                gtk::Spinner {
+                    #[watch]
+                    set_spinning: model.status == Status::Searching,
+                    #[watch]
+                    set_visible: model.status == Status::Searching,
+                },
+
+                gtk::Label {
+                    add_css_class: "dim-label",
+                    set_xalign: 0.0,
+                    set_ellipsize: pango::EllipsizeMode::End,
+                    #[watch]
+                    set_label: &model.status_text(),
+                },
+            },
+
+            gtk::ScrolledWindow {
+                set_hscrollbar_policy: gtk::PolicyType::Never,
+                set_vexpand: true,
+
+                #[name = "list_view"]
+                gtk::ListView {
+                    set_factory: Some(&factory),
+                    set_model: Some(&selection),
+                    set_single_click_activate: true,
+
+                    connect_activate[sender] => move |_, position| {
+                        sender.input(SearchMsg::Reveal(position));
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(root: gio::File, _: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let model = SearchModel {
+            root,
+            show_hidden: false,
+            options: SearchOptions::default(),
+            results: gio::ListStore::new::<gio::FileInfo>(),
+            cancellable: None,
+            generation: 0,
+            status: Status::Idle,
+        };
+
+        let selection = gtk::NoSelection::new(Some(model.results.clone()));
+
+        let factory = gtk::SignalListItemFactory::new();
+        factory.connect_setup(|_, list_item| {
+            let list_item = list_item.downcast_ref::<gtk::ListItem>().unwrap();
+            build_result_view(list_item);
+        });
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        msg: Self::Input,
+        sender: ComponentSender<Self>,
+        _: &Self::Root,
+    ) {
+        match msg {
+            SearchMsg::Start { root, show_hidden } => {
+                self.root = root;
+                self.show_hidden = show_hidden;
+                self.restart(&sender);
+
+                widgets.entry.grab_focus();
+            }
+            SearchMsg::Stop => {
+                self.cancel();
+
+                if self.status == Status::Searching {
+                    self.status = Status::Finished;
+                }
+            }
+            SearchMsg::SetQuery(query) => {
+                self.options.query = query;
+                self.restart(&sender);
+            }
+            SearchMsg::SetNameMatch(name_match) => {
+                self.options.name_match = name_match;
+                self.restart(&sender);
+            }
+            SearchMsg::SetKind(kind) => {
+                self.options.kind = kind;
+                self.restart(&sender);
+            }
+            SearchMsg::SetMinSize(megabytes) => {
+                self.options.min_size = (megabytes > 0).then(|| megabytes * MEGABYTE);
+                self.restart(&sender);
+            }
+            SearchMsg::SetMaxSize(megabytes) => {
+                self.options.max_size = (megabytes > 0).then(|| megabytes * MEGABYTE);
+                self.restart(&sender);
+            }
+            SearchMsg::SetModifiedWithin(modified_within) => {
+                self.options.modified_within = modified_within;
+                self.restart(&sender);
+            }
+            SearchMsg::Finished(generation) => {
+                if generation == self.generation {
+                    self.cancellable = None;
+                    self.status = Status::Finished;
+                }
+            }
+            SearchMsg::Reveal(pos) => {
+                let file = self
+                    .results
+                    .item(pos)
+                    .and_downcast::<gio::FileInfo>()
+                    .and_then(|info| info.file());
+
+                if let Some(file) = file {
+                    sender.output(AppMsg::RevealFile(file)).unwrap();
+                }
+            }
+        }
+
+        self.update_view(widgets, sender);
+    }
+}
+
+/// Construct the view for a search result, and set it as the item's child.
+///
+/// This view displays an icon, the name of the file, and the directory containing it.
+fn build_result_view(list_item: &gtk::ListItem) {
+    relm4::view! {
+        #[name = "root"]
+        gtk::Box {
+            set_orientation: gtk::Orientation::Horizontal,
+            set_spacing: 6,
+
+            #[name = "icon"]
+            gtk::Image {},
+
+            #[name = "file_name"]
+            gtk::Label {
+                set_ellipsize: pango::EllipsizeMode::Middle,
+            },
+
+            #[name = "location"]
+            gtk::Label {
+                add_css_class: "dim-label",
+                set_halign: gtk::Align::End,
+                set_hexpand: true,
+                set_ellipsize: pango::EllipsizeMode::Start,
+            },
+        }
+    }
+
+    list_item
+        .bind_property("item", &icon, "paintable")
+        .transform_to(|_, item: Option<gio::FileInfo>| {
+            item.map(|info| {
+                let icon_theme = gtk::IconTheme::for_display(&gdk::Display::default().unwrap());
+
+                util::icon_for_file(&icon_theme, 16, &info)
+            })
+        })
+        .build();
+
+    list_item
+        .bind_property("item", &file_name, "label")
+        .transform_to(|_, item: Option<gio::FileInfo>| item.map(|info| info.display_name()))
+        .build();
+
+    list_item
+        .bind_property("item", &location, "label")
+        .transform_to(|_, item: Option<gio::FileInfo>| {
+            item.and_then(|info| info.file()?.parent())
+                .map(|parent| parent.parse_name())
+        })
+        .build();
+
+    list_item.set_child(Some(&root));
+}
+
+/// Walk the directory tree beneath the root breadth-first, appending files matching the criteria
+/// to the results as they're found.
+///
+/// Directories that can't be read are skipped. Symbolic links are not followed, so the walk
+/// always terminates.
+async fn search(root: gio::File, criteria: Criteria, show_hidden: bool, results: gio::ListStore) {
+    let attributes = ATTRIBUTES.join(",");
+    let mut queue = VecDeque::from([root]);
+
+    while let Some(dir) = queue.pop_front() {
+        let enumerator = match dir
+            .enumerate_children_future(
+                &attributes,
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                glib::Priority::LOW,
+            )
+            .await
+        {
+            Ok(enumerator) => enumerator,
+            Err(err) => {
+                debug!("unable to search {}: {}", dir.uri(), err);
+                continue;
+            }
+        };
+
+        loop {
+            let infos = match enumerator
+                .next_files_future(ENUMERATE_BATCH_SIZE, glib::Priority::LOW)
+                .await
+            {
+                Ok(infos) if !infos.is_empty() => infos,
+                Ok(_) => break,
+                Err(err) => {
+                    debug!("unable to search {}: {}", dir.uri(), err);
+                    break;
+                }
+            };
+
+            let mut matches = vec![];
+
+            for info in infos {
+                if !show_hidden && (info.is_hidden() || info.is_backup()) {
+                    continue;
+                }
+
+                let file = enumerator.child(&info);
+
+                if info.file_type() == gio::FileType::Directory {
+                    queue.push_back(file.clone());
+                }
+
+                if criteria.matches(&info) {
+                    info.set_attribute_object("standard::file", &file);
+                    matches.push(info);
+                }
+            }
+
+            results.extend_from_slice(&matches);
+        }
+    }
+}
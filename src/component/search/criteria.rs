@@ -0,0 +1,295 @@
+//! Criteria that files must match to be included in search results.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use regex::{Regex, RegexBuilder};
+use relm4::gtk::{gio, prelude::*};
+
+/// How the search query is matched against file names.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NameMatch {
+    /// The name contains the query, ignoring case.
+    #[default]
+    Contains,
+
+    /// The name matches a shell-style wildcard pattern, ignoring case.
+    Glob,
+
+    /// The name matches a regular expression.
+    Regex,
+}
+
+impl NameMatch {
+    pub const ALL: [NameMatch; 3] = [NameMatch::Contains, NameMatch::Glob, NameMatch::Regex];
+
+    pub const LABELS: [&'static str; 3] = ["Contains", "Wildcard", "Regex"];
+}
+
+/// The kind of files to include in the results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    #[default]
+    Any,
+    Files,
+    Folders,
+}
+
+impl FileKind {
+    pub const ALL: [FileKind; 3] = [FileKind::Any, FileKind::Files, FileKind::Folders];
+
+    pub const LABELS: [&'static str; 3] = ["Any Type", "Files", "Folders"];
+}
+
+/// How recently files must have been modified to be included in the results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ModifiedWithin {
+    #[default]
+    AnyTime,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl ModifiedWithin {
+    pub const ALL: [ModifiedWithin; 5] = [
+        ModifiedWithin::AnyTime,
+        ModifiedWithin::Day,
+        ModifiedWithin::Week,
+        ModifiedWithin::Month,
+        ModifiedWithin::Year,
+    ];
+
+    pub const LABELS: [&'static str; 5] = [
+        "Any Time",
+        "Past Day",
+        "Past Week",
+        "Past Month",
+        "Past Year",
+    ];
+
+    fn duration(self) -> Option<Duration> {
+        const DAY: u64 = 60 * 60 * 24;
+
+        let days = match self {
+            ModifiedWithin::AnyTime => return None,
+            ModifiedWithin::Day => 1,
+            ModifiedWithin::Week => 7,
+            ModifiedWithin::Month => 30,
+            ModifiedWithin::Year => 365,
+        };
+
+        Some(Duration::from_secs(days * DAY))
+    }
+}
+
+/// The options chosen by the user for a search.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchOptions {
+    pub query: String,
+    pub name_match: NameMatch,
+    pub kind: FileKind,
+
+    /// The minimum size of files, in bytes.
+    pub min_size: Option<u64>,
+
+    /// The maximum size of files, in bytes.
+    pub max_size: Option<u64>,
+
+    pub modified_within: ModifiedWithin,
+}
+
+impl SearchOptions {
+    /// Returns whether the options would exclude any files. Searching without criteria would list
+    /// every file beneath the root, so it isn't attempted.
+    pub fn has_criteria(&self) -> bool {
+        !self.query.is_empty()
+            || self.kind != FileKind::Any
+            || self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.modified_within != ModifiedWithin::AnyTime
+    }
+}
+
+/// Compiled search options, ready to be matched against files.
+#[derive(Debug)]
+pub struct Criteria {
+    name: Regex,
+    kind: FileKind,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+
+    /// The earliest modification time of matching files, in seconds since the Unix epoch.
+    modified_after: Option<u64>,
+}
+
+impl Criteria {
+    /// Compiles the options. Fails if the query is not a valid pattern.
+    pub fn new(options: &SearchOptions) -> Result<Self, regex::Error> {
+        let name = match options.name_match {
+            NameMatch::Contains => RegexBuilder::new(&regex::escape(&options.query))
+                .case_insensitive(true)
+                .build()?,
+            NameMatch::Glob => RegexBuilder::new(&glob_to_regex(&options.query))
+                .case_insensitive(true)
+                .build()?,
+            NameMatch::Regex => Regex::new(&options.query)?,
+        };
+
+        let modified_after = options.modified_within.duration().map(|duration| {
+            (SystemTime::now() - duration)
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        });
+
+        Ok(Criteria {
+            name,
+            kind: options.kind,
+            min_size: options.min_size,
+            max_size: options.max_size,
+            modified_after,
+        })
+    }
+
+    /// Returns whether a file matches all criteria. The file info must contain the display name,
+    /// type, size, and modification time of the file.
+    pub fn matches(&self, info: &gio::FileInfo) -> bool {
+        let is_dir = info.file_type() == gio::FileType::Directory;
+
+        let kind_matches = match self.kind {
+            FileKind::Any => true,
+            FileKind::Files => !is_dir,
+            FileKind::Folders => is_dir,
+        };
+
+        if !kind_matches {
+            return false;
+        }
+
+        // The size of a directory doesn't reflect its contents, so directories never match a
+        // size range.
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let size = info.size() as u64;
+
+            if is_dir
+                || self.min_size.map_or(false, |min| size < min)
+                || self.max_size.map_or(false, |max| size > max)
+            {
+                return false;
+            }
+        }
+
+        if let Some(modified_after) = self.modified_after {
+            if info.attribute_uint64(gio::FILE_ATTRIBUTE_TIME_MODIFIED) < modified_after {
+                return false;
+            }
+        }
+
+        self.name.is_match(&info.display_name())
+    }
+}
+
+/// Translates a shell-style wildcard pattern into an anchored regular expression.
+///
+/// `*` matches any sequence of characters, `?` matches any single character, and `[...]` matches
+/// any of the enclosed characters (or none of them, if the first character is `!`).
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' if chars.clone().any(|c| c == ']') => {
+                regex.push('[');
+
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+
+                    // `&&`, `--` and `~~` are set operations within a regex class, so they must
+                    // not appear unescaped. A single `-` still denotes a range.
+                    let is_special = matches!(c, '\\' | '[' | '^' | '&' | '~')
+                        || c == '-' && regex.ends_with('-');
+
+                    if is_special {
+                        regex.push('\\');
+                    }
+
+                    regex.push(c);
+                }
+
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_to_regex;
+
+    fn glob_matches(glob: &str, name: &str) -> bool {
+        regex::Regex::new(&glob_to_regex(glob))
+            .unwrap()
+            .is_match(name)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert_eq!(glob_to_regex("*.rs"), r"^.*\.rs$");
+        assert_eq!(glob_to_regex("a?c"), "^a.c$");
+
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(!glob_matches("*.rs", "main.rs.bak"));
+        assert!(glob_matches("a?c", "abc"));
+        assert!(!glob_matches("a?c", "ac"));
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(glob_to_regex("[abc]"), "^[abc]$");
+        assert_eq!(glob_to_regex("[!abc]"), "^[^abc]$");
+        assert_eq!(glob_to_regex("[a-z]"), "^[a-z]$");
+
+        assert!(glob_matches("file[0-9]", "file7"));
+        assert!(!glob_matches("file[!0-9]", "file7"));
+    }
+
+    #[test]
+    fn unclosed_class_is_literal() {
+        assert_eq!(glob_to_regex("[abc"), r"^\[abc$");
+        assert!(glob_matches("[abc", "[abc"));
+    }
+
+    #[test]
+    fn class_set_operators_are_escaped() {
+        assert_eq!(glob_to_regex("[a&&b]"), r"^[a\&\&b]$");
+        assert_eq!(glob_to_regex("[a~~b]"), r"^[a\~\~b]$");
+        assert_eq!(glob_to_regex("[+--]"), r"^[+-\-]$");
+
+        assert!(glob_matches("[a&&b]", "&"));
+        assert!(glob_matches("[a~~b]", "~"));
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(glob_to_regex("[é]"), "^[é]$");
+        assert_eq!(glob_to_regex("[a]é"), "^[a]é$");
+        assert_eq!(glob_to_regex("é[a"), r"^é\[a$");
+
+        assert!(glob_matches("caf[eé]", "café"));
+        assert!(glob_matches("[a]é*", "aé.txt"));
+    }
+}
@@ -1,15 +1,22 @@
-use gtk::glib;
+use std::time::Instant;
+
+use gtk::{glib, prelude::*};
 use relm4::gtk;
-use relm4::panel::prelude::OrientableExt;
 use relm4::prelude::*;
 
-use super::app::AppMsg;
-use crate::ops::Progress;
+use super::app::{AppMsg, Transfer, TransferStatus};
+use crate::ops::{self, Progress, ProgressUnit};
+use crate::util::pluralize;
+
+/// Weight of the newest throughput sample in the smoothed throughput.
+const THROUGHPUT_SMOOTHING: f64 = 0.2;
 
 #[derive(Debug)]
 pub struct NewTransfer {
     pub id: u64,
     pub description: String,
+    pub pausable: bool,
+    pub unit: ProgressUnit,
 }
 
 #[derive(Debug)]
@@ -17,13 +24,68 @@ pub struct TransferProgress {
     pub id: u64,
 
     description: String,
+    pausable: bool,
+    unit: ProgressUnit,
+    paused: bool,
     current: i64,
     total: i64,
+
+    /// The time and progress of the previous progress update.
+    last_update: Option<(Instant, i64)>,
+
+    /// Smoothed transfer rate, in units per second.
+    throughput: f64,
+
+    /// The final outcome of the transfer, once it has finished.
+    status: Option<TransferStatus>,
+}
+
+impl TransferProgress {
+    /// Describes the state of the transfer beneath the progress bar.
+    fn status_text(&self) -> String {
+        match &self.status {
+            Some(TransferStatus::Completed) => String::from("Completed"),
+            Some(TransferStatus::Failed(message)) => format!("Failed: {}", message),
+            Some(TransferStatus::Cancelled) => String::from("Cancelled"),
+            None if self.paused => String::from("Paused"),
+            None if self.throughput > 0.0 => {
+                let remaining = (self.total - self.current).max(0) as f64 / self.throughput;
+
+                format!(
+                    "{}/s — {}",
+                    self.format_amount(self.throughput as u64),
+                    format_remaining(remaining as u64),
+                )
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Describes how far along the transfer is, within the progress bar.
+    fn progress_text(&self) -> String {
+        format!(
+            "{} / {}",
+            self.format_amount(self.current as u64),
+            self.format_amount(self.total as u64),
+        )
+    }
+
+    /// Formats an amount in the unit of the transfer.
+    fn format_amount(&self, amount: u64) -> String {
+        match self.unit {
+            ProgressUnit::Bytes => glib::format_size(amount).to_string(),
+            ProgressUnit::Items => format!("{} item{}", amount, pluralize!(amount)),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum TransferProgressMsg {
     Update(Progress),
+    Finished(TransferStatus),
+    TogglePause,
+    Cancel,
+    Dismiss,
 }
 
 #[relm4::factory(pub)]
@@ -37,9 +99,50 @@ impl FactoryComponent for TransferProgress {
     view! {
         gtk::Box {
             set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 3,
 
-            gtk::Label {
-                set_text: &self.description,
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+
+                gtk::Label {
+                    set_text: &self.description,
+                    set_hexpand: true,
+                    set_halign: gtk::Align::Start,
+                },
+
+                gtk::Button {
+                    add_css_class: "flat",
+                    #[watch]
+                    set_icon_name: if self.paused {
+                        "media-playback-start-symbolic"
+                    } else {
+                        "media-playback-pause-symbolic"
+                    },
+                    #[watch]
+                    set_tooltip_text: Some(if self.paused { "Resume" } else { "Pause" }),
+                    #[watch]
+                    set_visible: self.pausable && self.status.is_none(),
+                    connect_clicked => TransferProgressMsg::TogglePause,
+                },
+
+                gtk::Button {
+                    add_css_class: "flat",
+                    set_icon_name: "process-stop-symbolic",
+                    set_tooltip_text: Some("Cancel"),
+                    #[watch]
+                    set_visible: self.status.is_none(),
+                    connect_clicked => TransferProgressMsg::Cancel,
+                },
+
+                gtk::Button {
+                    add_css_class: "flat",
+                    set_icon_name: "window-close-symbolic",
+                    set_tooltip_text: Some("Dismiss"),
+                    #[watch]
+                    set_visible: self.status.is_some(),
+                    connect_clicked => TransferProgressMsg::Dismiss,
+                },
             },
             gtk::ProgressBar {
                 #[watch]
@@ -48,11 +151,15 @@ impl FactoryComponent for TransferProgress {
                 set_show_text: true,
 
                 #[watch]
-                set_text: Some(&format!(
-                    "{} / {}",
-                    glib::format_size(self.current as u64),
-                    glib::format_size(self.total as u64),
-                )),
+                set_text: Some(&self.progress_text()),
+            },
+            gtk::Label {
+                #[iterate]
+                add_css_class: ["caption", "dim-label"],
+                set_halign: gtk::Align::Start,
+
+                #[watch]
+                set_text: &self.status_text(),
             },
         }
     }
@@ -61,17 +168,77 @@ impl FactoryComponent for TransferProgress {
         TransferProgress {
             id: new_transfer.id,
             description: new_transfer.description,
+            pausable: new_transfer.pausable,
+            unit: new_transfer.unit,
+            paused: false,
             current: 0,
             total: 1,
+            last_update: None,
+            throughput: 0.0,
+            status: None,
         }
     }
 
-    fn update(&mut self, msg: Self::Input, _: FactorySender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: FactorySender<Self>) {
         match msg {
             TransferProgressMsg::Update(Progress { current, total, .. }) => {
+                let now = Instant::now();
+
+                if let Some((last_time, last_current)) = self.last_update {
+                    let elapsed = now.duration_since(last_time).as_secs_f64();
+
+                    if elapsed > 0.0 {
+                        let sample = (current - last_current) as f64 / elapsed;
+
+                        self.throughput = if self.throughput == 0.0 {
+                            sample
+                        } else {
+                            THROUGHPUT_SMOOTHING * sample
+                                + (1.0 - THROUGHPUT_SMOOTHING) * self.throughput
+                        };
+                    }
+                }
+
+                self.last_update = Some((now, current));
                 self.current = current;
                 self.total = total;
             }
+            TransferProgressMsg::Finished(status) => {
+                if let TransferStatus::Completed = status {
+                    self.current = self.total;
+                }
+
+                self.status = Some(status);
+            }
+            TransferProgressMsg::TogglePause => {
+                self.paused = !self.paused;
+
+                // Don't let the time spent paused count against the throughput.
+                self.last_update = None;
+                self.throughput = 0.0;
+
+                ops::set_paused(self.id, self.paused);
+            }
+            TransferProgressMsg::Cancel => ops::cancel(self.id),
+            TransferProgressMsg::Dismiss => {
+                sender
+                    .output(AppMsg::Transfer(Transfer::Dismiss(self.id)))
+                    .unwrap();
+            }
         }
     }
 }
+
+/// Formats an estimate of the remaining seconds of a transfer.
+fn format_remaining(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    let hours = minutes / 60;
+
+    if hours > 0 {
+        format!("{} hour{} remaining", hours, pluralize!(hours))
+    } else if minutes > 0 {
+        format!("{} minute{} remaining", minutes, pluralize!(minutes))
+    } else {
+        format!("{} second{} remaining", seconds, pluralize!(seconds))
+    }
+}
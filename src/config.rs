@@ -1,28 +1,327 @@
+use std::collections::HashMap;
 use std::fs::{self, File};
+use std::io;
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
 use directories::ProjectDirs;
+use relm4::gtk::glib;
 use serde::{Deserialize, Serialize};
 use tracing::*;
 
+use crate::shortcuts;
+
+/// User preferences. The configuration is converted to and from TOML, and stored in the platform's
+/// configuration directory. It is read at startup, and again whenever the file changes.
+///
+/// Every setting is optional, and unknown settings are rejected so that typos are reported rather
+/// than silently ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// The sort order of directory listings that haven't been sorted explicitly.
+    pub default_sort: DirectorySort,
+
+    /// Whether hidden and backup files are listed.
+    pub show_hidden: bool,
+
+    /// How the directories of new tabs are displayed. Each tab can be switched to another view
+    /// mode afterwards.
+    pub view_mode: ViewMode,
+
+    /// The size of the icons and thumbnails in the icon view, in pixels.
+    pub icon_size: i32,
+
+    /// The width of newly opened directory listings, in pixels.
+    pub column_width: i32,
+
+    /// Whether the selection is previewed.
+    pub preview: bool,
+
+    /// The number of bytes read from the start of a text file to preview it.
+    pub max_preview_bytes: u64,
+
+    /// The name of the GtkSourceView style scheme used to highlight previewed text.
+    pub style_scheme: String,
+
+    pub date_format: DateFormat,
+
+    pub confirm: Confirm,
+
+    /// How many clicks open a file.
+    pub click_to_open: ClickToOpen,
+
+    /// Whether the Delete key moves files to the trash, rather than deleting them permanently.
+    pub use_trash: bool,
+
+    /// Whether typing in a directory listing hides files that don't match, rather than selecting
+    /// the best match.
+    pub type_ahead_filter: bool,
+
+    /// Whether the `org.freedesktop.FileManager1` bus name is requested, so that other
+    /// applications reveal files in `fm`.
+    pub default_file_manager: bool,
+
+    /// Accelerators that replace the defaults, keyed by the detailed name of their action. An
+    /// empty list removes the shortcut.
+    pub shortcuts: HashMap<String, Vec<String>>,
+}
+
+impl Config {
+    /// Read from the configuration file on disk. If there is no configuration file, the default
+    /// configuration is returned.
+    pub fn read() -> Result<Self> {
+        let path = config_path()?;
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let config = toml::from_str::<Config>(&text)?;
+        config.date_format.validate()?;
+        shortcuts::validate(&config.shortcuts)?;
+
+        Ok(config)
+    }
+
+    /// Persist to disk. Only the settings that differ from the existing configuration file are
+    /// written, so that the comments and formatting of the file are preserved.
+    pub fn write(&self) -> Result<()> {
+        info!("writing configuration: {:?}", self);
+
+        let path = config_path()?;
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let text = self.update_text(&text)?;
+
+        fs::create_dir_all(path.parent().unwrap())?;
+
+        Ok(fs::write(path, text)?)
+    }
+
+    /// Returns the text of a configuration file, updated to match this configuration.
+    fn update_text(&self, text: &str) -> Result<String> {
+        let current = toml::from_str::<Config>(text)?;
+        let mut document = text.parse::<toml_edit::DocumentMut>()?;
+        update_table(
+            document.as_table_mut(),
+            toml_edit::ser::to_document(&current)?.as_table(),
+            toml_edit::ser::to_document(self)?.as_table(),
+        );
+
+        Ok(document.to_string())
+    }
+}
+
+/// Apply the differences between two serialized configurations to a table of a configuration file.
+fn update_table(
+    document: &mut dyn toml_edit::TableLike,
+    current: &dyn toml_edit::TableLike,
+    updated: &dyn toml_edit::TableLike,
+) {
+    for (key, _) in current.iter() {
+        if updated.get(key).is_none() {
+            document.remove(key);
+        }
+    }
+
+    for (key, item) in updated.iter() {
+        let current_item = current.get(key);
+        if current_item.map(|current| current.to_string()) == Some(item.to_string()) {
+            continue;
+        }
+
+        match (
+            item.as_table_like(),
+            current_item.and_then(|current| current.as_table_like()),
+        ) {
+            (Some(updated), Some(current)) => {
+                if let Some(document) = document
+                    .entry(key)
+                    .or_insert_with(toml_edit::table)
+                    .as_table_like_mut()
+                {
+                    update_table(document, current, updated);
+                }
+            }
+            _ => match document.get_mut(key) {
+                // Replace the value without replacing the key, which holds the comments above it.
+                Some(existing) => {
+                    let decor = existing.as_value().map(|value| value.decor().clone());
+                    *existing = item.clone();
+                    if let (Some(value), Some(decor)) = (existing.as_value_mut(), decor) {
+                        *value.decor_mut() = decor;
+                    }
+                }
+                None => {
+                    document.insert(key, item.clone());
+                }
+            },
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            default_sort: DirectorySort::default(),
+            show_hidden: false,
+            view_mode: ViewMode::default(),
+            icon_size: 64,
+            column_width: 200,
+            preview: true,
+            max_preview_bytes: 4096,
+            style_scheme: String::from("oblivion"),
+            date_format: DateFormat::default(),
+            confirm: Confirm::default(),
+            click_to_open: ClickToOpen::default(),
+            use_trash: true,
+            type_ahead_filter: false,
+            default_file_manager: false,
+            shortcuts: HashMap::new(),
+        }
+    }
+}
+
+/// Formats used to display dates, as understood by [`glib::DateTime::format`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateFormat {
+    /// The format used when a single file is selected.
+    pub long: String,
+
+    /// The format used when multiple files are selected.
+    pub short: String,
+}
+
+impl DateFormat {
+    /// Returns an error if either format can't be used to format a date.
+    pub fn validate(&self) -> Result<()> {
+        let now = glib::DateTime::now_local()?;
+
+        for format in [&self.long, &self.short] {
+            if now.format(format).is_err() {
+                return Err(anyhow!("invalid date format: {:?}", format));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        DateFormat {
+            long: String::from("%A, %B %-d, %Y at %-I:%M %p"),
+            short: String::from("%b %-d, %Y"),
+        }
+    }
+}
+
+/// Which actions ask for confirmation before they're carried out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Confirm {
+    /// Permanently deleting files explicitly. Files that can't be moved to the trash are always
+    /// confirmed before they're deleted permanently.
+    pub delete: bool,
+
+    /// Emptying the trash.
+    pub empty_trash: bool,
+}
+
+impl Default for Confirm {
+    fn default() -> Self {
+        Confirm {
+            delete: true,
+            empty_trash: true,
+        }
+    }
+}
+
+/// How many clicks open a file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClickToOpen {
+    /// Clicking opens a file, and hovering over it selects it.
+    Single,
+
+    /// Clicking selects a file, and double-clicking opens it.
+    #[default]
+    Double,
+}
+
+/// How the directories of a tab are displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ViewMode {
+    /// Every directory from the root to the selection is listed side by side.
+    #[default]
+    Columns,
+
+    /// A single directory is listed, with a column for each attribute of its entries.
+    Details,
+
+    /// A single directory is listed as a grid of large icons and thumbnails.
+    Icons,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 3] = [ViewMode::Columns, ViewMode::Details, ViewMode::Icons];
+
+    /// A stable identifier for the mode, used as the state of the view mode action.
+    pub fn id(self) -> &'static str {
+        match self {
+            ViewMode::Columns => "columns",
+            ViewMode::Details => "details",
+            ViewMode::Icons => "icons",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<ViewMode> {
+        ViewMode::ALL.into_iter().find(|mode| mode.id() == id)
+    }
+
+    /// A human-readable name for the mode.
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewMode::Columns => "Columns",
+            ViewMode::Details => "Details",
+            ViewMode::Icons => "Icons",
+        }
+    }
+
+    /// Whether a single directory is listed at a time, rather than every directory from the root
+    /// to the selection.
+    pub fn lists_single_directory(self) -> bool {
+        self != ViewMode::Columns
+    }
+}
+
 /// Application state that is not intended to be directly configurable by the user. The state is
-/// converted to and from JSON, and stored in the platform's application directory. It is not
-/// updated during application execution.
+/// converted to and from JSON, and stored in the platform's application directory. It is read at
+/// startup, and written whenever a window is closed.
 ///
 /// We could use [`gio::Settings`] for this, but for now this is simpler than installing and
 /// managing schemas.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct State {
-    /// Width of the main window at startup.
-    pub width: i32,
+    /// The windows that were open when the state was written, most recently focused first. They
+    /// are restored when fm is started without arguments. Otherwise, windows take the geometry at
+    /// the position they're opened in.
+    pub windows: Vec<WindowState>,
 
-    /// Height of the main window at startup.
-    pub height: i32,
-
-    /// Whether the window should be maximized at startup.
-    pub is_maximized: bool,
+    /// The sort order of directory listings, keyed by the URI of the directory. Directories using
+    /// the default sort order are not included.
+    pub directory_sorts: HashMap<String, DirectorySort>,
 }
 
 impl State {
@@ -45,18 +344,224 @@ impl State {
     }
 }
 
+impl State {
+    /// Returns the geometry of the window opened while `index` other windows are open.
+    pub fn window(&self, index: usize) -> WindowState {
+        self.windows.get(index).cloned().unwrap_or_default()
+    }
+
+    /// Returns the sort order of a directory listing, or the default if it hasn't been sorted.
+    pub fn directory_sort(&self, uri: &str, default: DirectorySort) -> DirectorySort {
+        self.directory_sorts.get(uri).copied().unwrap_or(default)
+    }
+
+    /// Remember the sort order of a directory listing, unless it's the default.
+    pub fn set_directory_sort(&mut self, uri: String, sort: DirectorySort, default: DirectorySort) {
+        if sort == default {
+            self.directory_sorts.remove(&uri);
+        } else {
+            self.directory_sorts.insert(uri, sort);
+        }
+    }
+}
+
 impl Default for State {
     fn default() -> Self {
         State {
+            windows: vec![],
+            directory_sorts: HashMap::new(),
+        }
+    }
+}
+
+/// The geometry and contents of a single window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowState {
+    pub width: i32,
+    pub height: i32,
+    pub is_maximized: bool,
+
+    /// Whether the places sidebar is shown.
+    pub sidebar_revealed: bool,
+
+    /// The tabs of the window, in order.
+    pub tabs: Vec<TabState>,
+
+    /// The position of the selected tab.
+    pub selected_tab: usize,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        WindowState {
             width: 900,
             height: 600,
             is_maximized: false,
+            sidebar_revealed: true,
+            tabs: vec![],
+            selected_tab: 0,
         }
     }
 }
 
+/// The location displayed by a tab. Files are identified by their URI.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabState {
+    /// The directory listed by the leftmost column.
+    pub root: String,
+
+    /// The deepest directory that is listed.
+    pub last_dir: String,
+
+    /// The selected files, which are all in the same directory.
+    pub selection: Vec<String>,
+
+    /// The width of each directory listing, from left to right.
+    pub column_widths: Vec<i32>,
+
+    /// The width of the preview, if it's shown.
+    pub preview_width: Option<i32>,
+
+    /// How the directories are displayed, or `None` to use the default view mode from the
+    /// configuration.
+    pub view_mode: Option<ViewMode>,
+}
+
+/// The attribute that directory entries are sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// The display name, comparing numbers within the name by their value.
+    #[default]
+    Name,
+    Size,
+    Modified,
+
+    /// The content type.
+    Type,
+    Extension,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 5] = [
+        SortKey::Name,
+        SortKey::Size,
+        SortKey::Modified,
+        SortKey::Type,
+        SortKey::Extension,
+    ];
+
+    /// A stable identifier for the key, used as the state of the sort action.
+    pub fn id(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Type => "type",
+            SortKey::Extension => "extension",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<SortKey> {
+        SortKey::ALL.into_iter().find(|key| key.id() == id)
+    }
+
+    /// A human-readable name for the key.
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::Size => "Size",
+            SortKey::Modified => "Modified",
+            SortKey::Type => "Type",
+            SortKey::Extension => "Extension",
+        }
+    }
+}
+
+/// How the entries of a directory listing are ordered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DirectorySort {
+    pub key: SortKey,
+    pub descending: bool,
+
+    /// Whether directories are listed before all other files, regardless of the sort key.
+    pub folders_first: bool,
+}
+
+fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("io", "eucl", "fm")
+        .ok_or_else(|| anyhow!("unable to find user home directory"))
+}
+
 fn state_path() -> Result<PathBuf> {
-    let dirs = ProjectDirs::from("io", "eucl", "fm")
-        .ok_or_else(|| anyhow!("unable to find user home directory"))?;
-    Ok(dirs.data_local_dir().join("state.json"))
+    Ok(project_dirs()?.data_local_dir().join("state.json"))
+}
+
+/// Returns the path of the configuration file.
+pub fn config_path() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn new_file_contains_changed_settings() {
+        let config = Config {
+            show_hidden: true,
+            ..Config::default()
+        };
+
+        assert_eq!(config.update_text("").unwrap(), "show-hidden = true\n");
+    }
+
+    #[test]
+    fn comments_are_preserved() {
+        let text = "\
+# Preferences
+
+# Show dotfiles.
+show-hidden = true # for now
+
+[confirm]
+# Ask first.
+delete = false
+";
+
+        let mut config = toml::from_str::<Config>(text).unwrap();
+        config.show_hidden = false;
+        config.confirm.empty_trash = false;
+
+        assert_eq!(
+            config.update_text(text).unwrap(),
+            "\
+# Preferences
+
+# Show dotfiles.
+show-hidden = false # for now
+
+[confirm]
+# Ask first.
+delete = false
+empty-trash = false
+",
+        );
+    }
+
+    #[test]
+    fn unchanged_settings_are_not_written() {
+        let text = "[confirm]\ndelete = false\n";
+        let config = toml::from_str::<Config>(text).unwrap();
+
+        assert_eq!(config.update_text(text).unwrap(), text);
+    }
+
+    #[test]
+    fn unreadable_file_is_rejected() {
+        assert!(Config::default().update_text("show-hidden = ").is_err());
+    }
 }
@@ -10,6 +10,8 @@
 mod component;
 mod config;
 mod ops;
+mod shortcuts;
+mod thumbnail;
 mod util;
 
-pub use component::app::AppModel;
+pub use component::app::{AppInit, AppModel};
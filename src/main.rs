@@ -1,23 +1,59 @@
+use std::env;
 use std::fs;
 use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::Parser;
-use relm4::{gtk, RelmApp};
+use relm4::gtk::{self, gio, glib, prelude::*};
+use relm4::RelmApp;
 use tracing::*;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 use tracing_tree::HierarchicalLayer;
 
-use fm::AppModel;
+use fm::{AppInit, AppModel};
 
 /// A paned file manager with automatic preview.
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
 struct Args {
-    /// The file or directory to open.
-    #[clap(default_value = ".")]
-    file: PathBuf,
+    /// The directory to open, or the file to select and preview. Defaults to the root. If neither
+    /// this nor the root is given, the windows from the previous session are restored, or the
+    /// current directory is opened if there were none.
+    file: Option<PathBuf>,
+
+    /// The directory listed by the leftmost column. Every directory between the root and the file
+    /// is listed. Defaults to the directory, or the parent of the file.
+    #[clap(long, value_name = "DIR")]
+    root: Option<PathBuf>,
+
+    /// If fm is already running, open a new window. This is the default.
+    #[clap(long, overrides_with = "tab")]
+    new_window: bool,
+
+    /// If fm is already running, open a new tab in its most recently focused window.
+    #[clap(long, overrides_with = "new_window")]
+    tab: bool,
+
+    /// Open the current directory instead of restoring the previous session.
+    #[clap(long)]
+    no_restore: bool,
+}
+
+impl Args {
+    /// Whether the previous session should be restored instead of opening a location.
+    fn restore(&self) -> bool {
+        self.file.is_none() && self.root.is_none() && !self.no_restore
+    }
+
+    /// The location to open: the file if it was given, otherwise the root, otherwise the current
+    /// directory.
+    fn file(&self) -> PathBuf {
+        self.file
+            .clone()
+            .or_else(|| self.root.clone())
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
 }
 
 fn main() -> Result<()> {
@@ -29,12 +65,59 @@ fn main() -> Result<()> {
     let args = Args::parse();
     info!("running with arguments: {:?}", args);
 
+    let restore = args.restore();
+
     // Call `gtk::init` manually because we instantiate GTK types in the app model.
     gtk::init().unwrap();
 
     relm4::set_global_css(include_str!("styles.css"));
-    let app = RelmApp::new("io.github.fm").with_args(vec![]);
-    app.run::<AppModel>(fs::canonicalize(args.file)?);
+    let app = RelmApp::new("io.github.fm").with_args(env::args().collect());
+
+    // If the application is already running, the arguments are sent to the running instance, which
+    // opens a new window or tab. Otherwise, this process becomes the running instance.
+    let application = relm4::main_application();
+    application.set_flags(gio::ApplicationFlags::HANDLES_COMMAND_LINE);
+    application.connect_command_line(move |application, command_line| {
+        if !command_line.is_remote() {
+            // The arguments of this process have already been parsed. Activating the application
+            // opens a window at the location they name.
+            if !(restore && AppModel::restore_session()) {
+                application.activate();
+            }
+            return glib::ExitCode::SUCCESS;
+        }
+
+        let args = match Args::try_parse_from(command_line.arguments()) {
+            Ok(args) => args,
+            Err(err) => {
+                command_line.printerr_literal(&err.to_string());
+                return glib::ExitCode::FAILURE;
+            }
+        };
+        info!("opening location from remote arguments: {:?}", args);
+
+        // The running instance already has windows open, so there's no session to restore.
+        let mut init = AppInit::new(command_line.create_file_for_arg(args.file()));
+        if let Some(root) = args.root {
+            init = init.with_root(command_line.create_file_for_arg(root));
+        }
+
+        if args.tab && !args.new_window {
+            AppModel::open_in_tab(init);
+        } else {
+            AppModel::open(init);
+        }
+
+        glib::ExitCode::SUCCESS
+    });
+
+    let file = fs::canonicalize(args.file())?;
+    let mut init = AppInit::new(gio::File::for_path(file));
+    if let Some(root) = args.root {
+        init = init.with_root(gio::File::for_path(fs::canonicalize(root)?));
+    }
+
+    app.run::<AppModel>(init);
 
     info!("main loop exited");
 
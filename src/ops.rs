@@ -3,17 +3,41 @@
 //! This module contains functions that abstract filesystem operations at a higher level than
 //! raw gio.
 
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
+use futures::channel::oneshot;
+use futures::future::{self, Either, LocalBoxFuture};
 use futures::prelude::*;
-use gtk::{gio, glib, prelude::*};
+use gtk::{gdk, gio, glib, prelude::*};
 use relm4::{gtk, Sender};
 use tracing::*;
 
-use crate::component::app::{AppMsg, Transfer};
+use crate::component::app::{AppMsg, Transfer, TransferStatus};
+use crate::component::conflict_dialog::{Conflict, ConflictDialogMsg, CONFLICT_BROKER};
+
+pub mod clipboard;
+pub mod journal;
+
+use journal::Operation;
 
 static ID: AtomicU64 = AtomicU64::new(0);
 
+/// The number of directory entries requested at a time while enumerating a directory.
+pub const ENUMERATE_BATCH_SIZE: i32 = 64;
+
+/// The number of bytes read and written at a time while copying a file.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// How often a paused transfer checks whether it has been resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// File transfer progress update.
 #[derive(Debug)]
 pub struct Progress {
@@ -31,10 +55,161 @@ impl Progress {
     }
 }
 
-/// Move a file to a destination.
-pub async fn move_(file: gio::File, destination: gio::File, sender: Sender<AppMsg>) {
-    info!("moving {} to {}", file.uri(), destination.uri());
+/// What the progress of a transfer is measured in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressUnit {
+    Bytes,
+
+    /// Files and directories.
+    Items,
+}
+
+/// How to resolve a transfer whose destination already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Overwrite the existing destination.
+    Replace,
+
+    /// Leave the destination alone, and don't transfer the file.
+    Skip,
+
+    /// Transfer the file next to the destination under a new name, such as "name (2).ext".
+    KeepBoth,
+
+    /// Merge the contents of the source directory into the destination directory.
+    Merge,
+}
+
+/// State shared by all transfers started from a single user action, such as dropping several
+/// files at once.
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    /// Whether more than one conflict may occur during the action.
+    multiple: bool,
+
+    /// The resolution that the user chose to apply to all conflicts, if any.
+    resolution: Rc<Cell<Option<ConflictResolution>>>,
+}
+
+impl Batch {
+    /// Create a new batch for an action on the given number of files.
+    pub fn new(len: usize) -> Self {
+        Batch {
+            multiple: len > 1,
+            resolution: Default::default(),
+        }
+    }
+
+    /// Returns a batch sharing the same resolution, for conflicts inside merged directories.
+    fn nested(&self) -> Self {
+        Batch {
+            multiple: true,
+            resolution: self.resolution.clone(),
+        }
+    }
+}
+
+/// Where a transfer should be written to after resolving any conflicts.
+enum Target {
+    /// Transfer to the file, using the given flags.
+    File(gio::File, gio::FileCopyFlags),
+
+    /// Transfer to a temporary file next to the destination, then replace the destination with it.
+    /// This is used when either file is a directory, since directories can't be overwritten.
+    Replace {
+        temporary: TemporaryFile,
+        destination: gio::File,
+    },
+
+    /// Merge the source directory into the existing directory.
+    Merge(gio::File),
+
+    /// Don't transfer the file.
+    Skip,
+}
+
+/// A file that a transfer is written to before it is moved into place. Unless the file is moved,
+/// it is removed when dropped, so that transfers that fail or are cancelled part way don't leave
+/// anything behind.
+struct TemporaryFile {
+    file: gio::File,
+
+    /// Whether the file is being moved into place, and must be kept from then on.
+    persisted: bool,
+}
+
+impl TemporaryFile {
+    /// Returns an unused hidden name next to the given file.
+    async fn next_to(file: &gio::File) -> Self {
+        let mut name = OsString::from(".");
+        name.push(file.basename().unwrap());
+        name.push(".fm-partial");
+
+        let mut temporary = file.parent().unwrap().child(name);
+
+        if exists(&temporary).await {
+            temporary = unused_name(&temporary).await;
+        }
+
+        TemporaryFile {
+            file: temporary,
+            persisted: false,
+        }
+    }
+
+    fn file(&self) -> &gio::File {
+        &self.file
+    }
+
+    /// Move the temporary file to the destination, using the given flags.
+    async fn persist(
+        mut self,
+        destination: &gio::File,
+        flags: gio::FileCopyFlags,
+    ) -> Result<(), glib::Error> {
+        self.persisted = true;
 
+        let (res, _) = self.file.move_future(
+            destination,
+            flags | gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+            glib::Priority::DEFAULT,
+        );
+        res.await
+    }
+
+    /// Replace the destination with the temporary file. The destination is deleted first, so this
+    /// must only be called once the transfer to the temporary file has succeeded.
+    async fn replace(mut self, destination: &gio::File) -> Result<(), glib::Error> {
+        // From here on, the temporary file may be the only copy of the transferred data.
+        self.persisted = true;
+
+        delete_recursive(destination.clone()).await?;
+
+        self.persist(destination, gio::FileCopyFlags::NONE).await
+    }
+}
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        if self.persisted {
+            return;
+        }
+
+        let file = self.file.clone();
+        relm4::spawn_local(async move {
+            match delete_recursive(file.clone()).await {
+                Err(e) if !e.matches(gio::IOErrorEnum::NotFound) => {
+                    warn!("unable to remove {}: {}", file.uri(), e);
+                }
+                _ => (),
+            }
+        });
+    }
+}
+
+/// Query the display names of a file and the directory that it is being transferred into, for
+/// use in transfer descriptions.
+async fn display_names(file: &gio::File, destination: &gio::File) -> (String, String) {
     let (file_display_name, destination_display_name) = futures::join!(
         file.query_info_future(
             gio::FILE_ATTRIBUTE_STANDARD_DISPLAY_NAME,
@@ -53,23 +228,349 @@ pub async fn move_(file: gio::File, destination: gio::File, sender: Sender<AppMs
             .map_ok(|info| info.display_name()),
     );
 
+    (
+        file_display_name.map_or_else(|_| "file".into(), String::from),
+        destination_display_name.map_or_else(|_| "destination".into(), String::from),
+    )
+}
+
+/// Controls of an ongoing transfer, shared between the operation and the transfer progress UI.
+#[derive(Debug, Clone)]
+struct TransferHandle {
+    /// Cancels the transfer.
+    cancellable: gio::Cancellable,
+
+    /// Whether the transfer should stop making progress until it is resumed.
+    paused: Rc<Cell<bool>>,
+}
+
+impl TransferHandle {
+    /// Wait until the transfer is no longer paused.
+    async fn wait_while_paused(&self) {
+        while self.paused.get() && !self.cancellable.is_cancelled() {
+            glib::timeout_future(PAUSE_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Wait until the transfer is paused.
+    async fn wait_until_paused(&self) {
+        while !self.paused.get() {
+            glib::timeout_future(PAUSE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+thread_local! {
+    /// Controls of the ongoing transfers, by ID.
+    static TRANSFERS: RefCell<HashMap<u64, TransferHandle>> = RefCell::default();
+}
+
+/// Cancel an ongoing transfer.
+pub fn cancel(id: u64) {
+    info!("cancelling transfer {}", id);
+
+    if let Some(handle) = TRANSFERS.with(|transfers| transfers.borrow().get(&id).cloned()) {
+        handle.cancellable.cancel();
+    }
+}
+
+/// Pause or resume an ongoing transfer.
+///
+/// Transfers only pause between files or chunks of data, so moves within the same filesystem, which
+/// happen all at once, cannot be paused. A file that is being copied when the transfer is paused
+/// is copied again from the start in chunks once the transfer is resumed.
+pub fn set_paused(id: u64, paused: bool) {
+    info!("setting transfer {} paused: {}", id, paused);
+
+    if let Some(handle) = TRANSFERS.with(|transfers| transfers.borrow().get(&id).cloned()) {
+        handle.paused.set(paused);
+    }
+}
+
+/// Notify the application of a new transfer, returning its ID and controls.
+fn new_transfer(
+    description: String,
+    pausable: bool,
+    unit: ProgressUnit,
+    sender: &Sender<AppMsg>,
+) -> (u64, TransferHandle) {
     let id = ID.fetch_add(1, Ordering::SeqCst);
-    let description = format!(
-        "Moving '{}' to '{}'",
-        file_display_name.unwrap_or_else(|_| "file".into()),
-        destination_display_name.unwrap_or_else(|_| "destination".into()),
-    );
+
+    let handle = TransferHandle {
+        cancellable: gio::Cancellable::new(),
+        paused: Rc::new(Cell::new(false)),
+    };
+
+    TRANSFERS.with(|transfers| transfers.borrow_mut().insert(id, handle.clone()));
 
     sender
-        .send(AppMsg::Transfer(Transfer::New { id, description }))
+        .send(AppMsg::Transfer(Transfer::New {
+            id,
+            description,
+            pausable,
+            unit,
+        }))
         .unwrap();
 
-    let (res, mut progress) = file.move_future(
-        &destination,
-        gio::FileCopyFlags::NONE,
-        glib::source::Priority::DEFAULT,
+    (id, handle)
+}
+
+/// Run a transfer until it finishes or is cancelled, then notify the application of the outcome.
+///
+/// Returns whether the transfer completed successfully.
+async fn run_transfer(
+    id: u64,
+    handle: TransferHandle,
+    transfer: impl Future<Output = Result<(), glib::Error>>,
+    sender: &Sender<AppMsg>,
+) -> bool {
+    let res = gio::CancellableFuture::new(transfer, handle.cancellable).await;
+
+    TRANSFERS.with(|transfers| transfers.borrow_mut().remove(&id));
+
+    let status = match res {
+        Ok(Ok(())) => TransferStatus::Completed,
+        Ok(Err(err)) if err.matches(gio::IOErrorEnum::Cancelled) => TransferStatus::Cancelled,
+        Err(gio::Cancelled) => TransferStatus::Cancelled,
+        Ok(Err(err)) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err.clone())));
+            TransferStatus::Failed(err.to_string())
+        }
+    };
+
+    info!("transfer {} finished: {:?}", id, status);
+
+    let completed = matches!(status, TransferStatus::Completed);
+
+    let _ = sender.send(AppMsg::Transfer(Transfer::Finished { id, status }));
+
+    completed
+}
+
+/// Returns whether a file exists. Broken symbolic links are considered to exist.
+async fn exists(file: &gio::File) -> bool {
+    file.query_info_future(
+        gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+        glib::Priority::DEFAULT,
+    )
+    .await
+    .is_ok()
+}
+
+/// Returns whether a file is a directory. Symbolic links to directories are not considered to be
+/// directories.
+async fn is_directory(file: &gio::File) -> Result<bool, glib::Error> {
+    let info = file
+        .query_info_future(
+            gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    Ok(info.file_type() == gio::FileType::Directory)
+}
+
+/// Determine where a file should be transferred to. If the destination already exists, the user is
+/// asked how to resolve the conflict, unless they already chose a resolution for the whole batch.
+///
+/// Merging is only offered if `allow_merge` is true and both files are directories.
+async fn resolve_conflict(
+    file: &gio::File,
+    destination: gio::File,
+    allow_merge: bool,
+    batch: &Batch,
+) -> Result<Target, glib::Error> {
+    if !exists(&destination).await {
+        return Ok(Target::File(destination, gio::FileCopyFlags::NONE));
+    }
+
+    let attributes = [
+        &**gio::FILE_ATTRIBUTE_STANDARD_DISPLAY_NAME,
+        &**gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+        &**gio::FILE_ATTRIBUTE_STANDARD_SIZE,
+        &**gio::FILE_ATTRIBUTE_TIME_MODIFIED,
+    ]
+    .join(",");
+
+    let (source_info, destination_info) = futures::try_join!(
+        file.query_info_future(
+            &attributes,
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            glib::Priority::DEFAULT,
+        ),
+        destination.query_info_future(
+            &attributes,
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            glib::Priority::DEFAULT,
+        ),
+    )?;
+
+    let is_dir = |info: &gio::FileInfo| info.file_type() == gio::FileType::Directory;
+    let replaces_dir = is_dir(&source_info) || is_dir(&destination_info);
+    let can_merge = allow_merge && is_dir(&source_info) && is_dir(&destination_info);
+
+    let resolution = match batch.resolution.get() {
+        Some(ConflictResolution::Merge) if !can_merge => None,
+        resolution => resolution,
+    };
+
+    let resolution = match resolution {
+        Some(resolution) => resolution,
+        None => {
+            let (_, destination_dir_name) = display_names(file, &destination).await;
+
+            let (responder, response) = oneshot::channel();
+            CONFLICT_BROKER.send(ConflictDialogMsg::Show(Conflict {
+                source: source_info,
+                destination: destination_info,
+                destination_dir_name,
+                can_merge,
+                can_apply_to_all: batch.multiple,
+                responder,
+            }));
+
+            let (resolution, apply_to_all) =
+                response.await.unwrap_or((ConflictResolution::Skip, false));
+
+            if apply_to_all {
+                batch.resolution.set(Some(resolution));
+            }
+
+            resolution
+        }
+    };
+
+    info!(
+        "resolved conflict at {} with {:?}",
+        destination.uri(),
+        resolution
+    );
+
+    Ok(match resolution {
+        // Directories can't be overwritten, so they are removed once the transfer has succeeded.
+        ConflictResolution::Replace if replaces_dir => Target::Replace {
+            temporary: TemporaryFile::next_to(&destination).await,
+            destination,
+        },
+        ConflictResolution::Replace => Target::File(destination, gio::FileCopyFlags::OVERWRITE),
+        ConflictResolution::Skip => Target::Skip,
+        ConflictResolution::KeepBoth => {
+            Target::File(unused_name(&destination).await, gio::FileCopyFlags::NONE)
+        }
+        ConflictResolution::Merge => Target::Merge(destination),
+    })
+}
+
+/// Returns a file next to the given file whose name is not yet taken, by appending an increasing
+/// number to the file stem: "name (2).ext", "name (3).ext", and so on.
+async fn unused_name(file: &gio::File) -> gio::File {
+    let parent = file.parent().unwrap();
+    let name = file.basename().unwrap();
+
+    for n in 2.. {
+        let candidate = parent.child(numbered_name(&name, n));
+
+        if !exists(&candidate).await {
+            return candidate;
+        }
+    }
+
+    unreachable!("ran out of file names")
+}
+
+/// Inserts a number between the stem and the extension of a file name.
+fn numbered_name(name: &Path, n: u32) -> OsString {
+    let mut numbered = name.file_stem().unwrap_or(name.as_os_str()).to_owned();
+    numbered.push(format!(" ({})", n));
+
+    if let Some(extension) = name.extension() {
+        numbered.push(".");
+        numbered.push(extension);
+    }
+
+    numbered
+}
+
+/// Move a file to a destination. Returns whether the file was moved.
+pub async fn move_(
+    file: gio::File,
+    destination: gio::File,
+    batch: Batch,
+    sender: Sender<AppMsg>,
+) -> bool {
+    info!("moving {} to {}", file.uri(), destination.uri());
+
+    let target = resolve_conflict(&file, destination, true, &batch).await;
+
+    let (destination, flags, replaced) = match target {
+        Ok(Target::File(destination, flags)) => (destination, flags, None),
+        Ok(Target::Replace {
+            temporary,
+            destination,
+        }) => (
+            temporary.file().clone(),
+            gio::FileCopyFlags::NONE,
+            Some((temporary, destination)),
+        ),
+        Ok(Target::Merge(destination)) => {
+            let (file_display_name, destination_display_name) =
+                display_names(&file, &destination).await;
+
+            let (id, handle) = new_transfer(
+                format!(
+                    "Merging '{}' into '{}'",
+                    file_display_name, destination_display_name
+                ),
+                true,
+                ProgressUnit::Bytes,
+                &sender,
+            );
+
+            let from_trash = file.has_uri_scheme("trash");
+            let operations = Rc::new(RefCell::new(vec![]));
+
+            let merge = merge_move(
+                file,
+                destination,
+                batch.nested(),
+                handle.clone(),
+                operations.clone(),
+            );
+            let completed = run_transfer(id, handle, merge, &sender).await;
+
+            // Whatever was merged before a failure or cancellation can still be undone, unless it
+            // was restored from the trash.
+            let operations = operations.take();
+            if !operations.is_empty() && !from_trash {
+                journal::record(Operation::Merge(operations));
+            }
+
+            return completed;
+        }
+        Ok(Target::Skip) => return false,
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+            return false;
+        }
+    };
+
+    let (file_display_name, destination_display_name) = display_names(&file, &destination).await;
+
+    let (id, handle) = new_transfer(
+        format!(
+            "Moving '{}' to '{}'",
+            file_display_name, destination_display_name
+        ),
+        false,
+        ProgressUnit::Bytes,
+        &sender,
     );
 
+    let (res, mut progress) =
+        file.move_future(&destination, flags, glib::source::Priority::DEFAULT);
+
     let sender_ = sender.clone();
     relm4::spawn_local(async move {
         while let Some((current, total)) = progress.next().await {
@@ -81,20 +582,757 @@ pub async fn move_(file: gio::File, destination: gio::File, sender: Sender<AppMs
         }
     });
 
-    if let Err(err) = res.await {
+    let completed = run_transfer(id, handle, res, &sender).await;
+
+    if let Some((temporary, replaced)) = replaced {
+        if !completed {
+            return false;
+        }
+
+        // Replaced directories can't be brought back, so this can't be undone.
+        return match temporary.replace(&replaced).await {
+            Ok(()) => true,
+            Err(err) => {
+                let _ = sender.send(AppMsg::Error(Box::new(err)));
+                false
+            }
+        };
+    }
+
+    // Replaced files can't be brought back, and files restored from the trash can't be moved back
+    // into it, so neither can be undone.
+    if completed && !flags.contains(gio::FileCopyFlags::OVERWRITE) && !file.has_uri_scheme("trash")
+    {
+        journal::record(Operation::Move {
+            from: file,
+            to: destination,
+        });
+    }
+
+    completed
+}
+
+/// Move the contents of a directory into an existing directory, resolving conflicts between their
+/// children. The source directory is removed if it is empty afterwards.
+///
+/// The moves and removals are appended to `operations` as they happen, so that they can be undone.
+/// Children that replaced existing files are not included, since the replaced files can't be
+/// brought back.
+fn merge_move(
+    dir: gio::File,
+    destination: gio::File,
+    batch: Batch,
+    handle: TransferHandle,
+    operations: Rc<RefCell<Vec<Operation>>>,
+) -> LocalBoxFuture<'static, Result<(), glib::Error>> {
+    async move {
+        for name in child_names(&dir).await? {
+            handle.wait_while_paused().await;
+
+            let child = dir.child(&name);
+
+            match resolve_conflict(&child, destination.child(&name), true, &batch).await? {
+                Target::File(child_destination, flags) => {
+                    let (res, _) =
+                        child.move_future(&child_destination, flags, glib::Priority::DEFAULT);
+                    res.await?;
+
+                    if !flags.contains(gio::FileCopyFlags::OVERWRITE) {
+                        operations.borrow_mut().push(Operation::Move {
+                            from: child,
+                            to: child_destination,
+                        });
+                    }
+                }
+                Target::Replace {
+                    temporary,
+                    destination: child_destination,
+                } => {
+                    let (res, _) = child.move_future(
+                        temporary.file(),
+                        gio::FileCopyFlags::NONE,
+                        glib::Priority::DEFAULT,
+                    );
+                    res.await?;
+
+                    temporary.replace(&child_destination).await?;
+                }
+                Target::Merge(child_destination) => {
+                    merge_move(
+                        child,
+                        child_destination,
+                        batch.clone(),
+                        handle.clone(),
+                        operations.clone(),
+                    )
+                    .await?;
+                }
+                Target::Skip => (),
+            }
+        }
+
+        match dir.delete_future(glib::Priority::DEFAULT).await {
+            Ok(()) => {
+                operations.borrow_mut().push(Operation::RemoveFolder(dir));
+                Ok(())
+            }
+            Err(e) if e.matches(gio::IOErrorEnum::NotEmpty) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+    .boxed_local()
+}
+
+/// Copy a file to a destination. Directories are copied recursively. Returns whether the file was
+/// copied.
+pub async fn copy(
+    file: gio::File,
+    destination: gio::File,
+    batch: Batch,
+    sender: Sender<AppMsg>,
+) -> bool {
+    info!("copying {} to {}", file.uri(), destination.uri());
+
+    let target = match resolve_conflict(&file, destination.clone(), true, &batch).await {
+        Ok(Target::Skip) => return false,
+        Ok(target) => target,
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+            return false;
+        }
+    };
+
+    let (file_display_name, destination_display_name) = display_names(&file, &destination).await;
+
+    let (id, handle) = new_transfer(
+        format!(
+            "Copying '{}' to '{}'",
+            file_display_name, destination_display_name
+        ),
+        true,
+        ProgressUnit::Bytes,
+        &sender,
+    );
+
+    // Unlike moves, copies of directories are not handled by gio, so we need to know the size of
+    // the entire tree up front to report meaningful progress.
+    let (total, _) = file.measure_disk_usage_future(
+        gio::FileMeasureFlags::APPARENT_SIZE,
+        glib::Priority::DEFAULT,
+    );
+    let total = match total.await {
+        Ok((bytes, _, _)) => bytes as i64,
+        Err(e) => {
+            warn!("unable to measure size of {}: {}", file.uri(), e);
+            0
+        }
+    };
+
+    let context = CopyContext {
+        id,
+        copied: Rc::new(Cell::new(0)),
+        total,
+        batch: batch.nested(),
+        handle: handle.clone(),
+        sender: sender.clone(),
+    };
+
+    let copy = async move {
+        copy_recursive(file, target, context.clone()).await?;
+        context.send_progress(total);
+        Ok::<_, glib::Error>(())
+    };
+
+    run_transfer(id, handle, copy, &sender).await
+}
+
+/// State shared by all files copied as part of a recursive copy.
+#[derive(Clone)]
+struct CopyContext {
+    id: u64,
+
+    /// The number of bytes copied so far.
+    copied: Rc<Cell<i64>>,
+
+    total: i64,
+    batch: Batch,
+    handle: TransferHandle,
+    sender: Sender<AppMsg>,
+}
+
+impl CopyContext {
+    fn send_progress(&self, current: i64) {
+        let _ = self
+            .sender
+            .send(AppMsg::Transfer(Transfer::Progress(Progress {
+                id: self.id,
+                current: current.min(self.total),
+                total: self.total,
+            })));
+    }
+
+    /// Record that more bytes have been copied.
+    fn add_copied(&self, bytes: i64) {
+        self.copied.set(self.copied.get() + bytes);
+        self.send_progress(self.copied.get());
+    }
+}
+
+/// Copy a file to its target, recursing into directories.
+fn copy_recursive(
+    file: gio::File,
+    target: Target,
+    context: CopyContext,
+) -> LocalBoxFuture<'static, Result<(), glib::Error>> {
+    async move {
+        context.handle.wait_while_paused().await;
+
+        let destination = match target {
+            Target::File(destination, flags) => {
+                let info = file
+                    .query_info_future(
+                        &[
+                            &**gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+                            &**gio::FILE_ATTRIBUTE_STANDARD_SIZE,
+                        ]
+                        .join(","),
+                        gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                        glib::Priority::DEFAULT,
+                    )
+                    .await?;
+
+                match info.file_type() {
+                    gio::FileType::Regular => {
+                        return copy_contents(&file, &destination, flags, info.size(), &context)
+                            .await;
+                    }
+                    gio::FileType::Directory => (),
+                    _ => {
+                        // Symbolic links and special files are copied by gio as-is.
+                        let (res, _) = file.copy_future(
+                            &destination,
+                            flags | gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+                            glib::Priority::DEFAULT,
+                        );
+                        res.await?;
+
+                        context.add_copied(info.size());
+                        return Ok(());
+                    }
+                }
+
+                destination
+                    .make_directory_future(glib::Priority::DEFAULT)
+                    .await?;
+
+                for name in child_names(&file).await? {
+                    let child_target =
+                        Target::File(destination.child(&name), gio::FileCopyFlags::NONE);
+                    copy_recursive(file.child(&name), child_target, context.clone()).await?;
+                }
+
+                return Ok(());
+            }
+            Target::Replace {
+                temporary,
+                destination,
+            } => {
+                let temporary_target =
+                    Target::File(temporary.file().clone(), gio::FileCopyFlags::NONE);
+                copy_recursive(file, temporary_target, context).await?;
+
+                return temporary.replace(&destination).await;
+            }
+            Target::Merge(destination) => destination,
+            Target::Skip => return Ok(()),
+        };
+
+        for name in child_names(&file).await? {
+            let child = file.child(&name);
+            let child_target =
+                resolve_conflict(&child, destination.child(&name), true, &context.batch).await?;
+            copy_recursive(child, child_target, context.clone()).await?;
+        }
+
+        Ok(())
+    }
+    .boxed_local()
+}
+
+/// Copy a regular file. The file is copied to a temporary file next to the destination, which is
+/// only moved into place once the copy has succeeded.
+async fn copy_contents(
+    file: &gio::File,
+    destination: &gio::File,
+    flags: gio::FileCopyFlags,
+    size: i64,
+    context: &CopyContext,
+) -> Result<(), glib::Error> {
+    let temporary = match copy_whole(file, destination, size, context).await? {
+        Some(temporary) => temporary,
+        None => copy_in_chunks(file, destination, context).await?,
+    };
+
+    temporary.persist(destination, flags).await
+}
+
+/// Copy a regular file with gio, which can use copy-on-write and other fast paths that aren't
+/// available when copying in chunks. Returns `None` if the transfer is paused before the copy
+/// finishes, in which case the partial copy is discarded.
+async fn copy_whole(
+    file: &gio::File,
+    destination: &gio::File,
+    size: i64,
+    context: &CopyContext,
+) -> Result<Option<TemporaryFile>, glib::Error> {
+    let temporary = TemporaryFile::next_to(destination).await;
+
+    let (copy, progress) = file.copy_future(
+        temporary.file(),
+        gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+        glib::Priority::DEFAULT,
+    );
+
+    let copied = Cell::new(0);
+
+    let copy = future::join(
+        copy,
+        progress.for_each(|(current, _)| {
+            context.add_copied(current - copied.replace(current));
+            future::ready(())
+        }),
+    );
+
+    match future::select(pin!(copy), pin!(context.handle.wait_until_paused())).await {
+        Either::Left(((res, ()), _)) => {
+            res?;
+            context.add_copied(size - copied.get());
+            Ok(Some(temporary))
+        }
+        Either::Right(((), _)) => {
+            info!("interrupting copy of {} to pause", file.uri());
+            context.add_copied(-copied.get());
+            Ok(None)
+        }
+    }
+}
+
+/// Copy the contents of a regular file in chunks, so that the copy can be paused in between.
+async fn copy_in_chunks(
+    file: &gio::File,
+    destination: &gio::File,
+    context: &CopyContext,
+) -> Result<TemporaryFile, glib::Error> {
+    let temporary = TemporaryFile::next_to(destination).await;
+
+    let input = file.read_future(glib::Priority::DEFAULT).await?;
+
+    let output = temporary
+        .file()
+        .create_future(gio::FileCreateFlags::NONE, glib::Priority::DEFAULT)
+        .await?;
+
+    loop {
+        context.handle.wait_while_paused().await;
+
+        let chunk = input
+            .read_bytes_future(COPY_CHUNK_SIZE, glib::Priority::DEFAULT)
+            .await?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        let len = chunk.len() as i64;
+
+        match output
+            .write_all_future(chunk, glib::Priority::DEFAULT)
+            .await
+        {
+            Ok((_, _, Some(err))) | Err((_, err)) => return Err(err),
+            Ok(_) => (),
+        }
+
+        context.add_copied(len);
+    }
+
+    output.close_future(glib::Priority::DEFAULT).await?;
+
+    // There is no asynchronous version of copying attributes, so it's done on another thread.
+    let (file, output_file) = (file.clone(), temporary.file().clone());
+    gio::spawn_blocking(move || {
+        file.copy_attributes(
+            &output_file,
+            gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+            gio::Cancellable::NONE,
+        )
+    })
+    .await
+    .expect("copying attributes panicked")?;
+
+    Ok(temporary)
+}
+
+/// Returns the names of the children of a directory.
+async fn child_names(dir: &gio::File) -> Result<Vec<PathBuf>, glib::Error> {
+    let enumerator = dir
+        .enumerate_children_future(
+            gio::FILE_ATTRIBUTE_STANDARD_NAME,
+            gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    let mut names = vec![];
+
+    loop {
+        let children = enumerator
+            .next_files_future(ENUMERATE_BATCH_SIZE, glib::Priority::DEFAULT)
+            .await?;
+
+        if children.is_empty() {
+            break;
+        }
+
+        names.extend(children.iter().map(|info| info.name()));
+    }
+
+    Ok(names)
+}
+
+/// Delete a file. Directories are deleted recursively.
+fn delete_recursive(file: gio::File) -> LocalBoxFuture<'static, Result<(), glib::Error>> {
+    async move {
+        if is_directory(&file).await? {
+            for name in child_names(&file).await? {
+                delete_recursive(file.child(name)).await?;
+            }
+        }
+
+        file.delete_future(glib::Priority::DEFAULT).await
+    }
+    .boxed_local()
+}
+
+/// Permanently delete files. Directories are deleted recursively.
+pub async fn delete(files: Vec<gio::File>, sender: Sender<AppMsg>) {
+    info!(
+        "deleting files: {:?}",
+        files.iter().map(|file| file.uri()).collect::<Vec<_>>()
+    );
+
+    let description = match &files[..] {
+        [file] => {
+            let display_name = file
+                .query_info_future(
+                    gio::FILE_ATTRIBUTE_STANDARD_DISPLAY_NAME,
+                    gio::FileQueryInfoFlags::NONE,
+                    glib::Priority::DEFAULT,
+                )
+                .await
+                .map_or_else(|_| "file".into(), |info| String::from(info.display_name()));
+
+            format!("Deleting '{}'", display_name)
+        }
+        _ => format!("Deleting {} items", files.len()),
+    };
+
+    let (id, handle) = new_transfer(description, true, ProgressUnit::Items, &sender);
+
+    let mut total = 0;
+    for file in &files {
+        total += count_items(file).await;
+    }
+
+    let context = DeleteContext {
+        id,
+        deleted: Rc::new(Cell::new(0)),
+        total,
+        handle: handle.clone(),
+        sender: sender.clone(),
+    };
+
+    let deletion = async move {
+        for file in files {
+            delete_with_progress(file, context.clone()).await?;
+        }
+
+        Ok(())
+    };
+
+    run_transfer(id, handle, deletion, &sender).await;
+}
+
+/// State shared by all files deleted as part of a recursive delete.
+#[derive(Clone)]
+struct DeleteContext {
+    id: u64,
+
+    /// The number of files and directories deleted so far.
+    deleted: Rc<Cell<i64>>,
+
+    total: i64,
+    handle: TransferHandle,
+    sender: Sender<AppMsg>,
+}
+
+/// Delete a file, recursing into directories and reporting progress for each deleted item.
+fn delete_with_progress(
+    file: gio::File,
+    context: DeleteContext,
+) -> LocalBoxFuture<'static, Result<(), glib::Error>> {
+    async move {
+        context.handle.wait_while_paused().await;
+
+        // Items in the trash can only be deleted as a whole, and the trash takes care of their
+        // contents.
+        if !file.has_uri_scheme("trash") && is_directory(&file).await? {
+            for name in child_names(&file).await? {
+                delete_with_progress(file.child(name), context.clone()).await?;
+            }
+        }
+
+        file.delete_future(glib::Priority::DEFAULT).await?;
+
+        context.deleted.set(context.deleted.get() + 1);
+
+        let _ = context
+            .sender
+            .send(AppMsg::Transfer(Transfer::Progress(Progress {
+                id: context.id,
+                current: context.deleted.get().min(context.total),
+                total: context.total,
+            })));
+
+        Ok(())
+    }
+    .boxed_local()
+}
+
+/// Returns the number of files and directories that deleting a file would remove.
+async fn count_items(file: &gio::File) -> i64 {
+    if file.has_uri_scheme("trash") {
+        return 1;
+    }
+
+    let (usage, _) =
+        file.measure_disk_usage_future(gio::FileMeasureFlags::NONE, glib::Priority::DEFAULT);
+
+    match usage.await {
+        Ok((_, num_dirs, num_files)) => (num_dirs + num_files) as i64,
+        Err(e) => {
+            warn!("unable to count items in {}: {}", file.uri(), e);
+            1
+        }
+    }
+}
+
+/// Returns the items in the trash.
+pub async fn trash_contents() -> Result<Vec<gio::File>, glib::Error> {
+    let trash = gio::File::for_uri("trash:///");
+
+    Ok(child_names(&trash)
+        .await?
+        .into_iter()
+        .map(|name| trash.child(name))
+        .collect())
+}
+
+/// Create a symbolic link at the destination that points to a file. Returns whether the link was
+/// created.
+pub async fn link(
+    file: gio::File,
+    destination: gio::File,
+    batch: Batch,
+    sender: Sender<AppMsg>,
+) -> bool {
+    info!("linking {} to {}", destination.uri(), file.uri());
+
+    let Some(target) = file.path() else {
+        let err = glib::Error::new(
+            gio::IOErrorEnum::NotSupported,
+            &format!("Cannot create a link to '{}'", file.uri()),
+        );
         let _ = sender.send(AppMsg::Error(Box::new(err)));
+        return false;
+    };
+
+    let (destination, flags) = match resolve_conflict(&file, destination, false, &batch).await {
+        Ok(Target::File(destination, flags)) => (destination, flags),
+        Ok(Target::Replace {
+            temporary,
+            destination,
+        }) => {
+            let res = async {
+                temporary
+                    .file()
+                    .make_symbolic_link_future(target, glib::Priority::DEFAULT)
+                    .await?;
+                temporary.replace(&destination).await
+            };
+
+            return match res.await {
+                Ok(()) => true,
+                Err(err) => {
+                    let _ = sender.send(AppMsg::Error(Box::new(err)));
+                    false
+                }
+            };
+        }
+        Ok(_) => return false,
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+            return false;
+        }
+    };
+
+    if flags.contains(gio::FileCopyFlags::OVERWRITE) {
+        match destination.delete_future(glib::Priority::DEFAULT).await {
+            Err(err) if !err.matches(gio::IOErrorEnum::NotFound) => {
+                let _ = sender.send(AppMsg::Error(Box::new(err)));
+                return false;
+            }
+            _ => (),
+        }
+    }
+
+    match destination
+        .make_symbolic_link_future(target, glib::Priority::DEFAULT)
+        .await
+    {
+        Ok(()) => true,
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+            false
+        }
+    }
+}
+
+/// Returns the action that should be performed when files are dropped while the given modifiers
+/// are held.
+///
+/// This follows the conventions of other file managers: Ctrl copies, Shift moves, and Ctrl+Shift
+/// creates a link. Drops without modifiers move.
+pub fn drop_action_for_modifiers(modifiers: gdk::ModifierType) -> gdk::DragAction {
+    let control = modifiers.contains(gdk::ModifierType::CONTROL_MASK);
+    let shift = modifiers.contains(gdk::ModifierType::SHIFT_MASK);
+
+    match (control, shift) {
+        (true, true) => gdk::DragAction::LINK,
+        (true, false) => gdk::DragAction::COPY,
+        _ => gdk::DragAction::MOVE,
+    }
+}
+
+/// Returns the files contained in a dropped value, which may either be a single [`gio::File`] or
+/// a [`gdk::FileList`].
+pub fn dropped_files(value: &glib::Value) -> Vec<gio::File> {
+    if let Ok(file_list) = value.get::<gdk::FileList>() {
+        file_list.files()
+    } else {
+        vec![value.get::<gio::File>().unwrap()]
+    }
+}
+
+/// Move, copy, or link dropped files into the destination directory, depending on the action.
+pub fn handle_drop(
+    value: &glib::Value,
+    destination: &gio::File,
+    action: gdk::DragAction,
+    sender: Sender<AppMsg>,
+) {
+    let transfer = transfer_files(dropped_files(value), destination.clone(), action, sender);
+
+    relm4::spawn_local(async move {
+        transfer.await;
+    });
+}
+
+/// Returns whether a file is the directory itself or somewhere beneath it.
+fn is_within(file: &gio::File, dir: &gio::File) -> bool {
+    file.equal(dir) || file.has_prefix(dir)
+}
+
+/// Move, copy, or link files into the destination directory, depending on the action.
+///
+/// The files are transferred one after another, so that conflicts are presented to the user in
+/// order. Copying a file into its own directory creates a numbered copy; moving or linking it
+/// there does nothing.
+///
+/// Returns whether every file was transferred.
+pub async fn transfer_files(
+    files: Vec<gio::File>,
+    destination: gio::File,
+    action: gdk::DragAction,
+    sender: Sender<AppMsg>,
+) -> bool {
+    let batch = Batch::new(files.len());
+    let mut transferred_all = true;
+
+    for file in files {
+        // A directory copied into itself would be copied again from within the copy, forever.
+        if action == gdk::DragAction::COPY && is_within(&destination, &file) {
+            let err = glib::Error::new(
+                gio::IOErrorEnum::WouldRecurse,
+                "Can't copy a folder into itself",
+            );
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+            transferred_all = false;
+            continue;
+        }
+
+        let mut destination_file = destination.child(file.basename().unwrap());
+
+        if destination_file.equal(&file) {
+            if action != gdk::DragAction::COPY {
+                transferred_all = false;
+                continue;
+            }
+
+            destination_file = unused_name(&destination_file).await;
+        }
+
+        let batch = batch.clone();
+        let sender = sender.clone();
+
+        let transferred = if action == gdk::DragAction::COPY {
+            copy(file, destination_file, batch, sender).await
+        } else if action == gdk::DragAction::LINK {
+            link(file, destination_file, batch, sender).await
+        } else {
+            move_(file, destination_file, batch, sender).await
+        };
+
+        transferred_all &= transferred;
     }
+
+    transferred_all
 }
 
-/// Move a dropped file into the destination directory.
-pub fn handle_drop(value: &glib::Value, destination: &gio::File, sender: Sender<AppMsg>) {
-    let file = value.get::<gio::File>().unwrap();
+#[cfg(test)]
+mod tests {
+    use relm4::gtk::gio;
+
+    use super::is_within;
 
-    let destination_file = destination.child(file.basename().unwrap());
+    #[test]
+    fn folder_is_within_itself_and_its_ancestors() {
+        let dir = gio::File::for_path("/a");
 
-    if destination_file.equal(&file) {
-        return;
+        assert!(is_within(&dir, &dir));
+        assert!(is_within(&gio::File::for_path("/a/sub"), &dir));
+        assert!(is_within(&gio::File::for_path("/a/sub/deeper"), &dir));
     }
 
-    relm4::spawn_local(move_(file, destination_file, sender));
+    #[test]
+    fn folder_is_not_within_siblings_or_descendants() {
+        let dir = gio::File::for_path("/a/sub");
+
+        assert!(!is_within(&gio::File::for_path("/a"), &dir));
+        assert!(!is_within(&gio::File::for_path("/a/sibling"), &dir));
+        assert!(!is_within(
+            &gio::File::for_path("/ab"),
+            &gio::File::for_path("/a")
+        ));
+    }
 }
@@ -0,0 +1,145 @@
+//! Cutting, copying, and pasting files through the system clipboard.
+//!
+//! Files are offered both as a `text/uri-list` and in the `x-special/gnome-copied-files` format
+//! used by Nautilus and other GNOME applications, which also records whether the files were cut
+//! or copied.
+
+use gtk::{gdk, gio, glib, prelude::*};
+use relm4::{gtk, Sender};
+use tracing::*;
+
+use crate::component::app::AppMsg;
+
+/// MIME type of the GNOME clipboard format, which marks the files as cut or copied.
+const GNOME_COPIED_FILES_MIME_TYPE: &str = "x-special/gnome-copied-files";
+
+/// MIME type of a plain list of URIs, as described by RFC 2483.
+const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+
+/// Place files on the clipboard. If `cut` is set, pasting the files will move them instead of
+/// copying them.
+pub fn set_files(clipboard: &gdk::Clipboard, files: &[gio::File], cut: bool) {
+    let uris = files.iter().map(|file| file.uri()).collect::<Vec<_>>();
+
+    info!(
+        "{} files to clipboard: {:?}",
+        if cut { "cutting" } else { "copying" },
+        uris
+    );
+
+    let gnome_copied_files = format!("{}\n{}", if cut { "cut" } else { "copy" }, uris.join("\n"));
+
+    let uri_list = uris
+        .iter()
+        .map(|uri| format!("{}\r\n", uri))
+        .collect::<String>();
+
+    let provider = gdk::ContentProvider::new_union(&[
+        gdk::ContentProvider::for_bytes(
+            GNOME_COPIED_FILES_MIME_TYPE,
+            &glib::Bytes::from_owned(gnome_copied_files.into_bytes()),
+        ),
+        gdk::ContentProvider::for_bytes(
+            URI_LIST_MIME_TYPE,
+            &glib::Bytes::from_owned(uri_list.into_bytes()),
+        ),
+    ]);
+
+    if let Err(err) = clipboard.set_content(Some(&provider)) {
+        warn!("unable to set clipboard content: {}", err);
+    }
+}
+
+/// Paste the files on the clipboard into the destination directory.
+///
+/// Cut files are moved and the clipboard is cleared once all of them have been moved, so that they
+/// can't be pasted twice. Copied files are copied.
+pub async fn paste(clipboard: gdk::Clipboard, destination: gio::File, sender: Sender<AppMsg>) {
+    let (files, cut) = match read_files(&clipboard).await {
+        Ok(Some(contents)) => contents,
+        Ok(None) => return,
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+            return;
+        }
+    };
+
+    info!(
+        "pasting files into {}: {:?}",
+        destination.uri(),
+        files.iter().map(|file| file.uri()).collect::<Vec<_>>()
+    );
+
+    let action = if cut {
+        gdk::DragAction::MOVE
+    } else {
+        gdk::DragAction::COPY
+    };
+
+    let transferred_all = super::transfer_files(files, destination, action, sender).await;
+
+    if cut && transferred_all {
+        if let Err(err) = clipboard.set_content(None::<&gdk::ContentProvider>) {
+            warn!("unable to clear clipboard: {}", err);
+        }
+    }
+}
+
+/// Returns whether the clipboard holds files that can be pasted.
+pub fn has_files(clipboard: &gdk::Clipboard) -> bool {
+    let formats = clipboard.formats();
+
+    formats.contain_mime_type(GNOME_COPIED_FILES_MIME_TYPE)
+        || formats.contain_mime_type(URI_LIST_MIME_TYPE)
+}
+
+/// Read the files on the clipboard, and whether they were cut. Returns `None` if the clipboard
+/// doesn't contain any files.
+async fn read_files(
+    clipboard: &gdk::Clipboard,
+) -> Result<Option<(Vec<gio::File>, bool)>, glib::Error> {
+    if !has_files(clipboard) {
+        return Ok(None);
+    }
+
+    let (stream, mime_type) = clipboard
+        .read_future(
+            &[GNOME_COPIED_FILES_MIME_TYPE, URI_LIST_MIME_TYPE],
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    let output = gio::MemoryOutputStream::new_resizable();
+    output
+        .splice_future(
+            &stream,
+            gio::OutputStreamSpliceFlags::CLOSE_SOURCE | gio::OutputStreamSpliceFlags::CLOSE_TARGET,
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    let bytes = output.steal_as_bytes();
+    let contents = String::from_utf8_lossy(&bytes);
+
+    let mut lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+
+    let cut = if mime_type == GNOME_COPIED_FILES_MIME_TYPE {
+        lines.next() == Some("cut")
+    } else {
+        false
+    };
+
+    let files = lines
+        .filter(|line| !line.starts_with('#'))
+        .map(gio::File::for_uri)
+        .collect::<Vec<_>>();
+
+    Ok(if files.is_empty() {
+        None
+    } else {
+        Some((files, cut))
+    })
+}
@@ -0,0 +1,264 @@
+//! Journal of completed file operations, used to undo and redo them.
+
+use std::cell::{Cell, RefCell};
+
+use educe::Educe;
+use futures::prelude::*;
+use gtk::{gio, glib, prelude::*};
+use relm4::{gtk, Sender};
+use tracing::*;
+
+use crate::component::app::AppMsg;
+use crate::util;
+
+/// The maximum number of operations that can be undone.
+const MAX_UNDO_OPERATIONS: usize = 100;
+
+/// A completed file operation, with enough information to invert it.
+#[derive(Educe)]
+#[educe(Debug)]
+pub enum Operation {
+    /// A file was moved to a new location.
+    Move {
+        #[educe(Debug(method = "util::fmt_file_as_uri"))]
+        from: gio::File,
+        #[educe(Debug(method = "util::fmt_file_as_uri"))]
+        to: gio::File,
+    },
+
+    /// A file was renamed.
+    Rename {
+        #[educe(Debug(method = "util::fmt_file_as_uri"))]
+        from: gio::File,
+        #[educe(Debug(method = "util::fmt_file_as_uri"))]
+        to: gio::File,
+    },
+
+    /// Files were moved to the trash. The files refer to their original locations.
+    Trash(#[educe(Debug(method = "util::fmt_files_as_uris"))] Vec<gio::File>),
+
+    /// A new folder was created.
+    CreateFolder(#[educe(Debug(method = "util::fmt_file_as_uri"))] gio::File),
+
+    /// An empty folder was removed.
+    RemoveFolder(#[educe(Debug(method = "util::fmt_file_as_uri"))] gio::File),
+
+    /// A directory was merged into another directory. The operations are the moves of its
+    /// children and the removal of the emptied directories, in the order that they happened.
+    Merge(Vec<Operation>),
+}
+
+impl Operation {
+    /// Revert the effects of the operation.
+    async fn undo(&self) -> Result<(), glib::Error> {
+        match self {
+            Operation::Move { from, to } | Operation::Rename { from, to } => {
+                move_file(to, from).await
+            }
+            Operation::Trash(files) => {
+                for file in files {
+                    restore_from_trash(file).await?;
+                }
+
+                Ok(())
+            }
+            Operation::CreateFolder(dir) => dir.delete_future(glib::Priority::DEFAULT).await,
+            Operation::RemoveFolder(dir) => {
+                dir.make_directory_future(glib::Priority::DEFAULT).await
+            }
+            Operation::Merge(operations) => {
+                for operation in operations.iter().rev() {
+                    Box::pin(operation.undo()).await?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Perform the operation again after it was undone.
+    async fn redo(&self) -> Result<(), glib::Error> {
+        match self {
+            Operation::Move { from, to } | Operation::Rename { from, to } => {
+                move_file(from, to).await
+            }
+            Operation::Trash(files) => {
+                for file in files {
+                    file.trash_future(glib::Priority::DEFAULT).await?;
+                }
+
+                Ok(())
+            }
+            Operation::CreateFolder(dir) => {
+                dir.make_directory_future(glib::Priority::DEFAULT).await
+            }
+            Operation::RemoveFolder(dir) => dir.delete_future(glib::Priority::DEFAULT).await,
+            Operation::Merge(operations) => {
+                for operation in operations {
+                    Box::pin(operation.redo()).await?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Identifies a recorded operation, so that it can be undone even if other operations have been
+/// recorded since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, glib::Variant)]
+pub struct OperationId(u64);
+
+#[derive(Debug)]
+struct Entry {
+    id: OperationId,
+    operation: Operation,
+}
+
+#[derive(Debug, Default)]
+struct Journal {
+    /// Operations that can be undone, most recent last.
+    undo: Vec<Entry>,
+
+    /// Operations that were undone and can be redone, most recently undone last.
+    redo: Vec<Entry>,
+}
+
+thread_local! {
+    static JOURNAL: RefCell<Journal> = RefCell::default();
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Record a completed operation so that it can be undone. Any undone operations can no longer be
+/// redone.
+pub fn record(operation: Operation) -> OperationId {
+    info!("recording operation: {:?}", operation);
+
+    let id = OperationId(NEXT_ID.with(|next_id| next_id.replace(next_id.get() + 1)));
+
+    JOURNAL.with(|journal| {
+        let mut journal = journal.borrow_mut();
+
+        journal.redo.clear();
+        journal.undo.push(Entry { id, operation });
+
+        if journal.undo.len() > MAX_UNDO_OPERATIONS {
+            journal.undo.remove(0);
+        }
+    });
+
+    id
+}
+
+/// Undo the most recent operation, if any.
+pub async fn undo(sender: Sender<AppMsg>) {
+    let entry = JOURNAL.with(|journal| journal.borrow_mut().undo.pop());
+    undo_entry(entry, sender).await;
+}
+
+/// Undo a specific operation, unless it has already been undone or is too old to be undone.
+pub async fn undo_operation(id: OperationId, sender: Sender<AppMsg>) {
+    let entry = JOURNAL.with(|journal| {
+        let mut journal = journal.borrow_mut();
+        let index = journal.undo.iter().position(|entry| entry.id == id)?;
+        Some(journal.undo.remove(index))
+    });
+
+    undo_entry(entry, sender).await;
+}
+
+async fn undo_entry(entry: Option<Entry>, sender: Sender<AppMsg>) {
+    let Some(entry) = entry else {
+        return;
+    };
+
+    info!("undoing operation: {:?}", entry.operation);
+
+    match entry.operation.undo().await {
+        Ok(()) => JOURNAL.with(|journal| journal.borrow_mut().redo.push(entry)),
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+        }
+    }
+}
+
+/// Redo the most recently undone operation, if any.
+pub async fn redo(sender: Sender<AppMsg>) {
+    let Some(entry) = JOURNAL.with(|journal| journal.borrow_mut().redo.pop()) else {
+        return;
+    };
+
+    info!("redoing operation: {:?}", entry.operation);
+
+    match entry.operation.redo().await {
+        Ok(()) => JOURNAL.with(|journal| journal.borrow_mut().undo.push(entry)),
+        Err(err) => {
+            let _ = sender.send(AppMsg::Error(Box::new(err)));
+        }
+    }
+}
+
+/// Move a file without any user interaction. Fails if the destination exists.
+async fn move_file(from: &gio::File, to: &gio::File) -> Result<(), glib::Error> {
+    let (res, _) = from.move_future(to, gio::FileCopyFlags::NONE, glib::Priority::DEFAULT);
+    res.await
+}
+
+/// Restore the most recently trashed file that was originally at the given location.
+async fn restore_from_trash(original: &gio::File) -> Result<(), glib::Error> {
+    let trash = gio::File::for_uri("trash:///");
+
+    let enumerator = trash
+        .enumerate_children_future(
+            &[
+                &**gio::FILE_ATTRIBUTE_STANDARD_NAME,
+                &**gio::FILE_ATTRIBUTE_TRASH_ORIG_PATH,
+                &**gio::FILE_ATTRIBUTE_TRASH_DELETION_DATE,
+            ]
+            .join(","),
+            gio::FileQueryInfoFlags::NONE,
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    let mut trashed = None;
+
+    loop {
+        let infos = enumerator
+            .next_files_future(super::ENUMERATE_BATCH_SIZE, glib::Priority::DEFAULT)
+            .await?;
+
+        if infos.is_empty() {
+            break;
+        }
+
+        for info in infos {
+            let is_original = info
+                .attribute_byte_string(gio::FILE_ATTRIBUTE_TRASH_ORIG_PATH)
+                .map_or(false, |path| {
+                    gio::File::for_parse_name(&path).equal(original)
+                });
+
+            if !is_original {
+                continue;
+            }
+
+            let deletion_date = info.deletion_date();
+
+            if trashed
+                .as_ref()
+                .map_or(true, |(date, _)| deletion_date > *date)
+            {
+                trashed = Some((deletion_date, trash.child(info.name())));
+            }
+        }
+    }
+
+    match trashed {
+        Some((_, trashed)) => move_file(&trashed, original).await,
+        None => Err(glib::Error::new(
+            gio::IOErrorEnum::NotFound,
+            &format!("'{}' is no longer in the trash", original.parse_name()),
+        )),
+    }
+}
@@ -0,0 +1,271 @@
+//! Registry of the actions that have keyboard shortcuts.
+//!
+//! Every shortcut has a default accelerator, which can be overridden in the configuration file by
+//! the detailed name of its action. Window actions are bound through the application, so that the
+//! bindings apply wherever the window has focus. Directory listing actions are bound by each
+//! listing instead, so that they apply to the listing with focus and don't interfere with text
+//! entry elsewhere in the window.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use relm4::gtk::{self, glib, prelude::*};
+
+use crate::config::Config;
+
+/// An action that can be triggered by a keyboard shortcut.
+#[derive(Debug)]
+pub struct Shortcut {
+    pub action: Action,
+
+    /// A human-readable description of the action.
+    pub title: &'static str,
+
+    /// The default accelerators, in the format understood by [`gtk::accelerator_parse`].
+    pub accels: &'static [&'static str],
+}
+
+/// The action of a shortcut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// A window action, identified by its detailed name, such as `win.new-tab`.
+    Window(&'static str),
+
+    /// An action of the directory listing with focus.
+    Directory(DirectoryAction),
+}
+
+impl Action {
+    /// Returns the detailed name of the action, by which its accelerators are configured.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Window(name) => name,
+            Action::Directory(action) => action.name(),
+        }
+    }
+}
+
+/// The actions of a directory listing that have keyboard shortcuts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectoryAction {
+    OpenChooser,
+    NewFolder,
+    Rename,
+    CutSelection,
+    CopySelection,
+    Paste,
+    TrashSelection,
+    RestoreSelectionFromTrash,
+    DeleteSelection,
+}
+
+impl DirectoryAction {
+    /// Returns the detailed name of the action.
+    pub fn name(self) -> &'static str {
+        match self {
+            DirectoryAction::OpenChooser => "directory-list.open-chooser",
+            DirectoryAction::NewFolder => "directory-list.new-folder",
+            DirectoryAction::Rename => "directory-list.rename",
+            DirectoryAction::CutSelection => "directory-list.cut-selection",
+            DirectoryAction::CopySelection => "directory-list.copy-selection",
+            DirectoryAction::Paste => "directory-list.paste",
+            DirectoryAction::TrashSelection => "directory-list.trash-selection",
+            DirectoryAction::RestoreSelectionFromTrash => {
+                "directory-list.restore-selection-from-trash"
+            }
+            DirectoryAction::DeleteSelection => "directory-list.delete-selection",
+        }
+    }
+}
+
+/// A group of related shortcuts, as displayed in the shortcuts window.
+#[derive(Debug)]
+pub struct ShortcutGroup {
+    pub title: &'static str,
+    pub shortcuts: &'static [Shortcut],
+}
+
+macro_rules! shortcut {
+    (DirectoryAction::$action:ident, $title:literal, [$($accel:literal),*]) => {
+        Shortcut {
+            action: Action::Directory(DirectoryAction::$action),
+            title: $title,
+            accels: &[$($accel),*],
+        }
+    };
+    ($action:literal, $title:literal, [$($accel:literal),*]) => {
+        Shortcut {
+            action: Action::Window($action),
+            title: $title,
+            accels: &[$($accel),*],
+        }
+    };
+}
+
+/// The window actions, bound through the application.
+pub const WINDOW: &[ShortcutGroup] = &[
+    ShortcutGroup {
+        title: "Windows and Tabs",
+        shortcuts: &[
+            shortcut!("win.new-window", "New window", ["<Control>n"]),
+            shortcut!("win.new-tab", "New tab", ["<Control>t"]),
+            shortcut!("win.close-tab", "Close tab", ["<Control>w"]),
+            shortcut!("win.toggle-sidebar", "Show or hide the sidebar", ["F9"]),
+        ],
+    },
+    ShortcutGroup {
+        title: "Navigation",
+        shortcuts: &[
+            shortcut!("win.back", "Go back", ["<Alt>Left"]),
+            shortcut!("win.forward", "Go forward", ["<Alt>Right"]),
+            shortcut!("win.location", "Enter a location", ["<Control>l"]),
+            shortcut!("win.search", "Search", ["<Control>f"]),
+            shortcut!("win.show-hidden", "Show hidden files", ["<Control>h"]),
+            shortcut!("win.view-mode::columns", "View as columns", ["<Control>1"]),
+            shortcut!("win.view-mode::details", "View as details", ["<Control>2"]),
+            shortcut!("win.view-mode::icons", "View as icons", ["<Control>3"]),
+        ],
+    },
+    ShortcutGroup {
+        title: "Editing",
+        shortcuts: &[
+            shortcut!("win.undo", "Undo", ["<Control>z"]),
+            shortcut!("win.redo", "Redo", ["<Control><Shift>z"]),
+        ],
+    },
+    ShortcutGroup {
+        title: "General",
+        shortcuts: &[
+            shortcut!("win.mount", "Connect to server", ["<Control><Shift>c"]),
+            shortcut!("win.preferences", "Preferences", ["<Control>comma"]),
+            shortcut!(
+                "win.show-shortcuts",
+                "Keyboard shortcuts",
+                ["<Control>question"]
+            ),
+            shortcut!("win.about", "About", ["F1"]),
+        ],
+    },
+];
+
+/// The actions of a directory listing, bound by each listing.
+pub const DIRECTORY: &[ShortcutGroup] = &[ShortcutGroup {
+    title: "Files",
+    shortcuts: &[
+        shortcut!(
+            DirectoryAction::OpenChooser,
+            "Open with...",
+            ["<Control><Shift>o"]
+        ),
+        shortcut!(
+            DirectoryAction::NewFolder,
+            "New folder",
+            ["<Control><Shift>n"]
+        ),
+        shortcut!(DirectoryAction::Rename, "Rename", ["F2"]),
+        shortcut!(DirectoryAction::CutSelection, "Cut", ["<Control>x"]),
+        shortcut!(DirectoryAction::CopySelection, "Copy", ["<Control>c"]),
+        shortcut!(DirectoryAction::Paste, "Paste", ["<Control>v"]),
+        shortcut!(DirectoryAction::TrashSelection, "Move to trash", ["Delete"]),
+        shortcut!(
+            DirectoryAction::RestoreSelectionFromTrash,
+            "Restore from trash",
+            ["<Control><Shift>r"]
+        ),
+        shortcut!(
+            DirectoryAction::DeleteSelection,
+            "Delete permanently",
+            ["<Shift>Delete"]
+        ),
+    ],
+}];
+
+/// Returns every shortcut, in the order they're displayed.
+pub fn all() -> impl Iterator<Item = &'static Shortcut> {
+    WINDOW
+        .iter()
+        .chain(DIRECTORY)
+        .flat_map(|group| group.shortcuts)
+}
+
+/// Returns the accelerators of an action, taking overrides from the configuration into account.
+pub fn accels(shortcut: &Shortcut, config: &Config) -> Vec<String> {
+    match config.shortcuts.get(shortcut.action.name()) {
+        Some(accels) => accels.clone(),
+        None => shortcut
+            .accels
+            .iter()
+            .map(|&accel| String::from(accel))
+            .collect(),
+    }
+}
+
+/// Returns an error if an override names an unknown action or an invalid accelerator.
+pub fn validate(overrides: &HashMap<String, Vec<String>>) -> Result<()> {
+    for (action, accels) in overrides {
+        if !all().any(|shortcut| shortcut.action.name() == action) {
+            return Err(anyhow!("unknown action for shortcut: {}", action));
+        }
+
+        for accel in accels {
+            if gtk::accelerator_parse(accel).is_none() {
+                return Err(anyhow!("invalid accelerator for {}: {:?}", action, accel));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Bind the accelerators of the window actions through the application.
+pub fn apply(config: &Config) {
+    let app = relm4::main_application();
+
+    for shortcut in WINDOW.iter().flat_map(|group| group.shortcuts) {
+        let accels = accels(shortcut, config);
+        app.set_accels_for_action(
+            shortcut.action.name(),
+            &accels.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+    }
+}
+
+/// Build a window listing every shortcut with its effective accelerators.
+///
+/// The window is built from a UI definition, because groups can't be added to a shortcuts section
+/// programmatically in the supported GTK versions.
+pub fn window(config: &Config) -> gtk::ShortcutsWindow {
+    let mut ui = String::from(
+        r#"<interface><object class="GtkShortcutsWindow" id="window"><property name="modal">1</property><child><object class="GtkShortcutsSection"><property name="section-name">shortcuts</property>"#,
+    );
+
+    for group in WINDOW.iter().chain(DIRECTORY) {
+        ui.push_str(&format!(
+            r#"<child><object class="GtkShortcutsGroup"><property name="title">{}</property>"#,
+            glib::markup_escape_text(group.title),
+        ));
+
+        for shortcut in group.shortcuts {
+            let accels = accels(shortcut, config);
+
+            // Shortcuts that have been unbound aren't listed.
+            if accels.is_empty() {
+                continue;
+            }
+
+            ui.push_str(&format!(
+                r#"<child><object class="GtkShortcutsShortcut"><property name="title">{}</property><property name="accelerator">{}</property></object></child>"#,
+                glib::markup_escape_text(shortcut.title),
+                glib::markup_escape_text(&accels.join(" ")),
+            ));
+        }
+
+        ui.push_str("</object></child>");
+    }
+
+    ui.push_str("</object></child></object></interface>");
+
+    gtk::Builder::from_string(&ui)
+        .object("window")
+        .expect("shortcuts window must be defined")
+}
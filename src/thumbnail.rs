@@ -0,0 +1,439 @@
+//! Thumbnails of files, shared with other applications through the freedesktop thumbnail cache.
+//!
+//! Thumbnails that already exist are found by GIO, through the `thumbnail::*` file attributes.
+//! Missing thumbnails of images and PDF documents are generated by a small pool of worker threads,
+//! and saved to the cache as described by the [Thumbnail Managing Standard]. Files whose thumbnails
+//! can't be generated are recorded in the cache as well, so that they aren't attempted again until
+//! they're modified.
+//!
+//! [Thumbnail Managing Standard]: https://specifications.freedesktop.org/thumbnail-spec/latest/
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result};
+use futures::channel::oneshot;
+use futures::future::{LocalBoxFuture, Shared, WeakShared};
+use futures::prelude::*;
+use once_cell::sync::Lazy;
+use relm4::gtk::{cairo, gdk, gdk_pixbuf, gio, glib, prelude::*};
+use tracing::*;
+
+/// The size of the thumbnails in the `normal` directory of the cache, in pixels.
+const NORMAL_SIZE: i32 = 128;
+
+/// The number of thumbnails that are generated at the same time.
+const WORKERS: usize = 2;
+
+/// The number of thumbnails that may wait to be generated. Beyond that, the oldest requests are
+/// dropped, since their files have most likely been scrolled out of view.
+const MAX_QUEUED: usize = 256;
+
+/// The name of the directory of the cache that records the files whose thumbnails this
+/// application couldn't generate.
+const FAIL_DIR_NAME: &str = "fm";
+
+/// The mime types of the images that can be loaded.
+static IMAGE_TYPES: Lazy<Vec<String>> = Lazy::new(|| {
+    gdk_pixbuf::Pixbuf::formats()
+        .iter()
+        .flat_map(|format| format.mime_types())
+        .map(String::from)
+        .collect()
+});
+
+/// Thumbnails waiting to be generated by the worker threads, which are started when the first
+/// thumbnail is requested.
+static QUEUE: Lazy<Queue> = Lazy::new(|| {
+    for i in 0..WORKERS {
+        thread::Builder::new()
+            .name(format!("thumbnailer-{}", i))
+            .spawn(|| loop {
+                let job = QUEUE.pop();
+
+                // Nobody is waiting for the thumbnail anymore.
+                if job.result.is_canceled() {
+                    continue;
+                }
+
+                let result = job.run();
+                let _ = job.result.send(result);
+            })
+            .expect("unable to start thumbnail worker");
+    }
+
+    Queue::default()
+});
+
+/// Jobs waiting for a worker thread. The most recently requested thumbnails are generated first,
+/// since they belong to the files that are most likely in view.
+#[derive(Debug, Default)]
+struct Queue {
+    jobs: Mutex<VecDeque<Job>>,
+    available: Condvar,
+}
+
+impl Queue {
+    fn push(&self, job: Job) {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.push_back(job);
+
+        if jobs.len() > MAX_QUEUED {
+            jobs.pop_front();
+        }
+
+        self.available.notify_one();
+    }
+
+    /// Waits for a job to become available, and takes it from the queue.
+    fn pop(&self) -> Job {
+        let mut jobs = self.jobs.lock().unwrap();
+
+        loop {
+            if let Some(job) = jobs.pop_back() {
+                return job;
+            }
+
+            jobs = self.available.wait(jobs).unwrap();
+        }
+    }
+}
+
+type Thumbnail = Shared<LocalBoxFuture<'static, Option<PathBuf>>>;
+
+/// A thumbnail requested during this session.
+#[derive(Debug)]
+enum Request {
+    /// The thumbnail is waiting to be generated. Only a weak reference is kept, so that the job is
+    /// dropped once nobody is waiting for it.
+    Pending(WeakShared<LocalBoxFuture<'static, Option<PathBuf>>>),
+
+    /// The thumbnail was generated, or couldn't be.
+    Done(Option<PathBuf>),
+}
+
+thread_local! {
+    /// Thumbnails requested during this session, by URI and modification time.
+    static REQUESTED: RefCell<HashMap<(String, u64), Request>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Image,
+    Pdf,
+}
+
+impl Kind {
+    fn for_content_type(content_type: &str) -> Option<Self> {
+        let mime = gio::content_type_get_mime_type(content_type)?;
+
+        if mime.as_str() == "application/pdf" {
+            Some(Kind::Pdf)
+        } else if IMAGE_TYPES
+            .iter()
+            .any(|image_type| image_type.as_str() == mime.as_str())
+        {
+            Some(Kind::Image)
+        } else {
+            None
+        }
+    }
+}
+
+/// A thumbnail to be generated by a worker thread.
+#[derive(Debug)]
+struct Job {
+    path: PathBuf,
+    uri: String,
+    mtime: u64,
+    kind: Kind,
+
+    /// Receives the path of the thumbnail, or `None` if generating it already failed before.
+    result: oneshot::Sender<Result<Option<PathBuf>>>,
+}
+
+impl Job {
+    fn run(&self) -> Result<Option<PathBuf>> {
+        if has_failed(&self.uri, self.mtime) {
+            debug!(
+                "not generating thumbnail of {}, which failed before",
+                self.uri
+            );
+            return Ok(None);
+        }
+
+        info!("generating thumbnail of {}", self.uri);
+
+        let pixbuf = match self.kind {
+            Kind::Image => image_thumbnail(&self.path),
+            Kind::Pdf => pdf_thumbnail(&self.path),
+        };
+
+        let pixbuf = match pixbuf {
+            Ok(pixbuf) => pixbuf,
+            Err(e) => {
+                if let Err(e) = record_failure(&self.uri, self.mtime) {
+                    warn!("unable to record failed thumbnail of {}: {:#}", self.uri, e);
+                }
+
+                return Err(e);
+            }
+        };
+
+        save(&pixbuf, &cache_dir(), &self.uri, self.mtime).map(Some)
+    }
+}
+
+/// Returns the path of an up-to-date thumbnail of a file in the cache, if there is one.
+///
+/// The file info must have been queried with the `thumbnail::path` and `thumbnail::is-valid`
+/// attributes.
+pub fn cached(info: &gio::FileInfo) -> Option<PathBuf> {
+    if info.has_attribute(gio::FILE_ATTRIBUTE_THUMBNAIL_IS_VALID)
+        && !info.attribute_boolean(gio::FILE_ATTRIBUTE_THUMBNAIL_IS_VALID)
+    {
+        return None;
+    }
+
+    info.attribute_byte_string(gio::FILE_ATTRIBUTE_THUMBNAIL_PATH)
+        .map(PathBuf::from)
+}
+
+/// Generate a thumbnail of a file and save it to the cache, returning the path of the thumbnail.
+///
+/// Returns `None` if the file isn't a local image or PDF document, or if its thumbnail couldn't be
+/// generated. The file info must have been queried with the `standard::content-type` and
+/// `time::modified` attributes.
+pub async fn generate(file: &gio::File, info: &gio::FileInfo) -> Option<PathBuf> {
+    let path = file.path()?;
+    let kind = Kind::for_content_type(&info.content_type()?)?;
+
+    // Thumbnails are never made of other thumbnails.
+    if path.starts_with(cache_dir()) {
+        return None;
+    }
+
+    let uri = file.uri().to_string();
+    let mtime = info.attribute_uint64(gio::FILE_ATTRIBUTE_TIME_MODIFIED);
+
+    let key = (uri.clone(), mtime);
+
+    let thumbnail = REQUESTED.with(|requested| {
+        let mut requested = requested.borrow_mut();
+
+        match requested.get(&key) {
+            Some(Request::Done(thumbnail)) => return Err(thumbnail.clone()),
+            Some(Request::Pending(thumbnail)) => {
+                if let Some(thumbnail) = thumbnail.upgrade() {
+                    return Ok(thumbnail);
+                }
+            }
+            None => (),
+        }
+
+        let (sender, receiver) = oneshot::channel();
+
+        QUEUE.push(Job {
+            path,
+            uri: uri.clone(),
+            mtime,
+            kind,
+            result: sender,
+        });
+
+        let thumbnail: Thumbnail = async move {
+            let thumbnail = match receiver.await {
+                Ok(Ok(thumbnail)) => thumbnail,
+                Ok(Err(e)) => {
+                    warn!("unable to generate thumbnail of {}: {:#}", uri, e);
+                    None
+                }
+                // The job was dropped from the queue, so the thumbnail may be requested again.
+                Err(oneshot::Canceled) => {
+                    REQUESTED.with(|requested| requested.borrow_mut().remove(&(uri, mtime)));
+                    return None;
+                }
+            };
+
+            REQUESTED.with(|requested| {
+                requested
+                    .borrow_mut()
+                    .insert((uri, mtime), Request::Done(thumbnail.clone()))
+            });
+
+            thumbnail
+        }
+        .boxed_local()
+        .shared();
+
+        requested.insert(key, Request::Pending(thumbnail.downgrade().unwrap()));
+
+        Ok(thumbnail)
+    });
+
+    match thumbnail {
+        Ok(thumbnail) => thumbnail.await,
+        Err(thumbnail) => thumbnail,
+    }
+}
+
+/// Returns the directory of the cache that contains thumbnails of the normal size.
+fn cache_dir() -> PathBuf {
+    glib::user_cache_dir().join("thumbnails").join("normal")
+}
+
+/// Returns the directory of the cache that records the files whose thumbnails couldn't be
+/// generated.
+fn fail_dir() -> PathBuf {
+    glib::user_cache_dir()
+        .join("thumbnails")
+        .join("fail")
+        .join(FAIL_DIR_NAME)
+}
+
+/// Returns the name of the thumbnail of a file in each directory of the cache, without its
+/// extension.
+fn thumbnail_name(uri: &str) -> Result<String> {
+    glib::compute_checksum_for_string(glib::ChecksumType::Md5, uri)
+        .map(String::from)
+        .context("unable to hash URI")
+}
+
+/// Returns whether generating a thumbnail of the file already failed since it was last modified.
+fn has_failed(uri: &str, mtime: u64) -> bool {
+    let Ok(name) = thumbnail_name(uri) else {
+        return false;
+    };
+
+    gdk_pixbuf::Pixbuf::from_file(fail_dir().join(format!("{}.png", name))).is_ok_and(|pixbuf| {
+        pixbuf.option("tEXt::Thumb::MTime").as_deref() == Some(mtime.to_string().as_str())
+    })
+}
+
+/// Record that a thumbnail of the file couldn't be generated, as an empty thumbnail in the
+/// directory of failures.
+fn record_failure(uri: &str, mtime: u64) -> Result<()> {
+    let pixbuf = gdk_pixbuf::Pixbuf::new(gdk_pixbuf::Colorspace::Rgb, true, 8, 1, 1)
+        .context("unable to allocate image")?;
+    pixbuf.fill(0);
+
+    save(&pixbuf, &fail_dir(), uri, mtime)?;
+
+    Ok(())
+}
+
+/// Load an image, scaled down to the thumbnail size if it's larger.
+fn image_thumbnail(path: &Path) -> Result<gdk_pixbuf::Pixbuf> {
+    let (_, width, height) =
+        gdk_pixbuf::Pixbuf::file_info(path).context("unrecognized image format")?;
+
+    let pixbuf = if width <= NORMAL_SIZE && height <= NORMAL_SIZE {
+        gdk_pixbuf::Pixbuf::from_file(path)?
+    } else {
+        gdk_pixbuf::Pixbuf::from_file_at_scale(path, NORMAL_SIZE, NORMAL_SIZE, true)?
+    };
+
+    // Photos are thumbnailed the way they're displayed.
+    Ok(pixbuf.apply_embedded_orientation().unwrap_or(pixbuf))
+}
+
+/// Render the first page of a PDF document at the thumbnail size.
+fn pdf_thumbnail(path: &Path) -> Result<gdk_pixbuf::Pixbuf> {
+    let document =
+        poppler::Document::from_gfile(&gio::File::for_path(path), None, gio::Cancellable::NONE)?;
+    let page = document.page(0).context("document has no pages")?;
+
+    let (page_width, page_height) = page.size();
+    let scale = f64::from(NORMAL_SIZE) / page_width.max(page_height);
+    let width = (page_width * scale).round().max(1.0) as i32;
+    let height = (page_height * scale).round().max(1.0) as i32;
+
+    let surface = cairo::ImageSurface::create(cairo::Format::Rgb24, width, height)?;
+
+    {
+        let ctx = cairo::Context::new(&surface)?;
+
+        ctx.set_source_rgb(1.0, 1.0, 1.0);
+        ctx.paint()?;
+        ctx.scale(scale, scale);
+
+        page.render(&ctx);
+    }
+
+    gdk::pixbuf_get_from_surface(&surface, 0, 0, width, height)
+        .context("unable to read rendered page")
+}
+
+/// Save a thumbnail to a directory of the cache, along with the URI and modification time of the
+/// original file so that other applications can tell whether it's up to date.
+fn save(pixbuf: &gdk_pixbuf::Pixbuf, dir: &Path, uri: &str, mtime: u64) -> Result<PathBuf> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+
+    let name = thumbnail_name(uri)?;
+    let thumbnail = dir.join(format!("{}.png", name));
+
+    // The thumbnail is written to a temporary file and then moved into place, so that other
+    // applications never read a partially written thumbnail.
+    let temp = dir.join(format!("{}.fm-{}.png", name, process::id()));
+
+    pixbuf.savev(
+        &temp,
+        "png",
+        &[
+            ("tEXt::Thumb::URI", uri),
+            ("tEXt::Thumb::MTime", &mtime.to_string()),
+            ("tEXt::Software", "fm"),
+        ],
+    )?;
+    fs::set_permissions(&temp, fs::Permissions::from_mode(0o600))?;
+    fs::rename(&temp, &thumbnail)?;
+
+    Ok(thumbnail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(uri: &str) -> Job {
+        Job {
+            path: PathBuf::from(uri),
+            uri: uri.to_owned(),
+            mtime: 0,
+            kind: Kind::Image,
+            result: oneshot::channel().0,
+        }
+    }
+
+    #[test]
+    fn newest_jobs_run_first() {
+        let queue = Queue::default();
+        queue.push(job("a"));
+        queue.push(job("b"));
+
+        assert_eq!(queue.pop().uri, "b");
+        assert_eq!(queue.pop().uri, "a");
+    }
+
+    #[test]
+    fn oldest_jobs_are_dropped() {
+        let queue = Queue::default();
+
+        for i in 0..=MAX_QUEUED {
+            queue.push(job(&i.to_string()));
+        }
+
+        let jobs = queue.jobs.lock().unwrap();
+        assert_eq!(jobs.len(), MAX_QUEUED);
+        assert_eq!(jobs.front().unwrap().uri, "1");
+    }
+}
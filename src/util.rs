@@ -8,8 +8,10 @@ use std::{
 use relm4::gtk::{self, gdk, gio, glib, prelude::*};
 
 mod emblemed_paintable;
+mod natural_width_bin;
 
 use emblemed_paintable::EmblemedPaintable;
+pub use natural_width_bin::NaturalWidthBin;
 
 /// Extension functions for [`Result`]s containing [`GError`](glib::Error)s.
 pub trait GResultExt {
@@ -56,6 +58,17 @@ pub fn icon_for_file(
         )
         .upcast::<gdk::Paintable>();
 
+    with_emblems(theme, size, file_info, icon_paintable)
+}
+
+/// Adds a symlink emblem to the bottom left of the icon or thumbnail of a file, if the file is a
+/// symlink.
+pub fn with_emblems(
+    theme: &gtk::IconTheme,
+    size: i32,
+    file_info: &gio::FileInfo,
+    icon_paintable: gdk::Paintable,
+) -> gdk::Paintable {
     if file_info.is_symlink() && theme.has_icon("emblem-symbolic-link") {
         let emblem = theme
             .lookup_icon(
@@ -74,6 +87,36 @@ pub fn icon_for_file(
     }
 }
 
+/// Returns a short, human-readable name for a location, such as the title of a tab.
+///
+/// This is usually the base name of the file, but the home directory is called "Home", and
+/// locations without a parent (such as `/` or `trash:///`) are named by their full path or URI.
+pub fn file_label(file: &gio::File) -> String {
+    if file.path().map_or(false, |path| path == glib::home_dir()) {
+        return String::from("Home");
+    }
+
+    match (file.parent(), file.basename()) {
+        (Some(_), Some(basename)) => basename.to_string_lossy().into_owned(),
+        _ => file.parse_name().into(),
+    }
+}
+
+/// Returns the file if it exists, or its nearest ancestor that does.
+pub fn existing_ancestor(file: gio::File) -> Option<gio::File> {
+    let mut file = Some(file);
+
+    while let Some(f) = file {
+        if f.query_exists(gio::Cancellable::NONE) {
+            return Some(f);
+        }
+
+        file = f.parent();
+    }
+
+    None
+}
+
 /// Format a [`GFile`](gio::File) as its URI for nicer [`Debug`] output.
 pub fn fmt_file_as_uri(file: &gio::File, f: &mut fmt::Formatter) -> fmt::Result {
     f.write_str(&file.uri())
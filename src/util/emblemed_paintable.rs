@@ -65,6 +65,29 @@ mod imp {
     }
 
     impl PaintableImpl for EmblemedPaintable {
+        // The size of the icon is used, so that thumbnails keep their aspect ratio.
+
+        fn intrinsic_width(&self) -> i32 {
+            self.icon
+                .borrow()
+                .as_ref()
+                .map_or(0, |icon| icon.intrinsic_width())
+        }
+
+        fn intrinsic_height(&self) -> i32 {
+            self.icon
+                .borrow()
+                .as_ref()
+                .map_or(0, |icon| icon.intrinsic_height())
+        }
+
+        fn intrinsic_aspect_ratio(&self) -> f64 {
+            self.icon
+                .borrow()
+                .as_ref()
+                .map_or(0.0, |icon| icon.intrinsic_aspect_ratio())
+        }
+
         fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
             self.icon
                 .borrow()
@@ -0,0 +1,99 @@
+use glib::Object;
+use relm4::adw;
+use relm4::gtk::{self, glib, prelude::*, subclass::prelude::*};
+
+glib::wrapper! {
+    /// A container whose natural width can be raised above the natural width of its child, without
+    /// raising its minimum width. Used to restore the widths of panes that the user can still
+    /// shrink afterwards.
+    pub struct NaturalWidthBin(ObjectSubclass<imp::NaturalWidthBin>)
+        @extends adw::Bin, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl NaturalWidthBin {
+    pub fn new() -> Self {
+        Object::new()
+    }
+
+    /// The width that the container requests if there is room, or 0 to request the natural width
+    /// of the child.
+    pub fn natural_width(&self) -> i32 {
+        self.imp().natural_width.get()
+    }
+
+    pub fn set_natural_width(&self, width: i32) {
+        self.imp().natural_width.set(width);
+        self.queue_resize();
+    }
+}
+
+impl Default for NaturalWidthBin {
+    fn default() -> Self {
+        NaturalWidthBin::new()
+    }
+}
+
+mod imp {
+    use std::cell::Cell;
+
+    use adw::{prelude::*, subclass::prelude::*};
+    use relm4::adw;
+    use relm4::gtk::{self, glib};
+
+    #[derive(Debug, Default)]
+    pub struct NaturalWidthBin {
+        pub(super) natural_width: Cell<i32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for NaturalWidthBin {
+        const NAME: &'static str = "NaturalWidthBin";
+        type Type = super::NaturalWidthBin;
+        type ParentType = adw::Bin;
+    }
+
+    impl ObjectImpl for NaturalWidthBin {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // The layout manager of `AdwBin` would take precedence over the size negotiation
+            // below.
+            self.obj().set_layout_manager(None::<gtk::LayoutManager>);
+        }
+    }
+
+    impl WidgetImpl for NaturalWidthBin {
+        fn request_mode(&self) -> gtk::SizeRequestMode {
+            self.obj()
+                .child()
+                .map_or(gtk::SizeRequestMode::ConstantSize, |child| {
+                    child.request_mode()
+                })
+        }
+
+        fn measure(&self, orientation: gtk::Orientation, for_size: i32) -> (i32, i32, i32, i32) {
+            let Some(child) = self.obj().child().filter(|child| child.should_layout()) else {
+                return (0, 0, -1, -1);
+            };
+
+            let (minimum, natural, minimum_baseline, natural_baseline) =
+                child.measure(orientation, for_size);
+
+            let natural = match orientation {
+                gtk::Orientation::Horizontal => natural.max(self.natural_width.get()),
+                _ => natural,
+            };
+
+            (minimum, natural, minimum_baseline, natural_baseline)
+        }
+
+        fn size_allocate(&self, width: i32, height: i32, baseline: i32) {
+            if let Some(child) = self.obj().child().filter(|child| child.should_layout()) {
+                child.allocate(width, height, baseline, None);
+            }
+        }
+    }
+
+    impl BinImpl for NaturalWidthBin {}
+}